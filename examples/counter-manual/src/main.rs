@@ -33,7 +33,10 @@ async fn handler(ws: WebSocketUpgrade) -> Response {
 
 async fn app(socket: WebSocket) {
     use futures_util::StreamExt;
-    let browser = wsdom::Browser::new();
+    // hold commands for a few milliseconds so they are sent in fewer frames
+    let browser = wsdom::Browser::new()
+        .with_timer(|d| Box::pin(tokio::time::sleep(d)) as wsdom::Sleep)
+        .with_max_delay(std::time::Duration::from_millis(5));
 
     // make a future that executes our app
     // this is the app part same as in the (non-manual) counter example
@@ -91,8 +94,12 @@ async fn app(socket: WebSocket) {
     let rx_fut = {
         let browser = browser.clone();
         async move {
-            while let Some(Ok(Message::Text(msg))) = rx.next().await {
-                browser.receive_incoming_message(msg);
+            while let Some(Ok(msg)) = rx.next().await {
+                match msg {
+                    Message::Text(msg) => browser.receive_incoming_message(msg),
+                    Message::Binary(frame) => browser.receive_incoming_binary(frame),
+                    _ => break,
+                }
             }
         }
     };
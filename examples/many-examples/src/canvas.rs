@@ -34,11 +34,14 @@ pub async fn app(browser: Browser) {
         btn.set_inner_text(&"draw");
         click_stream.next().await;
 
-        ctx.begin_path();
-        ctx.move_to(&75, &50);
-        ctx.line_to(&100, &75);
-        ctx.line_to(&100, &25);
-        ctx.fill(&wsdom::undefined());
+        // free the values these calls return all at once
+        browser.scope(|s| {
+            s.adopt(ctx.begin_path());
+            s.adopt(ctx.move_to(&75, &50));
+            s.adopt(ctx.line_to(&100, &75));
+            s.adopt(ctx.line_to(&100, &25));
+            s.adopt(ctx.fill(&wsdom::undefined()));
+        });
 
         btn.set_inner_text(&"clear");
         click_stream.next().await;
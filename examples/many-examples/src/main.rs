@@ -3,7 +3,7 @@ mod canvas;
 mod computation;
 mod counter;
 
-use std::future::Future;
+use std::{future::Future, time::Duration};
 
 use axum::{extract::WebSocketUpgrade, http::header, response::Html, routing::get, Router};
 use wsdom::Browser;
@@ -33,6 +33,8 @@ async fn main() {
     axum::serve(listener, router).await.unwrap();
 }
 
+const BATCH_DELAY: Duration = Duration::from_millis(5);
+
 fn make_one_demo<Func, Fut>(name: &str, func: Func) -> Router
 where
     Func: Clone + Send + 'static + Fn(Browser) -> Fut,
@@ -45,7 +47,9 @@ where
             "/ws",
             get(|wsu: WebSocketUpgrade| async move {
                 wsu.on_upgrade(|ws| async move {
-                    socket_to_browser(ws, func).await;
+                    // Send each step's commands in one frame.
+                    let batched = |browser: Browser| func(browser.with_max_delay(BATCH_DELAY));
+                    socket_to_browser(ws, batched).await;
                 })
             }),
         );
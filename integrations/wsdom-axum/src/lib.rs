@@ -1,6 +1,7 @@
 //! Integration code for conveniently using WSDOM with the Axum web framework.
 //!
-//! This library provides only one function: [socket_to_browser].
+//! This library provides one function, [socket_to_browser],
+//! and [Sessions] for clients that resume their session after reconnecting.
 
 use std::{
     pin::Pin,
@@ -12,13 +13,32 @@ use futures_util::{Future, Sink, Stream, StreamExt};
 use pin_project_lite::pin_project;
 use wsdom_core::Browser;
 
+mod sessions;
+use sessions::Resumable;
+pub use sessions::Sessions;
+
 pin_project! {
     /// Future type returned from [socket_to_browser].
+    ///
+    /// The Browser is [closed][Browser::close] when the socket closes or this Future is dropped,
+    /// so anything still waiting on it finishes.
+    /// In a resumable session (see [Sessions]), a closed socket is instead replaced by the client's next connection,
+    /// unless none arrives within the grace period.
     pub struct ToBrowserFuture<Fut: Future> {
-        #[pin] ws: WebSocket,
+        ws: Option<WebSocket>,
         #[pin] fut: Fut,
         browser: Browser,
-        output: Option<Fut::Output>
+        output: Option<Fut::Output>,
+        resumable: Option<Resumable>,
+    }
+    impl<Fut: Future> PinnedDrop for ToBrowserFuture<Fut> {
+        fn drop(this: Pin<&mut Self>) {
+            let this = this.project();
+            this.browser.close("WebSocket dropped");
+            if let Some(resumable) = this.resumable.take() {
+                resumable.forget();
+            }
+        }
     }
 }
 
@@ -29,64 +49,115 @@ where
     type Output = Output<Fut::Output>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let mut this = self.project();
-        match this.ws.as_mut().poll_next(cx) {
-            Poll::Ready(Some(Ok(Message::Text(message)))) => {
-                this.browser.receive_incoming_message(message);
-            }
-            Poll::Ready(None | Some(Ok(Message::Close(_)))) => {
-                return Poll::Ready(Output::ConnectionClosed);
-            }
-            Poll::Ready(Some(Ok(_))) => {}
-            Poll::Ready(Some(Err(e))) => {
-                return Poll::Ready(Output::AxumError(e));
-            }
-            Poll::Pending => {}
+        let this = self.project();
+        if let Some((ws, resume)) = this.resumable.as_mut().and_then(|r| r.poll_reconnect(cx)) {
+            *this.ws = Some(ws);
+            this.browser.receive_incoming_message(resume);
         }
-        match this.ws.as_mut().poll_ready(cx) {
-            Poll::Ready(Ok(_)) => {
-                match this.browser.poll_next_unpin(cx) {
-                    Poll::Ready(Some(message)) => {
-                        match this.ws.as_mut().start_send(Message::Text(message)) {
-                            Ok(_) => {}
-                            Err(e) => return Poll::Ready(Output::AxumError(e)),
-                        }
-                    }
-                    Poll::Ready(None) => {
-                        if let Some(err) = this.browser.take_error() {
-                            return Poll::Ready(Output::WsdomError(err));
-                        } else {
-                            return Poll::Pending;
-                        }
-                    }
-                    Poll::Pending => {}
+        match this.ws.as_mut() {
+            Some(ws) => match drive(ws, this.browser, this.output, cx) {
+                Poll::Ready(Output::ConnectionClosed | Output::AxumError(_))
+                    if this.resumable.is_some() =>
+                {
+                    // Keep the Browser for the client to resume; poll again to start the grace period.
+                    *this.ws = None;
+                    cx.waker().wake_by_ref();
+                    return Poll::Pending;
                 }
-                match this.ws.as_mut().poll_flush(cx) {
-                    Poll::Ready(Err(e)) => {
-                        return Poll::Ready(Output::AxumError(e));
+                Poll::Ready(output) => {
+                    match &output {
+                        Output::ConnectionClosed => this.browser.close("WebSocket closed"),
+                        Output::AxumError(_) => this.browser.close("WebSocket error"),
+                        _ => {}
                     }
-                    Poll::Ready(Ok(_)) => {
-                        if let Some(output) = this.output.take() {
-                            return Poll::Ready(Output::Done(output));
-                        }
-                    }
-                    _ => {}
+                    return Poll::Ready(output);
+                }
+                Poll::Pending => {}
+            },
+            None => {
+                if this.resumable.as_mut().is_some_and(|r| r.poll_expired(cx)) {
+                    this.browser.close("WebSocket closed");
+                    return Poll::Ready(Output::ConnectionClosed);
                 }
             }
-            Poll::Ready(Err(e)) => {
-                return Poll::Ready(Output::AxumError(e));
-            }
-            Poll::Pending => {}
         }
         if this.output.is_none() {
             if let Poll::Ready(t) = this.fut.poll(cx) {
                 *this.output = Some(t);
             }
         }
+        // Nothing is left to flush without a socket.
+        if this.ws.is_none() {
+            if let Some(output) = this.output.take() {
+                return Poll::Ready(Output::Done(output));
+            }
+        }
         Poll::Pending
     }
 }
 
+/// Exchange messages between the socket and the Browser.
+fn drive<T>(
+    ws: &mut WebSocket,
+    browser: &mut Browser,
+    output: &mut Option<T>,
+    cx: &mut Context<'_>,
+) -> Poll<Output<T>> {
+    let mut ws = Pin::new(ws);
+    match ws.as_mut().poll_next(cx) {
+        Poll::Ready(Some(Ok(Message::Text(message)))) => {
+            browser.receive_incoming_message(message);
+        }
+        Poll::Ready(Some(Ok(Message::Binary(frame)))) => {
+            browser.receive_incoming_binary(frame);
+        }
+        Poll::Ready(None | Some(Ok(Message::Close(_)))) => {
+            return Poll::Ready(Output::ConnectionClosed);
+        }
+        Poll::Ready(Some(Ok(_))) => {}
+        Poll::Ready(Some(Err(e))) => {
+            return Poll::Ready(Output::AxumError(e));
+        }
+        Poll::Pending => {}
+    }
+    match ws.as_mut().poll_ready(cx) {
+        Poll::Ready(Ok(_)) => {
+            match browser.poll_next_unpin(cx) {
+                Poll::Ready(Some(message)) => {
+                    match ws.as_mut().start_send(Message::Text(message)) {
+                        Ok(_) => {}
+                        Err(e) => return Poll::Ready(Output::AxumError(e)),
+                    }
+                }
+                Poll::Ready(None) => {
+                    if let Some(err) = browser.take_error() {
+                        return Poll::Ready(Output::WsdomError(err));
+                    } else {
+                        return Poll::Pending;
+                    }
+                }
+                Poll::Pending => {}
+            }
+            match ws.as_mut().poll_flush(cx) {
+                Poll::Ready(Err(e)) => {
+                    return Poll::Ready(Output::AxumError(e));
+                }
+                Poll::Ready(Ok(_)) => {
+                    if let Some(output) = output.take() {
+                        return Poll::Ready(Output::Done(output));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Poll::Ready(Err(e)) => {
+            return Poll::Ready(Output::AxumError(e));
+        }
+        Poll::Pending => {}
+    }
+    Poll::Pending
+}
+
 /// Output type of [ToBrowserFuture].
 pub enum Output<T> {
     /// The inner function (the second argument passed to `socket_to_browser`) completed with this result.
@@ -97,6 +168,9 @@ pub enum Output<T> {
     AxumError(axum::Error),
     /// WSDOM raised an error.
     WsdomError(wsdom_core::Error),
+    /// The client resumed an existing session.
+    /// The socket was handed over to the [Sessions::socket_to_browser] call that started the session.
+    Resumed,
 }
 
 /// Get a [Browser] from an [axum::WebSocket] object.
@@ -106,9 +180,11 @@ pub enum Output<T> {
 /// pass it to `socket_to_browser` along with an async function/closure that takes a Browser as argument,
 /// then await the returned Future.
 ///
+/// The Browser comes with a Tokio [timer][Browser::with_timer].
+///
 /// ```rust
 /// # use wsdom_core::Browser;
-/// use wsdom_axum::socket_to_browser;
+/// use px_wsdom_axum::socket_to_browser;
 /// use axum::extract::{WebSocketUpgrade, ws::WebSocket};
 /// use axum::response::Response;
 /// async fn axum_handler(wsu: WebSocketUpgrade) -> Response {
@@ -119,18 +195,27 @@ pub enum Output<T> {
 /// async fn app(browser: Browser) {
 ///     // do things...
 /// }
-/// ````
+/// ```
 #[must_use = "the return type is a Future and should be .awaited"]
 pub fn socket_to_browser<Func, Fut>(ws: WebSocket, f: Func) -> ToBrowserFuture<Fut>
 where
     Func: FnOnce(Browser) -> Fut,
     Fut: Future,
 {
-    let browser = Browser::new();
+    let browser = new_browser();
     ToBrowserFuture {
         fut: f(browser.clone()),
-        ws,
+        ws: Some(ws),
         browser,
         output: None,
+        resumable: None,
     }
 }
+
+/// A Browser with a Tokio [timer][Browser::with_timer], so timeouts and batching delays work,
+/// that waits for the [handshake][Browser::with_handshake] before sending anything.
+fn new_browser() -> Browser {
+    Browser::new()
+        .with_timer(|d| Box::pin(tokio::time::sleep(d)) as wsdom_core::Sleep)
+        .with_handshake(true)
+}
@@ -0,0 +1,174 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Waker},
+    time::Duration,
+};
+
+use axum::extract::ws::{Message, WebSocket};
+use futures_util::StreamExt;
+use wsdom_core::{session::Resume, Browser};
+
+use crate::{Output, ToBrowserFuture};
+
+/// Browsers of resumable sessions, so a client that reconnects gets its old Browser back.
+///
+/// The JS client must be connected through a `WSDOMTransport` with `resumable: true`.
+/// Each session is driven by the [socket_to_browser][Self::socket_to_browser] call that started it;
+/// when the socket closes, that call keeps the Browser (and the app function) alive for a grace period,
+/// waiting for the client to reconnect.
+///
+/// ```rust
+/// # use wsdom_core::Browser;
+/// use px_wsdom_axum::Sessions;
+/// use axum::extract::{State, WebSocketUpgrade, ws::WebSocket};
+/// use axum::response::Response;
+/// async fn axum_handler(State(sessions): State<Sessions>, wsu: WebSocketUpgrade) -> Response {
+///     wsu.on_upgrade(move |ws: WebSocket| async move {
+///         sessions.socket_to_browser(ws, app).await;
+///     })
+/// }
+/// async fn app(browser: Browser) {
+///     // do things...
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Sessions {
+    handoffs: Arc<Mutex<HashMap<String, Arc<Mutex<Handoff>>>>>,
+    grace: Duration,
+}
+
+impl Default for Sessions {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(30))
+    }
+}
+
+/// Where a resuming connection leaves its socket for the session's driver.
+#[derive(Default)]
+struct Handoff {
+    /// The new socket, and the resume frame it started with.
+    socket: Option<(WebSocket, String)>,
+    waker: Option<Waker>,
+}
+
+impl Sessions {
+    /// Keep each session's Browser for `grace` after its socket closes.
+    pub fn new(grace: Duration) -> Self {
+        Self {
+            handoffs: Default::default(),
+            grace,
+        }
+    }
+    /// Like [crate::socket_to_browser], but for resumable sessions.
+    ///
+    /// If the client is resuming a session, the socket is handed over to that session
+    /// and this returns [Output::Resumed] right away.
+    /// Otherwise this starts a new session, running `f` with its Browser.
+    pub async fn socket_to_browser<Func, Fut>(
+        &self,
+        mut ws: WebSocket,
+        f: Func,
+    ) -> Output<Fut::Output>
+    where
+        Func: FnOnce(Browser) -> Fut,
+        Fut: Future,
+    {
+        // Resumable clients always open with a resume frame.
+        let first = loop {
+            match ws.next().await {
+                Some(Ok(Message::Text(first))) => break first,
+                Some(Ok(Message::Close(_))) | None => return Output::ConnectionClosed,
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Output::AxumError(e),
+            }
+        };
+        let Some(resume) = Resume::parse(&first) else {
+            // Not a resumable client.
+            let browser = crate::new_browser();
+            browser.receive_incoming_message(first);
+            return crate::ToBrowserFuture {
+                fut: f(browser.clone()),
+                ws: Some(ws),
+                browser,
+                output: None,
+                resumable: None,
+            }
+            .await;
+        };
+        let existing = self.handoffs.lock().unwrap().get(resume.token).cloned();
+        if let Some(handoff) = existing {
+            let mut handoff = handoff.lock().unwrap();
+            handoff.socket = Some((ws, first));
+            if let Some(waker) = handoff.waker.take() {
+                waker.wake();
+            }
+            return Output::Resumed;
+        }
+
+        let token = new_token();
+        let browser = crate::new_browser().with_session(token.clone());
+        let handoff = Arc::new(Mutex::new(Handoff::default()));
+        self.handoffs
+            .lock()
+            .unwrap()
+            .insert(token.clone(), handoff.clone());
+        ToBrowserFuture {
+            fut: f(browser.clone()),
+            ws: Some(ws),
+            browser,
+            output: None,
+            resumable: Some(Resumable {
+                sessions: self.clone(),
+                token,
+                handoff,
+                expiry: None,
+            }),
+        }
+        .await
+    }
+}
+
+/// An unguessable session token.
+fn new_token() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).expect("no randomness for session token");
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// The resumable part of a [ToBrowserFuture].
+pub(crate) struct Resumable {
+    sessions: Sessions,
+    token: String,
+    handoff: Arc<Mutex<Handoff>>,
+    /// Started once the socket is lost.
+    expiry: Option<Pin<Box<tokio::time::Sleep>>>,
+}
+
+impl Resumable {
+    /// Take the socket of a connection that resumed this session.
+    pub(crate) fn poll_reconnect(&mut self, cx: &mut Context<'_>) -> Option<(WebSocket, String)> {
+        let mut handoff = self.handoff.lock().unwrap();
+        handoff.waker = Some(cx.waker().clone());
+        let socket = handoff.socket.take();
+        if socket.is_some() {
+            self.expiry = None;
+        }
+        socket
+    }
+    /// Whether the grace period since the socket was lost is over.
+    pub(crate) fn poll_expired(&mut self, cx: &mut Context<'_>) -> bool {
+        let grace = self.sessions.grace;
+        self.expiry
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(grace)))
+            .as_mut()
+            .poll(cx)
+            .is_ready()
+    }
+    /// Stop accepting connections for this session.
+    pub(crate) fn forget(self) {
+        self.sessions.handoffs.lock().unwrap().remove(&self.token);
+    }
+}
@@ -18,7 +18,15 @@ var __classPrivateFieldGet = (this && this.__classPrivateFieldGet) || function (
     if (typeof state === "function" ? receiver !== state || !f : !state.has(receiver)) throw new TypeError("Cannot read private member from an object whose class did not declare it");
     return kind === "m" ? f : kind === "a" ? f.call(receiver) : f ? f.value : state.get(receiver);
 };
-var _WSDOMTransport_instances, _WSDOMTransport_clientConstructor, _WSDOMTransport_args, _WSDOMTransport_options, _WSDOMTransport_wrappers, _WSDOMTransport_outbound, _WSDOMTransport_started, _WSDOMTransport_closed, _WSDOMTransport_wrappersStarted, _WSDOMTransport_webSocket, _WSDOMTransport_pollAbort, _WSDOMTransport_reconnectTimer, _WSDOMTransport_pollTimer, _WSDOMTransport_failures, _WSDOMTransport_sendFromClient, _WSDOMTransport_startWrappers, _WSDOMTransport_context, _WSDOMTransport_dispatchOutbound, _WSDOMTransport_dispatchInbound, _WSDOMTransport_connect, _WSDOMTransport_connectWebSocket, _WSDOMTransport_flushWebSocket, _WSDOMTransport_startPolling, _WSDOMTransport_poll, _WSDOMTransport_schedulePoll, _WSDOMTransport_handleFailure, _WSDOMTransport_scheduleReconnect, _WSDOMTransport_clearPollTimer, _WSDOMTransport_clearTimers, _WSDOMTransport_setStatus, _WSDOMTransport_reportError;
+var _WSDOMTransport_instances, _WSDOMTransport_clientConstructor, _WSDOMTransport_args, _WSDOMTransport_options, _WSDOMTransport_wrappers, _WSDOMTransport_outbound, _WSDOMTransport_started, _WSDOMTransport_closed, _WSDOMTransport_wrappersStarted, _WSDOMTransport_webSocket, _WSDOMTransport_pollAbort, _WSDOMTransport_reconnectTimer, _WSDOMTransport_pollTimer, _WSDOMTransport_failures, _WSDOMTransport_session, _WSDOMTransport_createClient, _WSDOMTransport_sendFromClient, _WSDOMTransport_resume, _WSDOMTransport_receive, _WSDOMTransport_acknowledge, _WSDOMTransport_scheduleAck, _WSDOMTransport_startWrappers, _WSDOMTransport_context, _WSDOMTransport_dispatchOutbound, _WSDOMTransport_dispatchInbound, _WSDOMTransport_connect, _WSDOMTransport_connectWebSocket, _WSDOMTransport_flushWebSocket, _WSDOMTransport_startPolling, _WSDOMTransport_poll, _WSDOMTransport_schedulePoll, _WSDOMTransport_handleFailure, _WSDOMTransport_scheduleReconnect, _WSDOMTransport_clearPollTimer, _WSDOMTransport_clearTimers, _WSDOMTransport_setStatus, _WSDOMTransport_reportError;
+/** Binary frames become `B<base64>` text messages on transports or in sessions that only carry text. */
+function base64(bytes) {
+    let binary = "";
+    for (let i = 0; i < bytes.length; i += 0x8000) {
+        binary += String.fromCharCode(...bytes.subarray(i, i + 0x8000));
+    }
+    return btoa(binary);
+}
 /**
  * Connects a sender-first generated WSDOM client to either a WebSocket or a
  * JSON-array long-poll endpoint. Transport selection is always explicit.
@@ -41,11 +49,14 @@ export class WSDOMTransport {
         _WSDOMTransport_reconnectTimer.set(this, void 0);
         _WSDOMTransport_pollTimer.set(this, void 0);
         _WSDOMTransport_failures.set(this, 0);
+        _WSDOMTransport_session.set(this, void 0);
         __classPrivateFieldSet(this, _WSDOMTransport_clientConstructor, ClientConstructor, "f");
         __classPrivateFieldSet(this, _WSDOMTransport_args, args, "f");
         __classPrivateFieldSet(this, _WSDOMTransport_options, options, "f");
         __classPrivateFieldSet(this, _WSDOMTransport_wrappers, (_a = options.wrappers) !== null && _a !== void 0 ? _a : [], "f");
-        this.client = new (__classPrivateFieldGet(this, _WSDOMTransport_clientConstructor, "f"))((message) => __classPrivateFieldGet(this, _WSDOMTransport_instances, "m", _WSDOMTransport_sendFromClient).call(this, message), ...__classPrivateFieldGet(this, _WSDOMTransport_args, "f"));
+        if (options.resumable)
+            __classPrivateFieldSet(this, _WSDOMTransport_session, { token: "", sent: 0, received: 0, unacked: [], resuming: false }, "f");
+        this.client = __classPrivateFieldGet(this, _WSDOMTransport_instances, "m", _WSDOMTransport_createClient).call(this);
     }
     /** Starts wrappers and the explicitly configured physical transport. */
     start() {
@@ -96,8 +107,91 @@ export class WSDOMTransport {
         });
     }
 }
-_WSDOMTransport_clientConstructor = new WeakMap(), _WSDOMTransport_args = new WeakMap(), _WSDOMTransport_options = new WeakMap(), _WSDOMTransport_wrappers = new WeakMap(), _WSDOMTransport_outbound = new WeakMap(), _WSDOMTransport_started = new WeakMap(), _WSDOMTransport_closed = new WeakMap(), _WSDOMTransport_wrappersStarted = new WeakMap(), _WSDOMTransport_webSocket = new WeakMap(), _WSDOMTransport_pollAbort = new WeakMap(), _WSDOMTransport_reconnectTimer = new WeakMap(), _WSDOMTransport_pollTimer = new WeakMap(), _WSDOMTransport_failures = new WeakMap(), _WSDOMTransport_instances = new WeakSet(), _WSDOMTransport_sendFromClient = function _WSDOMTransport_sendFromClient(message) {
+_WSDOMTransport_clientConstructor = new WeakMap(), _WSDOMTransport_args = new WeakMap(), _WSDOMTransport_options = new WeakMap(), _WSDOMTransport_wrappers = new WeakMap(), _WSDOMTransport_outbound = new WeakMap(), _WSDOMTransport_started = new WeakMap(), _WSDOMTransport_closed = new WeakMap(), _WSDOMTransport_wrappersStarted = new WeakMap(), _WSDOMTransport_webSocket = new WeakMap(), _WSDOMTransport_pollAbort = new WeakMap(), _WSDOMTransport_reconnectTimer = new WeakMap(), _WSDOMTransport_pollTimer = new WeakMap(), _WSDOMTransport_failures = new WeakMap(), _WSDOMTransport_session = new WeakMap(), _WSDOMTransport_instances = new WeakSet(), _WSDOMTransport_createClient = function _WSDOMTransport_createClient() {
+    return new (__classPrivateFieldGet(this, _WSDOMTransport_clientConstructor, "f"))((message) => __classPrivateFieldGet(this, _WSDOMTransport_instances, "m", _WSDOMTransport_sendFromClient).call(this, message), ...__classPrivateFieldGet(this, _WSDOMTransport_args, "f"));
+}, _WSDOMTransport_sendFromClient = function _WSDOMTransport_sendFromClient(message) {
+    if (typeof message !== "string") {
+        // Binary frames go out as they are only when nothing on the way needs text.
+        if (!__classPrivateFieldGet(this, _WSDOMTransport_session, "f") && __classPrivateFieldGet(this, _WSDOMTransport_wrappers, "f").length === 0 && __classPrivateFieldGet(this, _WSDOMTransport_options, "f").transport.kind === "websocket") {
+            __classPrivateFieldGet(this, _WSDOMTransport_outbound, "f").push(message);
+            __classPrivateFieldGet(this, _WSDOMTransport_instances, "m", _WSDOMTransport_flushWebSocket).call(this);
+            return;
+        }
+        message = `B${base64(message)}`;
+    }
+    const session = __classPrivateFieldGet(this, _WSDOMTransport_session, "f");
+    if (session) {
+        session.unacked.push([++session.sent, message]);
+        // A resume in progress replays it along with the rest.
+        if (session.resuming)
+            return;
+        message = `m${session.sent},${session.received}:${message}`;
+    }
     void __classPrivateFieldGet(this, _WSDOMTransport_instances, "m", _WSDOMTransport_dispatchOutbound).call(this, 0, message).catch((error) => __classPrivateFieldGet(this, _WSDOMTransport_instances, "m", _WSDOMTransport_reportError).call(this, error));
+}, _WSDOMTransport_resume = function _WSDOMTransport_resume() {
+    return __awaiter(this, void 0, void 0, function* () {
+        const session = __classPrivateFieldGet(this, _WSDOMTransport_session, "f");
+        if (!session)
+            return;
+        // Whatever was queued while disconnected is replayed from `unacked`.
+        __classPrivateFieldSet(this, _WSDOMTransport_outbound, [], "f");
+        session.resuming = true;
+        try {
+            yield __classPrivateFieldGet(this, _WSDOMTransport_instances, "m", _WSDOMTransport_dispatchOutbound).call(this, 0, `t${session.token}:${session.received}`);
+            for (let index = 0; index < session.unacked.length; index++) {
+                const [seq, message] = session.unacked[index];
+                yield __classPrivateFieldGet(this, _WSDOMTransport_instances, "m", _WSDOMTransport_dispatchOutbound).call(this, 0, `m${seq},${session.received}:${message}`);
+            }
+        }
+        finally {
+            session.resuming = false;
+        }
+    });
+}, _WSDOMTransport_receive = function _WSDOMTransport_receive(frame) {
+    return __awaiter(this, void 0, void 0, function* () {
+        const session = __classPrivateFieldGet(this, _WSDOMTransport_session, "f");
+        if (!session)
+            return this.client.handleIncomingMessage(frame);
+        if (frame.startsWith("t")) {
+            const token = frame.slice(1);
+            if (session.token !== "" && token !== session.token) {
+                // The server started over; the old heap and numbering are meaningless now.
+                Object.assign(session, { sent: 0, received: 0, unacked: [] });
+                this.client = __classPrivateFieldGet(this, _WSDOMTransport_instances, "m", _WSDOMTransport_createClient).call(this);
+            }
+            session.token = token;
+            return;
+        }
+        if (frame.startsWith("a")) {
+            __classPrivateFieldGet(this, _WSDOMTransport_instances, "m", _WSDOMTransport_acknowledge).call(this, Number(frame.slice(1)));
+            return;
+        }
+        const header = /^m(\d+),(\d+):/.exec(frame);
+        if (!header)
+            throw new TypeError("Malformed WSDOM session frame");
+        __classPrivateFieldGet(this, _WSDOMTransport_instances, "m", _WSDOMTransport_acknowledge).call(this, Number(header[2]));
+        __classPrivateFieldGet(this, _WSDOMTransport_instances, "m", _WSDOMTransport_scheduleAck).call(this);
+        // Frames already handled are replays; frames after a gap are replayed in order on the next resume.
+        if (Number(header[1]) !== session.received + 1)
+            return;
+        session.received++;
+        yield this.client.handleIncomingMessage(frame.slice(header[0].length));
+    });
+}, _WSDOMTransport_acknowledge = function _WSDOMTransport_acknowledge(ack) {
+    const unacked = __classPrivateFieldGet(this, _WSDOMTransport_session, "f").unacked;
+    while (unacked.length > 0 && unacked[0][0] <= ack)
+        unacked.shift();
+}, _WSDOMTransport_scheduleAck = function _WSDOMTransport_scheduleAck() {
+    const session = __classPrivateFieldGet(this, _WSDOMTransport_session, "f");
+    if (session.ackTimer)
+        return;
+    session.ackTimer = setTimeout(() => {
+        session.ackTimer = undefined;
+        // The resume frame carries the acknowledgement.
+        if (session.resuming)
+            return;
+        void __classPrivateFieldGet(this, _WSDOMTransport_instances, "m", _WSDOMTransport_dispatchOutbound).call(this, 0, `a${session.received}`).catch((error) => __classPrivateFieldGet(this, _WSDOMTransport_instances, "m", _WSDOMTransport_reportError).call(this, error));
+    }, 0);
 }, _WSDOMTransport_startWrappers = function _WSDOMTransport_startWrappers() {
     var _a, _b;
     return __awaiter(this, void 0, void 0, function* () {
@@ -132,7 +226,7 @@ _WSDOMTransport_clientConstructor = new WeakMap(), _WSDOMTransport_args = new We
 }, _WSDOMTransport_dispatchInbound = function _WSDOMTransport_dispatchInbound(index, message) {
     return __awaiter(this, void 0, void 0, function* () {
         if (index < 0) {
-            yield this.client.handleIncomingMessage(message);
+            yield __classPrivateFieldGet(this, _WSDOMTransport_instances, "m", _WSDOMTransport_receive).call(this, message);
             return;
         }
         const wrapper = __classPrivateFieldGet(this, _WSDOMTransport_wrappers, "f")[index];
@@ -165,7 +259,10 @@ _WSDOMTransport_clientConstructor = new WeakMap(), _WSDOMTransport_args = new We
             return;
         __classPrivateFieldSet(this, _WSDOMTransport_failures, 0, "f");
         __classPrivateFieldGet(this, _WSDOMTransport_instances, "m", _WSDOMTransport_setStatus).call(this, "open");
-        __classPrivateFieldGet(this, _WSDOMTransport_instances, "m", _WSDOMTransport_flushWebSocket).call(this);
+        if (__classPrivateFieldGet(this, _WSDOMTransport_session, "f"))
+            void __classPrivateFieldGet(this, _WSDOMTransport_instances, "m", _WSDOMTransport_resume).call(this).catch((error) => __classPrivateFieldGet(this, _WSDOMTransport_instances, "m", _WSDOMTransport_handleFailure).call(this, error));
+        else
+            __classPrivateFieldGet(this, _WSDOMTransport_instances, "m", _WSDOMTransport_flushWebSocket).call(this);
     };
     socket.onmessage = (event) => {
         if (socket !== __classPrivateFieldGet(this, _WSDOMTransport_webSocket, "f") || typeof event.data !== "string") {
@@ -203,7 +300,10 @@ _WSDOMTransport_clientConstructor = new WeakMap(), _WSDOMTransport_args = new We
     }
 }, _WSDOMTransport_startPolling = function _WSDOMTransport_startPolling() {
     __classPrivateFieldGet(this, _WSDOMTransport_instances, "m", _WSDOMTransport_setStatus).call(this, "open");
-    void __classPrivateFieldGet(this, _WSDOMTransport_instances, "m", _WSDOMTransport_poll).call(this);
+    if (__classPrivateFieldGet(this, _WSDOMTransport_session, "f"))
+        void __classPrivateFieldGet(this, _WSDOMTransport_instances, "m", _WSDOMTransport_resume).call(this).then(() => __classPrivateFieldGet(this, _WSDOMTransport_instances, "m", _WSDOMTransport_poll).call(this), (error) => __classPrivateFieldGet(this, _WSDOMTransport_instances, "m", _WSDOMTransport_handleFailure).call(this, error));
+    else
+        void __classPrivateFieldGet(this, _WSDOMTransport_instances, "m", _WSDOMTransport_poll).call(this);
 }, _WSDOMTransport_poll = function _WSDOMTransport_poll() {
     var _a, _b;
     return __awaiter(this, void 0, void 0, function* () {
@@ -272,9 +372,14 @@ _WSDOMTransport_clientConstructor = new WeakMap(), _WSDOMTransport_args = new We
         clearTimeout(__classPrivateFieldGet(this, _WSDOMTransport_pollTimer, "f"));
     __classPrivateFieldSet(this, _WSDOMTransport_pollTimer, undefined, "f");
 }, _WSDOMTransport_clearTimers = function _WSDOMTransport_clearTimers() {
+    var _a;
     if (__classPrivateFieldGet(this, _WSDOMTransport_reconnectTimer, "f"))
         clearTimeout(__classPrivateFieldGet(this, _WSDOMTransport_reconnectTimer, "f"));
     __classPrivateFieldSet(this, _WSDOMTransport_reconnectTimer, undefined, "f");
+    if ((_a = __classPrivateFieldGet(this, _WSDOMTransport_session, "f")) === null || _a === void 0 ? void 0 : _a.ackTimer)
+        clearTimeout(__classPrivateFieldGet(this, _WSDOMTransport_session, "f").ackTimer);
+    if (__classPrivateFieldGet(this, _WSDOMTransport_session, "f"))
+        __classPrivateFieldGet(this, _WSDOMTransport_session, "f").ackTimer = undefined;
     __classPrivateFieldGet(this, _WSDOMTransport_instances, "m", _WSDOMTransport_clearPollTimer).call(this);
 }, _WSDOMTransport_setStatus = function _WSDOMTransport_setStatus(status) {
     var _a, _b;
@@ -20,7 +20,7 @@ class FakeWebSocket {
 	static readonly CLOSED = 3;
 	static instances: FakeWebSocket[] = [];
 	readyState = FakeWebSocket.CONNECTING;
-	sent: string[] = [];
+	sent: (string | Uint8Array)[] = [];
 	onopen: ((event: Event) => void) | null = null;
 	onmessage: ((event: MessageEvent) => void) | null = null;
 	onclose: ((event: CloseEvent) => void) | null = null;
@@ -30,7 +30,7 @@ class FakeWebSocket {
 		FakeWebSocket.instances.push(this);
 	}
 
-	send(message: string): void {
+	send(message: string | Uint8Array): void {
 		if (this.readyState !== FakeWebSocket.OPEN) throw new Error("socket is not open");
 		this.sent.push(message);
 	}
@@ -53,7 +53,7 @@ class FakeWebSocket {
 class Client implements WSDOMClient {
 	readonly received: string[] = [];
 
-	constructor(readonly send: (message: string) => void, readonly label: string) {}
+	constructor(readonly send: (message: string | Uint8Array) => void, readonly label: string) {}
 
 	handleIncomingMessage(message: string): void {
 		this.received.push(message);
@@ -148,6 +148,69 @@ async function longPollTest(): Promise<void> {
 	equal(requests.length, requestCountAfterClose, "close stops future long-poll requests");
 }
 
+async function resumableSessionTest(): Promise<void> {
+	FakeWebSocket.instances = [];
+	(globalThis as unknown as { WebSocket: typeof WebSocket }).WebSocket = FakeWebSocket as unknown as typeof WebSocket;
+	const transport = new WSDOMTransport(Client, ["session-client"], {
+		transport: { kind: "websocket", url: "ws://example.test/ws" },
+		resumable: true,
+	});
+	await transport.start();
+	const first = FakeWebSocket.instances[0];
+	first.open();
+	await tick();
+	equal(first.sent[0], "t:0", "a new session starts with an empty resume frame");
+	first.message("tsecret");
+	first.message("m1,0:hello");
+	await new Promise<void>((resolve) => setTimeout(resolve, 5));
+	equal(transport.client.received.join(","), "hello", "session frames are unwrapped for the client");
+	equal(first.sent[1], "a1", "received frames are acknowledged");
+	transport.client.send("reply");
+	await tick();
+	equal(first.sent[2], "m1,1:reply", "client messages are numbered");
+	first.close();
+	await new Promise<void>((resolve) => setTimeout(resolve, 275));
+	const second = FakeWebSocket.instances[1];
+	second.open();
+	await tick();
+	equal(second.sent.join("|"), "tsecret:1|m1,1:reply", "reconnecting resumes and replays unacknowledged messages");
+	second.message("m1,1:hello");
+	second.message("m2,1:again");
+	await tick();
+	equal(transport.client.received.join(","), "hello,again", "replayed frames are handled once");
+	const oldClient = transport.client;
+	second.message("tother");
+	await tick();
+	assert(transport.client !== oldClient, "a session the server could not resume gets a fresh client");
+	await transport.close();
+}
+
+async function binaryFrameTest(): Promise<void> {
+	FakeWebSocket.instances = [];
+	(globalThis as unknown as { WebSocket: typeof WebSocket }).WebSocket = FakeWebSocket as unknown as typeof WebSocket;
+	const frame = new Uint8Array([99, 50, 58, 0, 255]);
+	const plain = new WSDOMTransport(Client, ["binary-client"], {
+		transport: { kind: "websocket", url: "ws://example.test/ws" },
+	});
+	await plain.start();
+	FakeWebSocket.instances[0].open();
+	plain.client.send(frame);
+	equal(FakeWebSocket.instances[0].sent[0], frame, "binary frames go out as they are");
+	await plain.close();
+	const session = new WSDOMTransport(Client, ["binary-session-client"], {
+		transport: { kind: "websocket", url: "ws://example.test/ws" },
+		resumable: true,
+	});
+	await session.start();
+	const socket = FakeWebSocket.instances[1];
+	socket.open();
+	await tick();
+	session.client.send(frame);
+	await tick();
+	equal(socket.sent[1], "m1,0:BYzI6AP8=", "sessions carry binary frames as base64 text");
+	await session.close();
+}
+
 async function missingInteractionTest(): Promise<void> {
 	const errors: unknown[] = [];
 	const transport = new WSDOMTransport(Client, ["interaction-client"], {
@@ -170,6 +233,8 @@ async function missingInteractionTest(): Promise<void> {
 async function main(): Promise<void> {
 	await websocketAndMiddlewareTest();
 	await longPollTest();
+	await resumableSessionTest();
+	await binaryFrameTest();
 	await missingInteractionTest();
 	console.log("transport tests passed");
 }
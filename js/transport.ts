@@ -5,7 +5,7 @@ export interface WSDOMClient {
 
 /** The sender-first constructor shape emitted by the WSDOM generator. */
 export type WSDOMConstructor<Client extends WSDOMClient, Args extends readonly unknown[]> = new (
-	sender: (message: string) => void,
+	sender: (message: string | Uint8Array) => void,
 	...args: Args
 ) => Client;
 
@@ -67,10 +67,37 @@ export interface ProtocolWrapper<InteractionRequest = unknown, InteractionResult
 export interface WSDOMTransportOptions<InteractionRequest = unknown, InteractionResult = unknown>
 	extends TransportLifecycle {
 	transport: TransportOptions;
+	/**
+	 * Number messages and resume the session after reconnecting, so nothing sent during a gap is lost
+	 * or handled twice. The Rust side must use a resumable session (`Browser::with_session`).
+	 */
+	resumable?: boolean;
 	wrappers?: readonly ProtocolWrapper<InteractionRequest, InteractionResult>[];
 	interact?: (request: InteractionRequest) => Awaitable<InteractionResult>;
 }
 
+/** Sequence state of a resumable session, kept across reconnects. */
+interface SessionState {
+	/** Empty until the server starts the session. */
+	token: string;
+	sent: number;
+	received: number;
+	/** Messages the server has not acknowledged, oldest first. */
+	unacked: [number, string][];
+	/** Set while the resume frame and replays are being sent on a new connection. */
+	resuming: boolean;
+	ackTimer?: ReturnType<typeof setTimeout>;
+}
+
+/** Binary frames become `B<base64>` text messages on transports or in sessions that only carry text. */
+function base64(bytes: Uint8Array): string {
+	let binary = "";
+	for (let i = 0; i < bytes.length; i += 0x8000) {
+		binary += String.fromCharCode(...bytes.subarray(i, i + 0x8000));
+	}
+	return btoa(binary);
+}
+
 /**
  * Connects a sender-first generated WSDOM client to either a WebSocket or a
  * JSON-array long-poll endpoint. Transport selection is always explicit.
@@ -81,14 +108,15 @@ export class WSDOMTransport<
 	InteractionRequest = unknown,
 	InteractionResult = unknown,
 > {
-	public readonly client: Client;
+	/** The generated client. Replaced by a fresh one if a resumable session could not be resumed. */
+	public client: Client;
 	public status: TransportStatus = "idle";
 
 	readonly #clientConstructor: WSDOMConstructor<Client, Args>;
 	readonly #args: Args;
 	readonly #options: WSDOMTransportOptions<InteractionRequest, InteractionResult>;
 	readonly #wrappers: readonly ProtocolWrapper<InteractionRequest, InteractionResult>[];
-	#outbound: string[] = [];
+	#outbound: (string | Uint8Array)[] = [];
 	#started = false;
 	#closed = false;
 	#wrappersStarted = false;
@@ -97,6 +125,7 @@ export class WSDOMTransport<
 	#reconnectTimer?: ReturnType<typeof setTimeout>;
 	#pollTimer?: ReturnType<typeof setTimeout>;
 	#failures = 0;
+	readonly #session?: SessionState;
 
 	constructor(
 		ClientConstructor: WSDOMConstructor<Client, Args>,
@@ -107,7 +136,12 @@ export class WSDOMTransport<
 		this.#args = args;
 		this.#options = options;
 		this.#wrappers = options.wrappers ?? [];
-		this.client = new this.#clientConstructor((message) => this.#sendFromClient(message), ...this.#args);
+		if (options.resumable) this.#session = { token: "", sent: 0, received: 0, unacked: [], resuming: false };
+		this.client = this.#createClient();
+	}
+
+	#createClient(): Client {
+		return new this.#clientConstructor((message) => this.#sendFromClient(message), ...this.#args);
 	}
 
 	/** Starts wrappers and the explicitly configured physical transport. */
@@ -148,10 +182,88 @@ export class WSDOMTransport<
 		}
 	}
 
-	#sendFromClient(message: string): void {
+	#sendFromClient(message: string | Uint8Array): void {
+		if (typeof message !== "string") {
+			// Binary frames go out as they are only when nothing on the way needs text.
+			if (!this.#session && this.#wrappers.length === 0 && this.#options.transport.kind === "websocket") {
+				this.#outbound.push(message);
+				this.#flushWebSocket();
+				return;
+			}
+			message = `B${base64(message)}`;
+		}
+		const session = this.#session;
+		if (session) {
+			session.unacked.push([++session.sent, message]);
+			// A resume in progress replays it along with the rest.
+			if (session.resuming) return;
+			message = `m${session.sent},${session.received}:${message}`;
+		}
 		void this.#dispatchOutbound(0, message).catch((error) => this.#reportError(error));
 	}
 
+	/** Sends the resume frame and replays unacknowledged messages, first thing on each connection. */
+	async #resume(): Promise<void> {
+		const session = this.#session;
+		if (!session) return;
+		// Whatever was queued while disconnected is replayed from `unacked`.
+		this.#outbound = [];
+		session.resuming = true;
+		try {
+			await this.#dispatchOutbound(0, `t${session.token}:${session.received}`);
+			for (let index = 0; index < session.unacked.length; index++) {
+				const [seq, message] = session.unacked[index];
+				await this.#dispatchOutbound(0, `m${seq},${session.received}:${message}`);
+			}
+		} finally {
+			session.resuming = false;
+		}
+	}
+
+	/** Unwraps a session frame, handing new messages to the client exactly once. */
+	async #receive(frame: string): Promise<void> {
+		const session = this.#session;
+		if (!session) return this.client.handleIncomingMessage(frame);
+		if (frame.startsWith("t")) {
+			const token = frame.slice(1);
+			if (session.token !== "" && token !== session.token) {
+				// The server started over; the old heap and numbering are meaningless now.
+				Object.assign(session, { sent: 0, received: 0, unacked: [] });
+				this.client = this.#createClient();
+			}
+			session.token = token;
+			return;
+		}
+		if (frame.startsWith("a")) {
+			this.#acknowledge(Number(frame.slice(1)));
+			return;
+		}
+		const header = /^m(\d+),(\d+):/.exec(frame);
+		if (!header) throw new TypeError("Malformed WSDOM session frame");
+		this.#acknowledge(Number(header[2]));
+		this.#scheduleAck();
+		// Frames already handled are replays; frames after a gap are replayed in order on the next resume.
+		if (Number(header[1]) !== session.received + 1) return;
+		session.received++;
+		await this.client.handleIncomingMessage(frame.slice(header[0].length));
+	}
+
+	#acknowledge(ack: number): void {
+		const unacked = this.#session!.unacked;
+		while (unacked.length > 0 && unacked[0][0] <= ack) unacked.shift();
+	}
+
+	#scheduleAck(): void {
+		const session = this.#session!;
+		if (session.ackTimer) return;
+		session.ackTimer = setTimeout(() => {
+			session.ackTimer = undefined;
+			// The resume frame carries the acknowledgement.
+			if (session.resuming) return;
+			void this.#dispatchOutbound(0, `a${session.received}`).catch((error) => this.#reportError(error));
+		}, 0);
+	}
+
 	async #startWrappers(): Promise<void> {
 		for (let index = 0; index < this.#wrappers.length; index++) {
 			await this.#wrappers[index].start?.(this.#context(index));
@@ -183,7 +295,7 @@ export class WSDOMTransport<
 
 	async #dispatchInbound(index: number, message: string): Promise<void> {
 		if (index < 0) {
-			await this.client.handleIncomingMessage(message);
+			await this.#receive(message);
 			return;
 		}
 		const wrapper = this.#wrappers[index];
@@ -212,7 +324,8 @@ export class WSDOMTransport<
 			if (socket !== this.#webSocket || this.#closed) return;
 			this.#failures = 0;
 			this.#setStatus("open");
-			this.#flushWebSocket();
+			if (this.#session) void this.#resume().catch((error) => this.#handleFailure(error));
+			else this.#flushWebSocket();
 		};
 		socket.onmessage = (event: MessageEvent<unknown>) => {
 			if (socket !== this.#webSocket || typeof event.data !== "string") {
@@ -248,7 +361,8 @@ export class WSDOMTransport<
 
 	#startPolling(): void {
 		this.#setStatus("open");
-		void this.#poll();
+		if (this.#session) void this.#resume().then(() => this.#poll(), (error) => this.#handleFailure(error));
+		else void this.#poll();
 	}
 
 	async #poll(): Promise<void> {
@@ -320,6 +434,8 @@ export class WSDOMTransport<
 	#clearTimers(): void {
 		if (this.#reconnectTimer) clearTimeout(this.#reconnectTimer);
 		this.#reconnectTimer = undefined;
+		if (this.#session?.ackTimer) clearTimeout(this.#session.ackTimer);
+		if (this.#session) this.#session.ackTimer = undefined;
 		this.#clearPollTimer();
 	}
 
@@ -0,0 +1,176 @@
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use alloc::borrow::ToOwned;
+
+use crate::link::{Browser, BrowserInternal, Error};
+
+/// What a Browser does when its outgoing buffer reaches the limit set with [Browser::with_buffer_limit].
+///
+/// Commands are sent synchronously, so the Browser cannot hold up the code sending them.
+/// Producers that may outrun the client should await [Browser::ready] between batches of commands.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// [Browser::ready] waits until the transport has drained the buffer below the limit.
+    /// Commands sent without awaiting it are still buffered.
+    #[default]
+    Block,
+    /// [Browser::ready] fails with [Error::BufferFull] instead of waiting.
+    /// Commands sent without awaiting it are still buffered.
+    Error,
+    /// A command that takes the buffer past the limit kills the Browser with [Error::BufferFull].
+    /// [Browser::ready] waits like with [Overflow::Block], so producers that await it are not killed.
+    Kill,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct BufferLimit {
+    bytes: usize,
+    overflow: Overflow,
+}
+
+impl Browser {
+    /// Limit how much the Browser buffers for the JS client before producers are held back.
+    ///
+    /// `bytes` counts commands not yet taken from the Browser's [Stream][futures_core::Stream],
+    /// plus, with a [session][Self::with_session], commands the client has not acknowledged.
+    /// What happens at the limit depends on `overflow`.
+    ///
+    /// ```rust
+    /// # use px_wsdom_core::{Browser, Overflow};
+    /// let browser = Browser::new().with_buffer_limit(1 << 20, Overflow::Block);
+    /// ```
+    pub fn with_buffer_limit(self, bytes: usize, overflow: Overflow) -> Self {
+        self.0.lock().buffer_limit = Some(BufferLimit { bytes, overflow });
+        self
+    }
+    /// Wait until the outgoing buffer is below the [limit][Self::with_buffer_limit].
+    ///
+    /// Resolves right away if there is no limit, or if the Browser is dead (commands sent to a dead Browser are discarded).
+    ///
+    /// ```rust
+    /// # use px_wsdom_core::{Browser, Error};
+    /// # use px_wsdom_core::js_types::JsNumber;
+    /// async fn example(browser: &Browser) -> Result<(), Error> {
+    ///     for i in 0..1_000_000 {
+    ///         browser.ready().await?;
+    ///         browser.new_value::<JsNumber>(&i);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn ready(&self) -> Ready {
+        Ready {
+            browser: self.to_owned(),
+        }
+    }
+    /// The polling version of [ready][Self::ready].
+    pub fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        let mut link = self.0.lock();
+        let Some(limit) = link.buffer_limit else {
+            return Poll::Ready(Ok(()));
+        };
+        if link.is_dead() || link.buffered_len() < limit.bytes {
+            return Poll::Ready(Ok(()));
+        }
+        match limit.overflow {
+            Overflow::Error => Poll::Ready(Err(Error::BufferFull(limit.bytes))),
+            Overflow::Block | Overflow::Kill => {
+                link.ready_wakers.push(cx.waker().to_owned());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// A [Future] that resolves once the Browser can take more commands. Created by [Browser::ready].
+pub struct Ready {
+    browser: Browser,
+}
+
+impl Future for Ready {
+    type Output = Result<(), Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.browser.poll_ready(cx)
+    }
+}
+
+impl BrowserInternal {
+    /// Bytes waiting to be sent or acknowledged.
+    pub(crate) fn buffered_len(&self) -> usize {
+        self.commands_buf.len()
+            + self.pending_deletes_len()
+            + self.session.as_ref().map_or(0, |s| s.unacked_len())
+    }
+    /// Kill the Browser if it is past the limit under [Overflow::Kill].
+    pub(crate) fn check_buffer_limit(&mut self) {
+        if let Some(BufferLimit {
+            bytes,
+            overflow: Overflow::Kill,
+        }) = self.buffer_limit
+        {
+            if self.buffered_len() > bytes {
+                self.kill(Error::BufferFull(bytes));
+            }
+        }
+    }
+    /// Wake producers waiting in [Browser::ready] if the buffer has drained below the limit.
+    pub(crate) fn wake_ready(&mut self) {
+        let below = self
+            .buffer_limit
+            .is_none_or(|limit| self.buffered_len() < limit.bytes);
+        if below || self.is_dead() {
+            for waker in self.ready_wakers.drain(..) {
+                waker.wake();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::task::{Context, Poll};
+    use futures_util::{FutureExt, StreamExt, task::noop_waker_ref};
+
+    use super::Overflow;
+    use crate::{Browser, Error};
+
+    #[test]
+    fn ready_waits_for_the_transport() {
+        let mut browser = Browser::new().with_buffer_limit(16, Overflow::Block);
+        let mut cx = Context::from_waker(noop_waker_ref());
+        assert!(matches!(browser.poll_ready(&mut cx), Poll::Ready(Ok(()))));
+        browser.run_raw_code(format_args!("somethingLong()"));
+        let mut ready = browser.ready();
+        assert!(ready.poll_unpin(&mut cx).is_pending());
+        assert!(browser.poll_next_unpin(&mut cx).is_ready());
+        assert!(matches!(ready.poll_unpin(&mut cx), Poll::Ready(Ok(()))));
+        assert!(browser.take_error().is_none());
+    }
+
+    #[test]
+    fn overflow_errors_or_kills() {
+        let browser = Browser::new().with_buffer_limit(16, Overflow::Error);
+        browser.run_raw_code(format_args!("somethingLong()"));
+        let mut cx = Context::from_waker(noop_waker_ref());
+        assert!(matches!(
+            browser.poll_ready(&mut cx),
+            Poll::Ready(Err(Error::BufferFull(16)))
+        ));
+        assert!(!browser.is_closed());
+
+        let mut browser = Browser::new().with_buffer_limit(16, Overflow::Kill);
+        browser.run_raw_code(format_args!("short()"));
+        assert!(!browser.is_closed());
+        browser.run_raw_code(format_args!("somethingLong()"));
+        assert!(matches!(browser.take_error(), Some(Error::BufferFull(16))));
+        // Nothing more is buffered for a dead Browser.
+        browser.run_raw_code(format_args!("more()"));
+        assert_eq!(browser.0.lock().buffered_len(), 0);
+        assert_eq!(browser.poll_next_unpin(&mut cx), Poll::Ready(None));
+    }
+}
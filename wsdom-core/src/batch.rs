@@ -0,0 +1,230 @@
+use core::{future::Future, marker::PhantomData, pin::Pin, task::Poll, time::Duration};
+
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+use serde::de::DeserializeOwned;
+
+use crate::{
+    js::{immediates::array::ArrayOf, value::JsValue},
+    link::Browser,
+    retrieve::{RetrieveError, RetrieveFuture},
+    serialize::UseInJsCode,
+    tagged::{self, Raw},
+};
+
+impl Browser {
+    /// Retrieve several values in one roundtrip, deserialized together as `T`.
+    ///
+    /// The values are gathered into one JS array, so `T` is typically a tuple,
+    /// a [Vec](alloc::vec::Vec), or a struct whose fields are in the same order as the values.
+    /// If computing any of the values threw, this fails with the first exception.
+    ///
+    /// ```rust
+    /// # use px_wsdom_core::{Browser, UseInJsCode, js_types::JsValue};
+    /// async fn example(browser: &Browser, width: &JsValue, height: &JsValue, title: &JsValue) {
+    ///     let (width, height, title): (f64, f64, String) = browser
+    ///         .retrieve_many([width as &dyn UseInJsCode, height, title])
+    ///         .await
+    ///         .unwrap();
+    /// }
+    /// ```
+    #[track_caller]
+    pub fn retrieve_many<'a, T: DeserializeOwned>(
+        &'a self,
+        values: impl IntoIterator<Item = &'a dyn UseInJsCode>,
+    ) -> RetrieveMany<'a, T> {
+        let list = self.new_array(ArrayOf(values.into_iter().collect()));
+        RetrieveMany {
+            inner: RetrieveFuture::new(list.id, self),
+            _list: list,
+        }
+    }
+    /// Retrieve a snapshot of several properties of `object` in one roundtrip, deserialized as `T`.
+    ///
+    /// The properties are read at the same point in the command stream, and handed to `T`
+    /// as a map from property name to value, so `T` is typically a struct with the same field names.
+    /// Missing properties are `null`. If the properties do not deserialize as `T`,
+    /// this fails with [RetrieveError::Deserialize].
+    ///
+    /// ```rust
+    /// # use px_wsdom_core::{Browser, js_types::JsValue};
+    /// #[derive(serde::Deserialize)]
+    /// struct Scroll {
+    ///     #[serde(rename = "scrollX")]
+    ///     x: f64,
+    ///     #[serde(rename = "scrollY")]
+    ///     y: f64,
+    /// }
+    /// async fn example(browser: &Browser, window: &JsValue) {
+    ///     let scroll: Scroll = browser
+    ///         .retrieve_fields(window, ["scrollX", "scrollY"])
+    ///         .await
+    ///         .unwrap();
+    /// }
+    /// ```
+    #[track_caller]
+    pub fn retrieve_fields<'a, 'f, T: DeserializeOwned>(
+        &'a self,
+        object: &dyn UseInJsCode,
+        fields: impl IntoIterator<Item = &'f str>,
+    ) -> RetrieveFields<'a, T> {
+        let fields: Vec<String> = fields.into_iter().map(ToOwned::to_owned).collect();
+        let values: Vec<JsValue> = fields
+            .iter()
+            .map(|field| self.get_field(object, &field.as_str()))
+            .collect();
+        let list = self.new_array(ArrayOf(
+            values.iter().map(|v| v as &dyn UseInJsCode).collect(),
+        ));
+        RetrieveFields {
+            inner: RetrieveFuture::new(list.id, self),
+            fields,
+            _list: list,
+            _phantom: PhantomData,
+        }
+    }
+    #[track_caller]
+    fn new_array(&self, items: ArrayOf<'_>) -> JsValue {
+        let mut link = self.0.lock();
+        let out_id = link.get_new_value_id();
+        link.emit_value(out_id, &items);
+        link.wake_outgoing_lazy();
+        JsValue {
+            id: out_id,
+            browser: self.to_owned(),
+        }
+    }
+}
+
+/// A [Future] for several values retrieved together. Created by [Browser::retrieve_many].
+pub struct RetrieveMany<'a, T: DeserializeOwned> {
+    inner: RetrieveFuture<'a, T>,
+    // Dropped after `inner`, so the array is not deleted before the reply is requested.
+    _list: JsValue,
+}
+
+impl<T: DeserializeOwned> RetrieveMany<'_, T> {
+    /// See [RetrieveFuture::timeout].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.inner = self.inner.timeout(timeout);
+        self
+    }
+}
+
+impl<T: DeserializeOwned> Future for RetrieveMany<'_, T> {
+    type Output = Result<T, RetrieveError>;
+    fn poll(self: Pin<&mut Self>, cx: &mut core::task::Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.get_mut().inner).poll(cx)
+    }
+}
+
+/// A [Future] for a snapshot of several properties of an object. Created by [Browser::retrieve_fields].
+pub struct RetrieveFields<'a, T> {
+    inner: RetrieveFuture<'a, Vec<Raw>>,
+    fields: Vec<String>,
+    // Dropped after `inner`, so the array is not deleted before the reply is requested.
+    _list: JsValue,
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> RetrieveFields<'_, T> {
+    /// See [RetrieveFuture::timeout].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.inner = self.inner.timeout(timeout);
+        self
+    }
+}
+
+impl<T: DeserializeOwned> Future for RetrieveFields<'_, T> {
+    type Output = Result<T, RetrieveError>;
+    fn poll(self: Pin<&mut Self>, cx: &mut core::task::Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let values = match Pin::new(&mut this.inner).poll(cx) {
+            Poll::Ready(Ok(values)) => values,
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            Poll::Pending => return Poll::Pending,
+        };
+        let object = core::mem::take(&mut this.fields)
+            .into_iter()
+            .zip(values.into_iter().map(|Raw(value)| value))
+            .collect();
+        // Escaped, in case one of the fields is named `$`.
+        let object = serde_json::json!({ "$": "o", "v": serde_json::Value::Object(object) });
+        match tagged::from_value(&object) {
+            Ok(value) => Poll::Ready(Ok(value)),
+            Err(e) => Poll::Ready(Err(RetrieveError::Deserialize(e))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::task::{Context, Poll};
+    use futures_util::{FutureExt, task::noop_waker_ref};
+
+    use crate::{Browser, RetrieveError, serialize::UseInJsCode, test_support::sent};
+
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct Point {
+        x: f64,
+        y: f64,
+    }
+
+    /// Take the next frame, check it holds a single reply, and answer it with `json`.
+    fn reply(browser: &mut Browser, json: &str) -> alloc::string::String {
+        let frame = sent(browser);
+        assert_eq!(frame.matches("_w.r(").count(), 1);
+        let ret_id = frame
+            .rsplit("_w.r(")
+            .next()
+            .unwrap()
+            .split(',')
+            .next()
+            .unwrap();
+        browser.receive_incoming_message(alloc::format!("p{ret_id}:{json}"));
+        frame
+    }
+
+    #[test]
+    fn many_values_in_one_reply() {
+        let mut browser = Browser::new();
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let handle = browser.clone();
+        let a = browser.call_function("a", [], false);
+        let b = browser.call_function("b", [], false);
+        let c = browser.call_function("c", [], false);
+        let mut retrieval =
+            handle.retrieve_many::<(f64, bool, Point)>([&a as &dyn UseInJsCode, &b, &c]);
+        assert!(retrieval.poll_unpin(&mut cx).is_pending());
+        let frame = reply(&mut browser, r#"{"value":[1,true,[2,3]]}"#);
+        assert!(frame.contains(r#"[_w.g(2),_w.g(3),_w.g(4),]"#));
+        assert!(matches!(
+            retrieval.poll_unpin(&mut cx),
+            Poll::Ready(Ok((1.0, true, Point { x: 2.0, y: 3.0 })))
+        ));
+    }
+
+    #[test]
+    fn fields_by_name() {
+        let mut browser = Browser::new();
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let handle = browser.clone();
+        let object = browser.call_function("o", [], false);
+        let mut retrieval = handle.retrieve_fields::<Point>(&object, ["y", "x"]);
+        assert!(retrieval.poll_unpin(&mut cx).is_pending());
+        let frame = reply(&mut browser, r#"{"value":[4,5]}"#);
+        assert!(frame.contains(r#"(_w.g(2))["y"]"#));
+        assert!(matches!(
+            retrieval.poll_unpin(&mut cx),
+            Poll::Ready(Ok(Point { x: 5.0, y: 4.0 }))
+        ));
+
+        let mut retrieval = handle.retrieve_fields::<Point>(&object, ["x"]);
+        assert!(retrieval.poll_unpin(&mut cx).is_pending());
+        reply(&mut browser, r#"{"value":[1]}"#);
+        assert!(matches!(
+            retrieval.poll_unpin(&mut cx),
+            Poll::Ready(Err(RetrieveError::Deserialize(_)))
+        ));
+        assert!(browser.take_error().is_none());
+    }
+}
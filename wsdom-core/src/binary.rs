@@ -0,0 +1,443 @@
+use alloc::{borrow::ToOwned, collections::VecDeque, vec::Vec};
+use core::{
+    future::Future,
+    panic::Location,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use base64::Engine;
+use bytes::Bytes;
+
+use crate::{
+    js::value::JsValue,
+    link::{Browser, BrowserInternal},
+    retrieve::RetrieveError,
+    timer::Deadline,
+};
+
+/// What the JS client makes of uploaded bytes.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum BinaryKind<'a> {
+    Uint8Array,
+    ArrayBuffer,
+    Blob { mime_type: &'a str },
+}
+
+impl Browser {
+    /// Upload `bytes` into a new `Uint8Array` on the JS side.
+    ///
+    /// The bytes travel base64-encoded in a single command, which is far smaller and faster to decode than
+    /// a `Vec<u8>` sent through [SerdeToJs][crate::SerdeToJs] as an array of numbers.
+    ///
+    /// ```rust
+    /// # use px_wsdom_core::Browser;
+    /// fn example(browser: &Browser, pixels: &[u8]) {
+    ///     let array = browser.new_uint8array(pixels);
+    ///     let _clamped = browser.call_constructor("Uint8ClampedArray", [&array as &_], false);
+    /// }
+    /// ```
+    #[track_caller]
+    pub fn new_uint8array(&self, bytes: &[u8]) -> JsValue {
+        self.new_binary(bytes, BinaryKind::Uint8Array)
+    }
+    /// Upload `bytes` into a new `ArrayBuffer` on the JS side. See [new_uint8array][Self::new_uint8array].
+    #[track_caller]
+    pub fn new_array_buffer(&self, bytes: &[u8]) -> JsValue {
+        self.new_binary(bytes, BinaryKind::ArrayBuffer)
+    }
+    /// Upload `bytes` into a new `Blob` of MIME type `mime_type` on the JS side,
+    /// ready for `URL.createObjectURL` or a `File` input. See [new_uint8array][Self::new_uint8array].
+    ///
+    /// ```rust
+    /// # use px_wsdom_core::Browser;
+    /// fn example(browser: &Browser, png: &[u8]) {
+    ///     let blob = browser.new_blob(png, "image/png");
+    ///     let _url = browser.call_function("URL.createObjectURL", [&blob as &_], false);
+    /// }
+    /// ```
+    #[track_caller]
+    pub fn new_blob(&self, bytes: &[u8], mime_type: &str) -> JsValue {
+        self.new_binary(bytes, BinaryKind::Blob { mime_type })
+    }
+    #[track_caller]
+    fn new_binary(&self, bytes: &[u8], kind: BinaryKind<'_>) -> JsValue {
+        let base64 = base64::engine::general_purpose::STANDARD.encode(bytes);
+        let mut link = self.0.lock();
+        let out_id = link.get_new_value_id();
+        link.emit_bytes(out_id, &base64, kind);
+        link.wake_outgoing_lazy();
+        JsValue {
+            id: out_id,
+            browser: self.to_owned(),
+        }
+    }
+}
+
+impl JsValue {
+    /// Retrieve the bytes of this `ArrayBuffer`, typed array, `DataView`, `Blob` (or `File`), or `ImageData`.
+    ///
+    /// The bytes come back as binary frames rather than through JSON.
+    /// Any other kind of value fails with [RetrieveError::Thrown], holding a `TypeError`.
+    ///
+    /// ```rust
+    /// # use px_wsdom_core::js_types::JsValue;
+    /// async fn example(file: &JsValue) {
+    ///     let contents: Vec<u8> = file.retrieve_bytes().await.unwrap();
+    /// }
+    /// ```
+    pub fn retrieve_bytes(&self) -> RetrieveBytes<'_> {
+        RetrieveBytes {
+            stream: self.retrieve_byte_stream(),
+            bytes: Vec::new(),
+        }
+    }
+    /// Like [retrieve_bytes][Self::retrieve_bytes], but yield the bytes chunk by chunk as they arrive,
+    /// without holding all of a large `Blob` in memory at once.
+    ///
+    /// The JS client sends a few chunks ahead, then waits for the stream to yield them before sending more,
+    /// so a slow consumer does not pile up the rest of the bytes on the Rust side.
+    ///
+    /// The stream ends after the last chunk, or after the first error.
+    ///
+    /// ```rust
+    /// # use px_wsdom_core::js_types::JsValue;
+    /// use futures_util::StreamExt;
+    /// async fn example(blob: &JsValue) {
+    ///     let mut chunks = blob.retrieve_byte_stream();
+    ///     while let Some(chunk) = chunks.next().await {
+    ///         let _chunk: bytes::Bytes = chunk.unwrap();
+    ///     }
+    /// }
+    /// ```
+    pub fn retrieve_byte_stream(&self) -> ByteStream<'_> {
+        let timeout = self.browser.0.lock().default_timeout;
+        ByteStream {
+            id: self.id,
+            ret_id: 0,
+            done: false,
+            browser: &self.browser,
+            deadline: Deadline::new(timeout),
+        }
+    }
+}
+
+/// How many chunks (of up to a megabyte) the JS client may send ahead of what a [ByteStream] has yielded.
+const WINDOW: u32 = 4;
+
+/// Where the chunks of a [ByteStream] wait to be taken.
+#[derive(Debug)]
+pub(crate) struct ByteStreamState {
+    waker: Waker,
+    chunks: VecDeque<Bytes>,
+    /// Set when the JS client has sent everything: `Ok`, or the slot of the exception it threw.
+    end: Option<Result<(), u64>>,
+}
+
+impl ByteStreamState {
+    pub(crate) fn wake(&self) {
+        self.waker.wake_by_ref();
+    }
+}
+
+/// A [Stream][futures_core::Stream] of the bytes of a JS value, in chunks. Created by [JsValue::retrieve_byte_stream].
+///
+/// Like retrievals, it fails with [RetrieveError::Disconnected] if the Browser is closed,
+/// and can be given a [timeout][Self::timeout].
+pub struct ByteStream<'a> {
+    id: u64,
+    ret_id: u64,
+    done: bool,
+    browser: &'a Browser,
+    deadline: Deadline,
+}
+
+impl ByteStream<'_> {
+    /// Fail with [RetrieveError::TimedOut] if the last chunk has not arrived `timeout` after the first poll,
+    /// overriding the Browser's [default timeout][Browser::with_default_timeout].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.deadline = Deadline::new(Some(timeout));
+        self
+    }
+    fn finish(&mut self, link: &mut BrowserInternal) {
+        link.byte_streams.remove(&self.ret_id);
+        self.done = true;
+    }
+}
+
+impl futures_core::Stream for ByteStream<'_> {
+    type Item = Result<Bytes, RetrieveError>;
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(None);
+        }
+        let mut link = this.browser.0.lock();
+        if this.ret_id == 0 {
+            if link.is_dead() {
+                this.done = true;
+                return Poll::Ready(Some(Err(RetrieveError::Disconnected)));
+            }
+            this.ret_id = link.get_new_id();
+            link.emit_reply_bytes(this.ret_id, this.id, WINDOW);
+            link.wake_outgoing();
+            link.byte_streams.insert(
+                this.ret_id,
+                ByteStreamState {
+                    waker: cx.waker().to_owned(),
+                    chunks: VecDeque::new(),
+                    end: None,
+                },
+            );
+        }
+        let Some(state) = link.byte_streams.get_mut(&this.ret_id) else {
+            this.done = true;
+            return Poll::Ready(Some(Err(RetrieveError::Disconnected)));
+        };
+        if let Some(chunk) = state.chunks.pop_front() {
+            if state.end.is_none() {
+                // Room for one more.
+                link.emit_reply_bytes_credit(this.ret_id, Some(1));
+                link.wake_outgoing();
+            }
+            return Poll::Ready(Some(Ok(chunk)));
+        }
+        match state.end {
+            Some(Ok(())) => {
+                this.finish(&mut link);
+                return Poll::Ready(None);
+            }
+            Some(Err(error)) => {
+                this.finish(&mut link);
+                let location = link.location(this.id);
+                link.set_location(error, location);
+                link.count_value(error, location.unwrap_or(Location::caller()));
+                return Poll::Ready(Some(Err(RetrieveError::Thrown(JsValue {
+                    id: error,
+                    browser: this.browser.clone(),
+                }))));
+            }
+            None => {}
+        }
+        if !state.waker.will_wake(cx.waker()) {
+            state.waker = cx.waker().to_owned();
+        }
+        if link.is_dead() {
+            this.finish(&mut link);
+            return Poll::Ready(Some(Err(RetrieveError::Disconnected)));
+        }
+        if this.deadline.poll_elapsed(&link, cx) {
+            // Forget the stream; chunks already on their way will be ignored.
+            link.emit_reply_bytes_credit(this.ret_id, None);
+            link.wake_outgoing_lazy();
+            this.finish(&mut link);
+            return Poll::Ready(Some(Err(RetrieveError::TimedOut)));
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for ByteStream<'_> {
+    fn drop(&mut self) {
+        if self.ret_id != 0 && !self.done {
+            let mut link = self.browser.0.lock();
+            if link
+                .byte_streams
+                .remove(&self.ret_id)
+                .is_some_and(|state| state.end.is_none())
+            {
+                // Let the JS client stop reading the value.
+                link.emit_reply_bytes_credit(self.ret_id, None);
+                link.wake_outgoing_lazy();
+            }
+        }
+    }
+}
+
+/// A [Future] for the bytes of a JS value. Created by [JsValue::retrieve_bytes].
+pub struct RetrieveBytes<'a> {
+    stream: ByteStream<'a>,
+    bytes: Vec<u8>,
+}
+
+impl RetrieveBytes<'_> {
+    /// See [ByteStream::timeout].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.stream = self.stream.timeout(timeout);
+        self
+    }
+}
+
+impl Future for RetrieveBytes<'_> {
+    type Output = Result<Vec<u8>, RetrieveError>;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        use futures_core::Stream;
+        let this = self.get_mut();
+        loop {
+            match Pin::new(&mut this.stream).poll_next(cx) {
+                Poll::Ready(Some(Ok(chunk))) => this.bytes.extend_from_slice(&chunk),
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Err(e)),
+                Poll::Ready(None) => return Poll::Ready(Ok(core::mem::take(&mut this.bytes))),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl Browser {
+    /// Receive a binary frame sent from the WSDOM JS client.
+    ///
+    /// Like [receive_incoming_message][Self::receive_incoming_message], this is only needed for the "manual" route;
+    /// binary frames carry the chunks of [retrieve_bytes][JsValue::retrieve_bytes] and friends.
+    pub fn receive_incoming_binary(&self, frame: impl Into<Bytes>) {
+        let frame = frame.into();
+        let mut link = self.0.lock();
+        link.count_received(frame.len());
+        link.receive_binary(frame);
+        link.wake_ready();
+    }
+}
+
+impl BrowserInternal {
+    /// Handle a `c<ret_id>:<bytes>` chunk of a [ByteStream].
+    pub(crate) fn receive_binary(&mut self, frame: Bytes) {
+        let Some(colon) = frame.iter().take(21).position(|&b| b == b':') else {
+            return;
+        };
+        let ret_id = match core::str::from_utf8(&frame[..colon]) {
+            Ok(header) => header
+                .strip_prefix('c')
+                .and_then(|id| id.parse::<u64>().ok()),
+            Err(_) => None,
+        };
+        // Nobody is waiting anymore, for example because the stream timed out.
+        if let Some(state) = ret_id.and_then(|ret_id| self.byte_streams.get_mut(&ret_id)) {
+            state.chunks.push_back(frame.slice(colon + 1..));
+            state.wake();
+        }
+    }
+    /// Handle a `B<base64>` message: a binary frame, for transports that only carry text.
+    pub(crate) fn receive_binary_base64(&mut self, base64: &str) {
+        if let Ok(frame) = base64::engine::general_purpose::STANDARD.decode(base64) {
+            self.receive_binary(frame.into());
+        }
+    }
+    /// Handle the `<ret_id>:<json>` reply ending a [ByteStream].
+    pub(crate) fn end_byte_stream(&mut self, ret_id: u64, message: &str) {
+        let (Some(state), Some((_, json))) =
+            (self.byte_streams.get_mut(&ret_id), message.split_once(':'))
+        else {
+            return;
+        };
+        #[derive(serde::Deserialize)]
+        struct End {
+            error: Option<u64>,
+        }
+        state.end = Some(match serde_json::from_str::<End>(json) {
+            Ok(End { error: Some(error) }) => Err(error),
+            _ => Ok(()),
+        });
+        state.wake();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::task::{Context, Poll};
+    use futures_util::{FutureExt, StreamExt, task::noop_waker_ref};
+
+    use crate::{Browser, Encoding, RetrieveError};
+
+    #[test]
+    fn bytes_travel_as_base64() {
+        let mut browser = Browser::new();
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let _array = browser.new_uint8array(&[0, 255, 7]);
+        let _blob = browser.new_blob(b"hi", "text/\"plain\"");
+        assert_eq!(
+            browser.poll_next_unpin(&mut cx),
+            Poll::Ready(Some(
+                "_w.b(2,\"AP8H\",\"u8\",null);\n_w.b(3,\"aGk=\",\"blob\",\"text/\\\"plain\\\"\");\n"
+                    .into()
+            ))
+        );
+
+        let mut browser = Browser::new().with_encoding(Encoding::Opcode);
+        let _buffer = browser.new_array_buffer(&[]);
+        assert_eq!(
+            browser.poll_next_unpin(&mut cx),
+            Poll::Ready(Some("[\"b\",2,\"\",\"buffer\",null]\n".into()))
+        );
+    }
+
+    #[test]
+    fn bytes_come_back_in_chunks() {
+        let mut browser = Browser::new();
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let blob = browser.call_function("blob", [], false);
+        let mut retrieval = blob.retrieve_bytes();
+        assert!(retrieval.poll_unpin(&mut cx).is_pending());
+        assert_eq!(
+            browser.poll_next_unpin(&mut cx),
+            Poll::Ready(Some(
+                "try{_w.s(2,blob())}catch($){_w.e(2,$)};\n_w.rb(3,2,4);\n".into()
+            ))
+        );
+        browser.receive_incoming_binary(&b"c3:ab"[..]);
+        // Transports that only carry text send the same frame in base64.
+        browser.receive_incoming_message("BYzM6Y2Q=".into());
+        assert!(retrieval.poll_unpin(&mut cx).is_pending());
+        browser.receive_incoming_message("p3:{\"value\":null}".into());
+        assert!(matches!(
+            retrieval.poll_unpin(&mut cx),
+            Poll::Ready(Ok(bytes)) if bytes == b"abcd"
+        ));
+
+        let handle = browser.clone();
+        let mut chunks = blob.retrieve_byte_stream();
+        assert!(chunks.poll_next_unpin(&mut cx).is_pending());
+        let _ = browser.poll_next_unpin(&mut cx);
+        handle.receive_incoming_binary(&b"c4:x"[..]);
+        handle.receive_incoming_message("p4:{\"error\":9}".into());
+        assert!(matches!(
+            chunks.poll_next_unpin(&mut cx),
+            Poll::Ready(Some(Ok(chunk))) if chunk == "x"
+        ));
+        assert!(matches!(
+            chunks.poll_next_unpin(&mut cx),
+            Poll::Ready(Some(Err(RetrieveError::Thrown(e)))) if e.id == 9
+        ));
+        assert!(chunks.poll_next_unpin(&mut cx).is_ready());
+        assert!(browser.0.lock().byte_streams.is_empty());
+    }
+
+    #[test]
+    fn byte_stream_grants_credit_as_it_yields() {
+        let mut browser = Browser::new().with_encoding(Encoding::Opcode);
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let blob = browser.call_function("blob", [], false);
+        let mut chunks = blob.retrieve_byte_stream();
+        assert!(chunks.poll_next_unpin(&mut cx).is_pending());
+        let Poll::Ready(Some(frame)) = browser.poll_next_unpin(&mut cx) else {
+            panic!("reply not requested");
+        };
+        assert!(frame.ends_with("[\"rb\",3,2,4]\n"));
+
+        browser.receive_incoming_binary(&b"c3:a"[..]);
+        browser.receive_incoming_binary(&b"c3:b"[..]);
+        // Nothing is granted until a chunk is taken.
+        assert!(browser.poll_next_unpin(&mut cx).is_pending());
+        assert!(chunks.poll_next_unpin(&mut cx).is_ready());
+        assert_eq!(
+            browser.poll_next_unpin(&mut cx),
+            Poll::Ready(Some("[\"rc\",3,1]\n".into()))
+        );
+        // Dropping the stream early tells the JS client to stop.
+        drop(chunks);
+        assert_eq!(
+            browser.poll_next_unpin(&mut cx),
+            Poll::Ready(Some("[\"rc\",3,null]\n".into()))
+        );
+    }
+}
@@ -0,0 +1,200 @@
+use alloc::{
+    borrow::ToOwned,
+    collections::BTreeSet,
+    string::{String, ToString},
+};
+use core::fmt::Write;
+
+use crate::{
+    command::Callee,
+    serialize::{UseInJsCode, UseInJsCodeWriter},
+};
+
+/// An allowlist of what Rust code may reach on the JS side.
+///
+/// A Browser with no capabilities (the default) may touch anything.
+/// Once [Browser::with_capabilities][crate::Browser::with_capabilities] is set,
+/// every command is checked before it is sent, and a command reaching anything not listed here
+/// kills the Browser with [Error::CapabilityDenied][crate::Error::CapabilityDenied].
+///
+/// Pass the same value to `wsdom-gen` so the generated client enforces it again.
+/// Only [opcode-mode][crate::Encoding::Opcode] clients can enforce capabilities;
+/// a source-mode client runs whatever it receives.
+///
+/// ```rust
+/// # use px_wsdom_core::Capabilities;
+/// let capabilities = Capabilities::new()
+///     .allow_global("document")
+///     .allow_method("createElement")
+///     .allow_method("appendChild")
+///     .allow_property("body")
+///     .allow_property("innerText");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    globals: BTreeSet<String>,
+    constructors: BTreeSet<String>,
+    methods: BTreeSet<String>,
+    properties: BTreeSet<String>,
+}
+
+impl Capabilities {
+    /// A policy that allows nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Allow reading or calling a global, like `document` or `Math.cos`.
+    ///
+    /// Dotted names must be listed in full; allowing `Math` does not allow calling `Math.cos`.
+    pub fn allow_global(mut self, name: &str) -> Self {
+        self.globals.insert(name.to_owned());
+        self
+    }
+    /// Allow calling a global class with `new`, like `Map`.
+    pub fn allow_constructor(mut self, name: &str) -> Self {
+        self.constructors.insert(name.to_owned());
+        self
+    }
+    /// Allow calling a method with this name on any object.
+    pub fn allow_method(mut self, name: &str) -> Self {
+        self.methods.insert(name.to_owned());
+        self
+    }
+    /// Allow getting and setting a property with this name on any object.
+    ///
+    /// Numeric (array index) properties are always allowed.
+    pub fn allow_property(mut self, name: &str) -> Self {
+        self.properties.insert(name.to_owned());
+        self
+    }
+    pub fn globals(&self) -> impl Iterator<Item = &str> {
+        self.globals.iter().map(String::as_str)
+    }
+    pub fn constructors(&self) -> impl Iterator<Item = &str> {
+        self.constructors.iter().map(String::as_str)
+    }
+    pub fn methods(&self) -> impl Iterator<Item = &str> {
+        self.methods.iter().map(String::as_str)
+    }
+    pub fn properties(&self) -> impl Iterator<Item = &str> {
+        self.properties.iter().map(String::as_str)
+    }
+
+    /// Check a call target. Returns the denied name on failure.
+    pub(crate) fn check_callee(&self, callee: &Callee<'_>) -> Result<(), String> {
+        let (allowed, name) = match callee {
+            Callee::Function(name) => (&self.globals, *name),
+            Callee::Constructor(name) => (&self.constructors, *name),
+            Callee::Method(_, name) => (&self.methods, *name),
+            Callee::Value(_) => return Ok(()),
+        };
+        if allowed.contains(name) {
+            Ok(())
+        } else {
+            Err(name.to_owned())
+        }
+    }
+
+    /// Check every global an operand refers to.
+    ///
+    /// If `property` is set, a literal operand is also checked as a property name.
+    /// Property names held in JS values cannot be checked here; the client checks those.
+    pub(crate) fn check_operand(
+        &self,
+        operand: &dyn UseInJsCode,
+        property: bool,
+    ) -> Result<(), String> {
+        let mut rendered = String::new();
+        if write!(rendered, "{:#}", UseInJsCodeWriter(operand)).is_err() {
+            // Serialization errors are reported when the command itself is written.
+            return Ok(());
+        }
+        match serde_json::from_str(&rendered) {
+            Ok(operand) => self.check_operand_json(&operand, property),
+            Err(_) => Err(rendered),
+        }
+    }
+
+    fn check_operand_json(
+        &self,
+        operand: &serde_json::Value,
+        property: bool,
+    ) -> Result<(), String> {
+        use serde_json::Value;
+        let Some([Value::String(tag), rest @ ..]) = operand.as_array().map(|v| v.as_slice()) else {
+            return Err(operand.to_string());
+        };
+        match (tag.as_str(), rest) {
+            ("n", [Value::String(path)]) => self.check_global(path),
+            ("a", [Value::Array(items)]) => items
+                .iter()
+                .try_for_each(|item| self.check_operand_json(item, false)),
+            ("l", [Value::String(name)]) if property && !self.properties.contains(name) => {
+                Err(name.to_owned())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_global(&self, path: &str) -> Result<(), String> {
+        if self.globals.contains(path) {
+            Ok(())
+        } else {
+            Err(path.to_owned())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Browser, Capabilities, Encoding, Error, js_types::JsNumber, serialize::RawCodeImmediate,
+    };
+
+    fn capabilities() -> Capabilities {
+        Capabilities::new()
+            .allow_global("document")
+            .allow_global("Math.cos")
+            .allow_property("title")
+    }
+
+    #[test]
+    fn allowed_commands_are_sent() {
+        let browser = Browser::new()
+            .with_encoding(Encoding::Opcode)
+            .with_capabilities(capabilities());
+        let document = browser.value_from_raw_code(format_args!("document"));
+        let x: JsNumber = browser.new_value(&1.5);
+        let y = browser.call_function("Math.cos", [&x as &_], false);
+        browser.set_field(&document, &"title", &y);
+        browser.set_field(&y, &0, &x);
+        assert!(browser.take_error().is_none());
+    }
+
+    fn assert_denied(name: &str, command: impl FnOnce(&Browser)) {
+        let browser = Browser::new().with_capabilities(capabilities());
+        command(&browser);
+        match browser.take_error() {
+            Some(Error::CapabilityDenied(denied)) => assert_eq!(denied, name),
+            e => panic!("{name} was not denied: {e:?}"),
+        }
+    }
+
+    #[test]
+    fn denied_commands_kill_the_browser() {
+        assert_denied("fetch", |b| {
+            b.call_function("fetch", [&"/" as &_], false);
+        });
+        assert_denied("cookie", |b| {
+            let document = b.value_from_raw_code(format_args!("document"));
+            b.get_field(&document, &"cookie");
+        });
+        assert_denied("eval", |b| {
+            b.call_function("Math.cos", [&RawCodeImmediate("eval") as &_], false);
+        });
+        assert_denied("Map", |b| {
+            b.call_constructor("Map", [], false);
+        });
+        assert_denied("raw code", |b| b.run_raw_code(format_args!("alert(1)")));
+    }
+}
@@ -0,0 +1,208 @@
+use core::{future::Future, pin::Pin, task::Poll, time::Duration};
+
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+use futures_util::task::noop_waker_ref;
+use serde::Deserialize;
+
+use crate::{
+    exception::JsException,
+    link::{Browser, BrowserInternal, Error, RetrievalState},
+    retrieve::RetrieveError,
+    timer::Deadline,
+};
+
+/// A [Future] that resolves once the JS client has run every command sent before it was created.
+///
+/// Created by [Browser::sync]. If the Browser is [closed][Browser::close] before the JS client replies,
+/// this Future fails with [RetrieveError::Disconnected].
+/// Like retrievals, it can be given a [timeout][Self::timeout].
+pub struct Checkpoint {
+    ret_id: u64,
+    browser: Browser,
+    deadline: Deadline,
+}
+
+#[derive(Deserialize)]
+struct Failure {
+    id: u64,
+    #[serde(flatten)]
+    exception: JsException,
+}
+
+impl Browser {
+    /// Wait until the JS client has run every command sent so far,
+    /// and collect the exceptions they threw.
+    ///
+    /// Commands are normally fire-and-forget; an exception only surfaces if the failing value is retrieved.
+    /// The returned Future resolves to every exception stored by the JS client since the previous `sync`
+    /// (or since the connection started), whether or not the failing values have been dropped.
+    /// The JS client keeps at most the 1024 newest of them; older ones are left out.
+    ///
+    /// ```rust
+    /// # use px_wsdom_core::Browser;
+    /// async fn example(browser: &Browser) {
+    ///     browser.call_function("doesNotExist", [], false);
+    ///     let failures = browser.sync().await.unwrap();
+    ///     assert_eq!(failures[0].name, "ReferenceError");
+    /// }
+    /// ```
+    pub fn sync(&self) -> Checkpoint {
+        let mut link = self.0.lock();
+        let ret_id = link.start_query();
+        link.emit_checkpoint(ret_id);
+        link.wake_outgoing();
+        Checkpoint {
+            ret_id,
+            browser: self.to_owned(),
+            deadline: Deadline::new(link.default_timeout),
+        }
+    }
+}
+
+impl Checkpoint {
+    /// Fail with [RetrieveError::TimedOut] if the JS client has not replied `timeout` after the first poll,
+    /// overriding the Browser's [default timeout][Browser::with_default_timeout].
+    ///
+    /// A reply arriving after the deadline is discarded, along with the exceptions it reports.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.deadline = Deadline::new(Some(timeout));
+        self
+    }
+}
+
+impl Future for Checkpoint {
+    type Output = Result<Vec<JsException>, RetrieveError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut core::task::Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut link = this.browser.0.lock();
+        let value = match link.poll_query(this.ret_id, &mut this.deadline, cx) {
+            Poll::Ready(Ok(value)) => value,
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            Poll::Pending => return Poll::Pending,
+        };
+        let json = value.split_once(':').map_or("", |(_, json)| json);
+        match serde_json::from_str::<Vec<Failure>>(json) {
+            Ok(failures) => Poll::Ready(Ok(failures
+                .into_iter()
+                .map(|Failure { id, exception }| JsException {
+                    id,
+                    location: link.location(id),
+                    ..exception
+                })
+                .collect())),
+            Err(e) => {
+                // The reply is malformed, so the client cannot be trusted anymore.
+                link.kill(Error::DataDeserialize(e));
+                Poll::Ready(Err(RetrieveError::Disconnected))
+            }
+        }
+    }
+}
+
+impl BrowserInternal {
+    /// Register a reply to be tagged with a new id, for a query command about to be emitted.
+    pub(crate) fn start_query(&mut self) -> u64 {
+        let ret_id = self.get_new_id();
+        self.retrievals.insert(
+            ret_id,
+            RetrievalState {
+                waker: noop_waker_ref().to_owned(),
+                last_value: String::new(),
+                times: 0,
+                payloads: None,
+            },
+        );
+        ret_id
+    }
+    /// Wait for the reply registered by [start_query][Self::start_query], returning the raw `<id>:<json>` message.
+    pub(crate) fn poll_query(
+        &mut self,
+        ret_id: u64,
+        deadline: &mut Deadline,
+        cx: &mut core::task::Context<'_>,
+    ) -> Poll<Result<String, RetrieveError>> {
+        let dead = self.is_dead();
+        let Some(state) = self.retrievals.get_mut(&ret_id) else {
+            return if dead {
+                Poll::Ready(Err(RetrieveError::Disconnected))
+            } else {
+                Poll::Pending
+            };
+        };
+        if state.times == 0 && dead {
+            self.retrievals.remove(&ret_id);
+            return Poll::Ready(Err(RetrieveError::Disconnected));
+        }
+        if state.times == 0 {
+            let new_waker = cx.waker();
+            if !state.waker.will_wake(new_waker) {
+                state.waker = new_waker.to_owned();
+            }
+            if deadline.poll_elapsed(self, cx) {
+                self.retrievals.remove(&ret_id);
+                return Poll::Ready(Err(RetrieveError::TimedOut));
+            }
+            return Poll::Pending;
+        }
+        let value = core::mem::take(&mut state.last_value);
+        self.retrievals.remove(&ret_id);
+        Poll::Ready(Ok(value))
+    }
+}
+
+impl Drop for Checkpoint {
+    fn drop(&mut self) {
+        self.browser.0.lock().retrievals.remove(&self.ret_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::task::{Context, Poll};
+    use futures_util::{FutureExt, StreamExt, task::noop_waker_ref};
+
+    use crate::{Browser, RetrieveError};
+
+    #[test]
+    fn sync_collects_exceptions_in_window() {
+        let mut browser = Browser::new().with_locations(true);
+        let value = browser.call_function("doesNotExist", [], false);
+        let mut checkpoint = browser.sync();
+        let mut cx = Context::from_waker(noop_waker_ref());
+        assert_eq!(
+            browser.poll_next_unpin(&mut cx),
+            Poll::Ready(Some(
+                "try{_w.s(2,doesNotExist())}catch($){_w.e(2,$)};\n_w.r(3,_w.k());\n".into()
+            ))
+        );
+        assert!(checkpoint.poll_unpin(&mut cx).is_pending());
+
+        browser.receive_incoming_message(
+            r#"p3:[{"id":2,"name":"ReferenceError","message":"doesNotExist is not defined","stack":null}]"#
+                .into(),
+        );
+        let Poll::Ready(Ok(failures)) = checkpoint.poll_unpin(&mut cx) else {
+            panic!("checkpoint not resolved");
+        };
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].id, 2);
+        assert_eq!(failures[0].name, "ReferenceError");
+        assert_eq!(failures[0].location, value.location());
+        assert!(browser.take_error().is_none());
+    }
+
+    #[test]
+    fn malformed_reply_fails_checkpoint() {
+        let browser = Browser::new();
+        let mut checkpoint = browser.sync();
+        let mut cx = Context::from_waker(noop_waker_ref());
+        assert!(checkpoint.poll_unpin(&mut cx).is_pending());
+        browser.receive_incoming_message("p2:{}".into());
+        assert!(matches!(
+            checkpoint.poll_unpin(&mut cx),
+            Poll::Ready(Err(RetrieveError::Disconnected))
+        ));
+        assert!(browser.is_closed());
+    }
+}
@@ -0,0 +1,724 @@
+use core::fmt::{self, Write};
+
+use alloc::{string::String, vec::Vec};
+
+use crate::{
+    binary::BinaryKind,
+    callback::CallbackOptions,
+    capabilities::Capabilities,
+    link::{BrowserInternal, Error},
+    serialize::{SerdeToJs, UseInJsCode, UseInJsCodeWriter},
+};
+
+/// How a [Browser][crate::Browser] encodes the commands it sends to the JS client.
+///
+/// The JS client must be generated with the matching mode (see `wsdom-gen`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    /// JavaScript source that the WSDOM client executes with `Function()`.
+    #[default]
+    Source,
+    /// Newline-delimited JSON opcodes that the WSDOM client interprets itself.
+    ///
+    /// This mode never needs `eval` or `Function()`, so it works on pages whose
+    /// Content-Security-Policy forbids `unsafe-eval`.
+    /// Arbitrary JavaScript cannot be expressed as opcodes; see [Browser::run_raw_code][crate::Browser::run_raw_code].
+    Opcode,
+}
+
+/// Every opcode a Browser in [Encoding::Opcode] may send.
+///
+/// Opcode clients list the ones they interpret in their [Hello][crate::Hello].
+pub(crate) const OPCODES: [&str; 26] = [
+    "f", "n", "m", "c", "g", "p", "s", "o", "dm", "dr", "r", "k", "z", "x", "rp", "aw", "at", "cb",
+    "cs", "acb", "acr", "acs", "b", "rb", "rc", "cw",
+];
+
+/// The function called by a call command.
+pub(crate) enum Callee<'a> {
+    /// A global function, like `alert` or `Math.cos`.
+    Function(&'a str),
+    /// A global class, called with `new`.
+    Constructor(&'a str),
+    /// A method on a value held by the JS client.
+    Method(u64, &'a str),
+    /// A value held by the JS client, called directly.
+    Value(u64),
+}
+
+/// A slot in the JS client's value map.
+pub(crate) struct ValueRef(pub(crate) u64);
+
+/// Stands for the `_w` member that reads a slot, which a value cannot look up while being serialized.
+///
+/// Commands are written through [CommandWriter], which replaces it with the Browser's member.
+/// Serialized values never contain it, since JSON escapes control characters.
+const GET_MARKER: char = '\u{1}';
+
+impl UseInJsCode for ValueRef {
+    fn serialize_to(&self, buf: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let id = self.0;
+        if buf.alternate() {
+            write!(buf, "[\"r\",{id}]")
+        } else {
+            write!(buf, "{GET_MARKER}({id})")
+        }
+    }
+}
+
+/// Writes commands to the buffer, spelling value references with the Browser's `_w` member.
+struct CommandWriter<'a> {
+    buf: &'a mut String,
+    get: &'a str,
+}
+
+impl Write for CommandWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut parts = s.split(GET_MARKER);
+        self.buf.push_str(parts.next().unwrap_or_default());
+        for part in parts {
+            self.buf.push_str(self.get);
+            self.buf.push_str(part);
+        }
+        Ok(())
+    }
+}
+
+/// Whether `code` is a plain (possibly dotted) global name such as `document` or `Math.PI`.
+fn is_global_path(code: &str) -> bool {
+    !code.is_empty()
+        && code.split('.').all(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+                && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+        })
+}
+
+impl BrowserInternal {
+    fn write_command(&mut self, command: fmt::Arguments<'_>) {
+        if self.is_dead() {
+            // Nothing would send it.
+            return;
+        }
+        let mut writer = CommandWriter {
+            buf: &mut self.commands_buf,
+            get: &self.names.get,
+        };
+        if let Err(e) = writer.write_fmt(command) {
+            self.kill(Error::CommandSerialize(e));
+        }
+        self.check_buffer_limit();
+    }
+
+    /// Run a [Capabilities] check, killing the Browser if it fails.
+    ///
+    /// Returns whether the command may be emitted.
+    fn permit(&mut self, check: impl FnOnce(&Capabilities) -> Result<(), String>) -> bool {
+        match self.capabilities.as_ref().map(check) {
+            Some(Err(name)) => {
+                self.kill(Error::CapabilityDenied(name));
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// `out = callee(...args)`
+    pub(crate) fn emit_call<'a>(
+        &mut self,
+        out_id: u64,
+        callee: Callee<'_>,
+        args: impl IntoIterator<Item = &'a dyn UseInJsCode>,
+        last_arg_variadic: bool,
+    ) {
+        let args: Vec<&dyn UseInJsCode> = args.into_iter().collect();
+        if !self.permit(|c| {
+            c.check_callee(&callee)?;
+            args.iter().try_for_each(|arg| c.check_operand(*arg, false))
+        }) {
+            return;
+        }
+        let mut iter = args.into_iter().peekable();
+        let names = self.names.clone();
+        let (get, set, err) = (&names.get, &names.set, &names.err);
+        match self.encoding {
+            Encoding::Source => {
+                match callee {
+                    Callee::Function(name) => {
+                        self.write_command(format_args!("try{{{set}({out_id},{name}("))
+                    }
+                    Callee::Constructor(name) => {
+                        self.write_command(format_args!("try{{{set}({out_id},new {name}("))
+                    }
+                    Callee::Method(id, name) => {
+                        self.write_command(format_args!("try{{{set}({out_id},{get}({id}).{name}("))
+                    }
+                    Callee::Value(id) => {
+                        self.write_command(format_args!("try{{{set}({out_id},({get}({id}))("))
+                    }
+                }
+                while let Some(arg) = iter.next() {
+                    let arg = UseInJsCodeWriter(arg);
+                    if last_arg_variadic && iter.peek().is_none() {
+                        self.write_command(format_args!("...{arg},"));
+                    } else {
+                        self.write_command(format_args!("{arg},"));
+                    }
+                }
+                self.write_command(format_args!("))}}catch($){{{err}({out_id},$)}};\n"));
+            }
+            Encoding::Opcode => {
+                match callee {
+                    Callee::Function(name) => {
+                        let name = UseInJsCodeWriter(name);
+                        self.write_command(format_args!("[\"f\",{out_id},{name},["))
+                    }
+                    Callee::Constructor(name) => {
+                        let name = UseInJsCodeWriter(name);
+                        self.write_command(format_args!("[\"n\",{out_id},{name},["))
+                    }
+                    Callee::Method(id, name) => {
+                        let name = UseInJsCodeWriter(name);
+                        self.write_command(format_args!("[\"m\",{out_id},{id},{name},["))
+                    }
+                    Callee::Value(id) => self.write_command(format_args!("[\"c\",{out_id},{id},[")),
+                }
+                let mut first = true;
+                for arg in iter {
+                    let arg = UseInJsCodeWriter(arg);
+                    let sep = if core::mem::take(&mut first) { "" } else { "," };
+                    self.write_command(format_args!("{sep}{arg:#}"));
+                }
+                self.write_command(format_args!("],{last_arg_variadic}]\n"));
+            }
+        }
+    }
+
+    /// `out = base[property]`
+    pub(crate) fn emit_get(
+        &mut self,
+        out_id: u64,
+        base: &dyn UseInJsCode,
+        property: &dyn UseInJsCode,
+    ) {
+        if !self.permit(|c| {
+            c.check_operand(base, false)?;
+            c.check_operand(property, true)
+        }) {
+            return;
+        }
+        let (base, property) = (UseInJsCodeWriter(base), UseInJsCodeWriter(property));
+        let names = self.names.clone();
+        let (set, err) = (&names.set, &names.err);
+        match self.encoding {
+            Encoding::Source => self.write_command(format_args!(
+                "try{{{set}({out_id},({base})[{property}])}}catch($){{{err}({out_id},$)}};\n"
+            )),
+            Encoding::Opcode => {
+                self.write_command(format_args!("[\"g\",{out_id},{base:#},{property:#}]\n"))
+            }
+        }
+    }
+
+    /// `base[property] = value`
+    pub(crate) fn emit_set_field(
+        &mut self,
+        base: &dyn UseInJsCode,
+        property: &dyn UseInJsCode,
+        value: &dyn UseInJsCode,
+    ) {
+        if !self.permit(|c| {
+            c.check_operand(base, false)?;
+            c.check_operand(property, true)?;
+            c.check_operand(value, false)
+        }) {
+            return;
+        }
+        let (base, property, value) = (
+            UseInJsCodeWriter(base),
+            UseInJsCodeWriter(property),
+            UseInJsCodeWriter(value),
+        );
+        match self.encoding {
+            Encoding::Source => self.write_command(format_args!("({base})[{property}]={value};\n")),
+            Encoding::Opcode => {
+                self.write_command(format_args!("[\"p\",{base:#},{property:#},{value:#}]\n"))
+            }
+        }
+    }
+
+    /// `out = value`
+    pub(crate) fn emit_value(&mut self, out_id: u64, value: &dyn UseInJsCode) {
+        if !self.permit(|c| c.check_operand(value, false)) {
+            return;
+        }
+        let value = UseInJsCodeWriter(value);
+        let start = self.commands_buf.len();
+        let names = self.names.clone();
+        let (set, err) = (&names.set, &names.err);
+        match self.encoding {
+            Encoding::Source => self.write_command(format_args!(
+                "try{{{set}({out_id},{value})}}catch($){{{err}({out_id},$)}};\n"
+            )),
+            Encoding::Opcode => self.write_command(format_args!("[\"s\",{out_id},{value:#}]\n")),
+        }
+        if !self.is_dead() {
+            self.record_pure_creation(out_id, start..self.commands_buf.len());
+        }
+    }
+
+    /// `out = code`, for an arbitrary JavaScript expression.
+    ///
+    /// In opcode mode, or with [Capabilities] set, only global names (like `document`) can be expressed.
+    pub(crate) fn emit_raw_value(&mut self, out_id: u64, code: fmt::Arguments<'_>) {
+        if self.capabilities.is_some() {
+            let mut path = String::new();
+            if let Err(e) = path.write_fmt(code) {
+                return self.kill(Error::CommandSerialize(e));
+            }
+            if !self.permit(|c| c.check_global(&path)) {
+                return;
+            }
+        }
+        let names = self.names.clone();
+        let (set, err) = (&names.set, &names.err);
+        match self.encoding {
+            Encoding::Source => self.write_command(format_args!(
+                "try{{{set}({out_id},{code})}}catch($){{{err}({out_id},$)}};\n"
+            )),
+            Encoding::Opcode => {
+                let mut path = String::new();
+                if let Err(e) = path.write_fmt(code) {
+                    self.kill(Error::CommandSerialize(e));
+                } else if is_global_path(&path) {
+                    let path = UseInJsCodeWriter(path.as_str());
+                    self.write_command(format_args!("[\"s\",{out_id},[\"n\",{path}]]\n"));
+                } else {
+                    self.kill(Error::RawCodeInOpcodeMode);
+                }
+            }
+        }
+    }
+
+    /// Run an arbitrary JavaScript statement.
+    ///
+    /// Arbitrary code cannot be checked against [Capabilities], so it is refused when they are set.
+    pub(crate) fn emit_raw(&mut self, code: fmt::Arguments<'_>) {
+        if !self.permit(|_| Err(String::from("raw code"))) {
+            return;
+        }
+        match self.encoding {
+            Encoding::Source => self.write_command(format_args!("{{ {code} }}\n")),
+            Encoding::Opcode => self.kill(Error::RawCodeInOpcodeMode),
+        }
+    }
+
+    /// `out = op a` or `out = a op b`
+    pub(crate) fn emit_operator(&mut self, out_id: u64, op: &str, operands: &[&dyn UseInJsCode]) {
+        if !self.permit(|c| operands.iter().try_for_each(|a| c.check_operand(*a, false))) {
+            return;
+        }
+        let names = self.names.clone();
+        let (set, err) = (&names.set, &names.err);
+        match (self.encoding, operands) {
+            (Encoding::Source, [a]) => {
+                let a = UseInJsCodeWriter(*a);
+                self.write_command(format_args!(
+                    "try{{{set}({out_id},({op}({a})))}}catch($){{{err}({out_id},$)}};\n"
+                ))
+            }
+            (Encoding::Source, [a, b]) => {
+                let (a, b) = (UseInJsCodeWriter(*a), UseInJsCodeWriter(*b));
+                self.write_command(format_args!(
+                    "try{{{set}({out_id},(({a}) {op} ({b})))}}catch($){{{err}({out_id},$)}};\n"
+                ))
+            }
+            (Encoding::Opcode, [a]) => {
+                let a = UseInJsCodeWriter(*a);
+                self.write_command(format_args!("[\"o\",{out_id},\"{op}\",{a:#}]\n"))
+            }
+            (Encoding::Opcode, [a, b]) => {
+                let (a, b) = (UseInJsCodeWriter(*a), UseInJsCodeWriter(*b));
+                self.write_command(format_args!("[\"o\",{out_id},\"{op}\",{a:#},{b:#}]\n"))
+            }
+            _ => unreachable!("operators take one or two operands"),
+        }
+    }
+
+    /// Free a slot, along with the other slots freed before the next frame is sent.
+    pub(crate) fn emit_delete(&mut self, id: u64) {
+        if let Some(locations) = self.locations.as_mut() {
+            locations.remove(&id);
+        }
+        self.queue_delete(id);
+    }
+
+    /// Free every slot in the given inclusive ranges of ids.
+    pub(crate) fn emit_delete_ranges(&mut self, ranges: &[(u64, u64)]) {
+        if let Some(locations) = self.locations.as_mut() {
+            locations.retain(|id, _| !ranges.iter().any(|(from, to)| (from..=to).contains(&id)));
+        }
+        let names = self.names.clone();
+        match self.encoding {
+            Encoding::Source => self.write_command(format_args!("{}([", names.del_ranges)),
+            Encoding::Opcode => self.write_command(format_args!("[\"dr\",[")),
+        }
+        for (i, (from, to)) in ranges.iter().enumerate() {
+            let sep = if i == 0 { "" } else { "," };
+            self.write_command(format_args!("{sep}[{from},{to}]"));
+        }
+        match self.encoding {
+            Encoding::Source => self.write_command(format_args!("]);\n")),
+            Encoding::Opcode => self.write_command(format_args!("]]\n")),
+        }
+    }
+
+    /// Send the value (or error) in slot `id` back to Rust, tagged with `ret_id`.
+    pub(crate) fn emit_reply(&mut self, ret_id: u64, id: u64) {
+        let names = self.names.clone();
+        let (rep, catch) = (&names.rep, &names.catch);
+        match self.encoding {
+            Encoding::Source => {
+                self.write_command(format_args!("{rep}({ret_id},{catch}({id}));\n"))
+            }
+            Encoding::Opcode => self.write_command(format_args!("[\"r\",{ret_id},{id}]\n")),
+        }
+    }
+
+    /// Send the bytes of `id` back to Rust in binary chunks tagged with `ret_id`, followed by a reply.
+    ///
+    /// Only `credit` chunks are sent before [emit_reply_bytes_credit][Self::emit_reply_bytes_credit] allows more.
+    pub(crate) fn emit_reply_bytes(&mut self, ret_id: u64, id: u64, credit: u32) {
+        let names = self.names.clone();
+        let reply_bytes = &names.reply_bytes;
+        match self.encoding {
+            Encoding::Source => {
+                self.write_command(format_args!("{reply_bytes}({ret_id},{id},{credit});\n"))
+            }
+            Encoding::Opcode => {
+                self.write_command(format_args!("[\"rb\",{ret_id},{id},{credit}]\n"))
+            }
+        }
+    }
+
+    /// Let the bytes reply `ret_id` send `credit` more chunks, or with `None`, stop it.
+    pub(crate) fn emit_reply_bytes_credit(&mut self, ret_id: u64, credit: Option<u32>) {
+        let names = self.names.clone();
+        let reply_bytes_credit = &names.reply_bytes_credit;
+        let credit = UseInJsCodeWriter(&SerdeToJs(&credit));
+        match self.encoding {
+            Encoding::Source => {
+                self.write_command(format_args!("{reply_bytes_credit}({ret_id},{credit});\n"))
+            }
+            Encoding::Opcode => self.write_command(format_args!("[\"rc\",{ret_id},{credit}]\n")),
+        }
+    }
+
+    /// Send the exceptions stored since the previous checkpoint back to Rust, tagged with `ret_id`.
+    pub(crate) fn emit_checkpoint(&mut self, ret_id: u64) {
+        let names = self.names.clone();
+        let (rep, checkpoint) = (&names.rep, &names.checkpoint);
+        match self.encoding {
+            Encoding::Source => {
+                self.write_command(format_args!("{rep}({ret_id},{checkpoint}());\n"))
+            }
+            Encoding::Opcode => self.write_command(format_args!("[\"k\",{ret_id}]\n")),
+        }
+    }
+
+    /// `out = ` the bytes encoded in `base64`, made into `kind` by the JS client.
+    pub(crate) fn emit_bytes(&mut self, out_id: u64, base64: &str, kind: BinaryKind<'_>) {
+        let (kind, mime_type) = match kind {
+            BinaryKind::Uint8Array => ("u8", None),
+            BinaryKind::ArrayBuffer => ("buffer", None),
+            BinaryKind::Blob { mime_type } => ("blob", Some(mime_type)),
+        };
+        // A JSON string or `null`, the same in source and opcodes.
+        let mime_type = UseInJsCodeWriter(&SerdeToJs(&mime_type));
+        let start = self.commands_buf.len();
+        let names = self.names.clone();
+        let bytes = &names.bytes;
+        match self.encoding {
+            Encoding::Source => self.write_command(format_args!(
+                "{bytes}({out_id},\"{base64}\",\"{kind}\",{mime_type});\n"
+            )),
+            Encoding::Opcode => self.write_command(format_args!(
+                "[\"b\",{out_id},\"{base64}\",\"{kind}\",{mime_type}]\n"
+            )),
+        }
+        if !self.is_dead() {
+            self.record_pure_creation(out_id, start..self.commands_buf.len());
+        }
+    }
+
+    /// Send the number of slots held by the JS client back to Rust, tagged with `ret_id`.
+    pub(crate) fn emit_heap_size(&mut self, ret_id: u64) {
+        let names = self.names.clone();
+        let (rep, heap_size) = (&names.rep, &names.heap_size);
+        match self.encoding {
+            Encoding::Source => {
+                self.write_command(format_args!("{rep}({ret_id},{heap_size}());\n"))
+            }
+            Encoding::Opcode => self.write_command(format_args!("[\"z\",{ret_id}]\n")),
+        }
+    }
+
+    /// `out = import`, where `hash` identifies a module registered with the generated client.
+    pub(crate) fn emit_import(&mut self, out_id: u64, hash: &str) {
+        let names = self.names.clone();
+        let (set, err, import) = (&names.set, &names.err, &names.import);
+        match self.encoding {
+            Encoding::Source => self.write_command(format_args!(
+                "try{{{set}({out_id},{import}._{hash})}}catch($){{{err}({out_id},$)}};\n"
+            )),
+            Encoding::Opcode => self.write_command(format_args!("[\"x\",{out_id},\"{hash}\"]\n")),
+        }
+    }
+
+    /// Settle a JS-initiated RPC call.
+    pub(crate) fn emit_rpc_reply(&mut self, id: u64, value: &dyn UseInJsCode) {
+        if !self.permit(|c| c.check_operand(value, false)) {
+            return;
+        }
+        let value = UseInJsCodeWriter(value);
+        let names = self.names.clone();
+        let reply = &names.reply;
+        match self.encoding {
+            Encoding::Source => self.write_command(format_args!("{reply}({id},{value});\n")),
+            Encoding::Opcode => self.write_command(format_args!("[\"rp\",{id},{value:#}]\n")),
+        }
+    }
+
+    /// Subscribe to the promise in slot `id`; the outcome is stored in `cell_id` and `ret_id` is notified.
+    ///
+    /// The outcome goes into the cell object itself, so settling after the cell was deleted is harmless.
+    pub(crate) fn emit_await(&mut self, cell_id: u64, ret_id: u64, id: u64) {
+        let names = self.names.clone();
+        let (get, set, rep) = (&names.get, &names.set, &names.rep);
+        match self.encoding {
+            Encoding::Source => self.write_command(format_args!(
+                "{{var c={{}},k=function(r){{return function(e){{c.$={{e,r}};{rep}({ret_id},0)}}}};{set}({cell_id},c);try{{Promise.prototype.then.call({get}({id}),k(0),k(1))}}catch($){{k(1)($)}}}};\n"
+            )),
+            Encoding::Opcode => {
+                self.write_command(format_args!("[\"aw\",{cell_id},{ret_id},{id}]\n"))
+            }
+        }
+    }
+
+    /// Move the settled outcome of an [emit_await][Self::emit_await] cell into slot `out`.
+    pub(crate) fn emit_await_take(&mut self, out_id: u64, cell_id: u64) {
+        let names = self.names.clone();
+        let (get, set, err, del) = (&names.get, &names.set, &names.err, &names.del);
+        match self.encoding {
+            Encoding::Source => self.write_command(format_args!(
+                "{{var v = {get}({cell_id}).$;(v.r?{err}:{set})({out_id}, v.e);{del}({cell_id});}};\n"
+            )),
+            Encoding::Opcode => self.write_command(format_args!("[\"at\",{out_id},{cell_id}]\n")),
+        }
+    }
+
+    /// Create a function in slot `func_id` that queues its argument in `arr_id` and notifies `ret_id`.
+    pub(crate) fn emit_callback(&mut self, arr_id: u64, func_id: u64, ret_id: u64) {
+        let names = self.names.clone();
+        let (get, set, rep) = (&names.get, &names.set, &names.rep);
+        match self.encoding {
+            Encoding::Source => self.write_command(format_args!(
+                "{set}({arr_id}, []); {set}({func_id}, function(e) {{ {get}({arr_id}).push(e); {rep}({ret_id}, 0) }});\n"
+            )),
+            Encoding::Opcode => {
+                self.write_command(format_args!("[\"cb\",{arr_id},{func_id},{ret_id}]\n"))
+            }
+        }
+    }
+
+    /// Like [emit_callback][Self::emit_callback], but the notification carries an array of the
+    /// argument's property `paths` (dotted, like `"target.value"`), read when the function is called.
+    ///
+    /// Unless `events` is set, the argument itself is not queued.
+    pub(crate) fn emit_projected_callback(
+        &mut self,
+        arr_id: u64,
+        func_id: u64,
+        ret_id: u64,
+        paths: &[&str],
+        events: bool,
+    ) {
+        if !self.permit(|c| {
+            paths
+                .iter()
+                .flat_map(|path| path.split('.'))
+                .filter(|key| key.parse::<u32>().is_err())
+                .try_for_each(|key| c.check_operand(&SerdeToJs(key), true))
+        }) {
+            return;
+        }
+        let names = self.names.clone();
+        let (get, set, rep) = (&names.get, &names.set, &names.rep);
+        match self.encoding {
+            Encoding::Source => {
+                let mut body = String::new();
+                if events {
+                    let _ = write!(body, "{get}({arr_id}).push(e); ");
+                }
+                let _ = write!(body, "{rep}({ret_id}, [");
+                for path in paths {
+                    body.push('e');
+                    for key in path.split('.') {
+                        let key = serde_json::to_string(key).unwrap_or_default();
+                        let _ = write!(body, "?.[{key}]");
+                    }
+                    body.push(',');
+                }
+                body.push_str("])");
+                self.write_command(format_args!(
+                    "{set}({arr_id}, []); {set}({func_id}, function(e) {{ {body} }});\n"
+                ))
+            }
+            Encoding::Opcode => {
+                let paths = UseInJsCodeWriter(&SerdeToJs(paths));
+                let events = if events { "" } else { ",false" };
+                self.write_command(format_args!(
+                    "[\"cb\",{arr_id},{func_id},{ret_id},{paths}{events}]\n"
+                ))
+            }
+        }
+    }
+
+    /// Replace the callback function in slot `func_id` with one that applies `options` before calling it.
+    pub(crate) fn emit_wrap_callback(&mut self, func_id: u64, options: &CallbackOptions) {
+        // Only strings, numbers, and booleans, so the same in source and opcodes.
+        let options = UseInJsCodeWriter(&SerdeToJs(options));
+        let names = self.names.clone();
+        let wrap_callback = &names.wrap_callback;
+        match self.encoding {
+            Encoding::Source => {
+                self.write_command(format_args!("{wrap_callback}({func_id},{options});\n"))
+            }
+            Encoding::Opcode => self.write_command(format_args!("[\"cw\",{func_id},{options}]\n")),
+        }
+    }
+
+    /// Move the oldest queued argument of a callback into slot `out`.
+    pub(crate) fn emit_callback_shift(&mut self, out_id: u64, arr_id: u64) {
+        let names = self.names.clone();
+        let (get, set) = (&names.get, &names.set);
+        match self.encoding {
+            Encoding::Source => {
+                self.write_command(format_args!("{set}({out_id}, {get}({arr_id}).shift());\n"))
+            }
+            Encoding::Opcode => self.write_command(format_args!("[\"cs\",{out_id},{arr_id}]\n")),
+        }
+    }
+
+    /// Like [emit_callback][Self::emit_callback], but the function returns a promise,
+    /// queued with the argument as `[arg, settle]` where `settle(rejected, value)` settles it.
+    pub(crate) fn emit_async_callback(&mut self, arr_id: u64, func_id: u64, ret_id: u64) {
+        let names = self.names.clone();
+        let (get, set, rep) = (&names.get, &names.set, &names.rep);
+        match self.encoding {
+            Encoding::Source => self.write_command(format_args!(
+                "{set}({arr_id}, []); {set}({func_id}, function(e) {{ return new Promise(function(s, j) {{ {get}({arr_id}).push([e, function(r, v) {{ (r ? j : s)(v) }}]); {rep}({ret_id}, 0) }}) }});\n"
+            )),
+            Encoding::Opcode => {
+                self.write_command(format_args!("[\"acb\",{arr_id},{func_id},{ret_id}]\n"))
+            }
+        }
+    }
+
+    /// Reject the promise of every call still queued in async callback array `arr` with `reason`.
+    pub(crate) fn emit_async_callback_reject(&mut self, arr_id: u64, reason: &str) {
+        let names = self.names.clone();
+        let get = &names.get;
+        let reason = UseInJsCodeWriter(reason);
+        match self.encoding {
+            Encoding::Source => self.write_command(format_args!(
+                "{get}({arr_id}).forEach(function(t) {{ t[1](true, {reason}) }});\n"
+            )),
+            Encoding::Opcode => self.write_command(format_args!("[\"acr\",{arr_id},{reason}]\n")),
+        }
+    }
+
+    /// Move the oldest queued argument of an async callback into slot `out`, and its settle function into `settle`.
+    pub(crate) fn emit_async_callback_shift(&mut self, out_id: u64, settle_id: u64, arr_id: u64) {
+        let names = self.names.clone();
+        let (get, set) = (&names.get, &names.set);
+        match self.encoding {
+            Encoding::Source => self.write_command(format_args!(
+                "{{var t = {get}({arr_id}).shift();{set}({out_id}, t[0]);{set}({settle_id}, t[1]);}};\n"
+            )),
+            Encoding::Opcode => self.write_command(format_args!(
+                "[\"acs\",{out_id},{settle_id},{arr_id}]\n"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use crate::{
+        Browser, Encoding, Error,
+        js_types::{JsNumber, JsString},
+        test_support::sent,
+    };
+
+    #[test]
+    fn source_encoding_is_javascript() {
+        let mut browser = Browser::new();
+        let x: JsNumber = browser.new_value(&1.5);
+        let _y = browser.call_function("Math.cos", [&x as &_], false);
+        assert_eq!(
+            sent(&mut browser),
+            "try{_w.s(2,1.5)}catch($){_w.e(2,$)};\n\
+             try{_w.s(3,Math.cos(_w.g(2),))}catch($){_w.e(3,$)};\n"
+        );
+    }
+
+    #[test]
+    fn source_encoding_uses_host_names() {
+        let names = portal_jit_host_names::MappedHostMethodNames::new(
+            crate::protocol_names::ALL_METHODS.map(|m| (m.to_string(), alloc::format!("$w{m}"))),
+        );
+        let mut browser = Browser::new().with_host_names(&names);
+        let x: JsString = browser.new_value(&"\u{1}");
+        let _y = browser.call_function("f", [&x as &_], false);
+        drop(x);
+        assert_eq!(
+            sent(&mut browser),
+            "try{_w.$ws(2,\"\\u0001\")}catch($){_w.$we(2,$)};\n\
+             try{_w.$ws(3,f(_w.$wg(2),))}catch($){_w.$we(3,$)};\n\
+             _w.$wdm([2]);\n"
+        );
+    }
+
+    #[test]
+    fn opcode_encoding_is_json_lines() {
+        let mut browser = Browser::new().with_encoding(Encoding::Opcode);
+        let document = browser.value_from_raw_code(format_args!("document"));
+        let x: JsNumber = browser.new_value(&1.5);
+        let y = browser.op_add(&x, &"px");
+        browser.set_field(&document, &"title", &y);
+        drop(y);
+        assert_eq!(
+            sent(&mut browser),
+            "[\"s\",2,[\"n\",\"document\"]]\n\
+             [\"s\",3,[\"l\",1.5]]\n\
+             [\"o\",4,\"+\",[\"r\",3],[\"l\",\"px\"]]\n\
+             [\"p\",[\"r\",2],[\"l\",\"title\"],[\"r\",4]]\n\
+             [\"dm\",[4]]\n"
+        );
+    }
+
+    #[test]
+    fn opcode_encoding_rejects_raw_code() {
+        let browser = Browser::new().with_encoding(Encoding::Opcode);
+        browser.run_raw_code(format_args!("alert(1)"));
+        assert!(matches!(
+            browser.take_error(),
+            Some(Error::RawCodeInOpcodeMode)
+        ));
+    }
+}
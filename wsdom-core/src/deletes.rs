@@ -0,0 +1,155 @@
+use core::ops::Range;
+
+use alloc::{format, vec::Vec};
+use hashbrown::HashMap;
+
+use crate::{
+    command::Encoding,
+    link::{Browser, BrowserInternal},
+};
+
+/// Slots dropped by Rust, freed on the JS client in one call per frame.
+#[derive(Debug, Default)]
+pub(crate) struct Deletes {
+    pending: Vec<u64>,
+    elide: bool,
+    /// Commands in the unsent buffer that create a value without side effects.
+    pure_creations: HashMap<u64, Range<usize>>,
+}
+
+impl Browser {
+    /// Drop values that are created and dropped before being sent, without sending either command.
+    ///
+    /// Only values created without side effects qualify, like those from `new_value` or `JsValue::duplicate`,
+    /// and only if no other unsent command uses them.
+    /// Other values still need their command to run, and are freed as usual.
+    pub fn with_drop_elision(self, enabled: bool) -> Self {
+        self.0.lock().deletes.elide = enabled;
+        self
+    }
+}
+
+impl BrowserInternal {
+    /// Note that the command just written to `range` of the buffer creates `id` without side effects.
+    pub(crate) fn record_pure_creation(&mut self, id: u64, range: Range<usize>) {
+        if self.deletes.elide {
+            self.deletes.pure_creations.insert(id, range);
+        }
+    }
+    /// Free slot `id` with the next frame, or never create it if it has not been sent yet.
+    pub(crate) fn queue_delete(&mut self, id: u64) {
+        if self.is_scoped(id) {
+            return;
+        }
+        if let Some(range) = self.deletes.pure_creations.remove(&id) {
+            if !mentions(&self.commands_buf[range.end..], id) {
+                let removed = range.len();
+                self.remove_commands(range.clone());
+                for other in self.deletes.pure_creations.values_mut() {
+                    if other.start >= range.end {
+                        *other = other.start - removed..other.end - removed;
+                    }
+                }
+                return;
+            }
+        }
+        self.deletes.pending.push(id);
+    }
+    /// Bytes of memory held by queued deletes.
+    pub(crate) fn pending_deletes_len(&self) -> usize {
+        self.deletes.pending.len() * core::mem::size_of::<u64>()
+    }
+    pub(crate) fn has_pending_deletes(&self) -> bool {
+        !self.deletes.pending.is_empty()
+    }
+    /// Write the queued deletes as a single command. Called when a frame is taken.
+    pub(crate) fn write_pending_deletes(&mut self) {
+        // The buffer is about to be sent, so nothing in it can be elided anymore.
+        self.deletes.pure_creations.clear();
+        if self.deletes.pending.is_empty() {
+            return;
+        }
+        let ids = core::mem::take(&mut self.deletes.pending);
+        let buf = &mut self.commands_buf;
+        match self.encoding {
+            Encoding::Source => buf.push_str(&self.names.del_many),
+            Encoding::Opcode => buf.push_str("[\"dm\","),
+        }
+        buf.push_str(match self.encoding {
+            Encoding::Source => "([",
+            Encoding::Opcode => "[",
+        });
+        for (i, id) in ids.iter().enumerate() {
+            if i != 0 {
+                buf.push(',');
+            }
+            // Writing integers to a String cannot fail.
+            let _ = core::fmt::Write::write_fmt(buf, format_args!("{id}"));
+        }
+        buf.push_str(match self.encoding {
+            Encoding::Source => "]);\n",
+            Encoding::Opcode => "]]\n",
+        });
+        self.mark_command_boundary();
+    }
+    pub(crate) fn clear_pending_deletes(&mut self) {
+        self.deletes = Deletes {
+            elide: self.deletes.elide,
+            ..Default::default()
+        };
+    }
+}
+
+/// Whether `commands` might use slot `id`.
+///
+/// Every reference to a slot is its id written in decimal, so this only errs on the side of yes.
+fn mentions(commands: &str, id: u64) -> bool {
+    let id = format!("{id}");
+    commands.match_indices(&*id).any(|(i, _)| {
+        let bytes = commands.as_bytes();
+        (i == 0 || !bytes[i - 1].is_ascii_digit())
+            && !bytes.get(i + id.len()).is_some_and(u8::is_ascii_digit)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Browser, Encoding, js_types::JsNumber, test_support::sent};
+
+    #[test]
+    fn drops_are_freed_in_one_call() {
+        let mut browser = Browser::new();
+        let values: alloc::vec::Vec<_> = (0..3)
+            .map(|_| browser.call_function("f", [], false))
+            .collect();
+        sent(&mut browser);
+        drop(values);
+        assert_eq!(sent(&mut browser), "_w.dm([2,3,4]);\n");
+
+        let mut browser = Browser::new().with_encoding(Encoding::Opcode);
+        drop(browser.call_function("f", [], false));
+        assert_eq!(
+            sent(&mut browser),
+            "[\"f\",2,\"f\",[],false]\n[\"dm\",[2]]\n"
+        );
+    }
+
+    #[test]
+    fn unsent_pure_values_are_elided() {
+        let mut browser = Browser::new().with_drop_elision(true);
+        let unused: JsNumber = browser.new_value(&10);
+        let used: JsNumber = browser.new_value(&20);
+        let _result = browser.call_function("f", [&used as &_], false);
+        drop(unused);
+        drop(used);
+        // A call has side effects, so its value is created and freed.
+        drop(browser.call_function("g", [], false));
+        assert_eq!(
+            sent(&mut browser),
+            "try{_w.s(3,20)}catch($){_w.e(3,$)};\n\
+             try{_w.s(4,f(_w.g(3),))}catch($){_w.e(4,$)};\n\
+             try{_w.s(5,g())}catch($){_w.e(5,$)};\n\
+             _w.dm([3,5]);\n"
+        );
+    }
+}
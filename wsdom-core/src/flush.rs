@@ -0,0 +1,224 @@
+use core::{ops::Range, task::Context, time::Duration};
+
+use alloc::{collections::VecDeque, string::String};
+
+use crate::{
+    link::{Browser, BrowserInternal},
+    timer::Sleep,
+};
+
+/// How commands are grouped into frames for the JS client.
+#[derive(Debug, Default)]
+pub(crate) struct Batching {
+    max_frame_bytes: Option<usize>,
+    max_delay: Option<Duration>,
+    /// Something is waiting for the client, so the buffer should go out now.
+    flush_due: bool,
+    /// Started by the first command of a batch.
+    delay: Option<DelaySleep>,
+    /// Offsets in `commands_buf` where a command ends, so frames can be cut there.
+    boundaries: VecDeque<usize>,
+}
+
+struct DelaySleep(Sleep);
+
+impl core::fmt::Debug for DelaySleep {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Sleep").finish_non_exhaustive()
+    }
+}
+
+impl Browser {
+    /// Split the commands for the JS client into frames of at most `bytes` (unless one command is bigger).
+    ///
+    /// Frames are only cut between commands.
+    pub fn with_max_frame_bytes(self, bytes: usize) -> Self {
+        self.0.lock().batching.max_frame_bytes = Some(bytes);
+        self
+    }
+    /// Hold fire-and-forget commands for up to `delay`, so they go out together in fewer frames.
+    ///
+    /// Commands are sent right away when something waits for the JS client
+    /// (a retrieval, an await, a [sync][Self::sync], an RPC reply), when the buffer reaches the
+    /// [max frame size][Self::with_max_frame_bytes], or when [flush][Self::flush] is called.
+    /// Needs a [timer][Self::with_timer]; without one, commands are never held.
+    ///
+    /// ```rust
+    /// # use core::time::Duration;
+    /// # use px_wsdom_core::Browser;
+    /// # fn sleep(d: Duration) -> px_wsdom_core::Sleep { unimplemented!() }
+    /// let browser = Browser::new()
+    ///     .with_timer(sleep)
+    ///     .with_max_delay(Duration::from_millis(5))
+    ///     .with_max_frame_bytes(64 * 1024);
+    /// ```
+    pub fn with_max_delay(self, delay: Duration) -> Self {
+        self.0.lock().batching.max_delay = Some(delay);
+        self
+    }
+    /// Send the buffered commands now instead of waiting for the [max delay][Self::with_max_delay].
+    pub fn flush(&self) {
+        self.0.lock().wake_outgoing();
+    }
+}
+
+impl BrowserInternal {
+    /// Remember that a command ends here.
+    pub(crate) fn mark_command_boundary(&mut self) {
+        let len = self.commands_buf.len();
+        let boundaries = &mut self.batching.boundaries;
+        if self.batching.max_frame_bytes.is_some() && boundaries.back().is_none_or(|b| *b < len) {
+            boundaries.push_back(len);
+        }
+    }
+    /// Whether a lazy wake should wake the writer now.
+    ///
+    /// Otherwise the batch delay is started, and the writer is woken once to wait on it.
+    pub(crate) fn should_wake_lazily(&mut self) -> bool {
+        let (Some(delay), Some(timer)) = (self.batching.max_delay, self.timer.as_ref()) else {
+            return true;
+        };
+        if self
+            .batching
+            .max_frame_bytes
+            .is_some_and(|max| self.commands_buf.len() >= max)
+        {
+            return true;
+        }
+        if self.batching.delay.is_some() {
+            return false;
+        }
+        self.batching.delay = Some(DelaySleep(timer.sleep(delay)));
+        true
+    }
+    /// Remove commands that turned out to be unnecessary from the unsent buffer.
+    pub(crate) fn remove_commands(&mut self, range: Range<usize>) {
+        let removed = range.len();
+        self.commands_buf.replace_range(range.clone(), "");
+        let mut previous = 0;
+        self.batching.boundaries.retain_mut(|b| {
+            if *b >= range.end {
+                *b -= removed;
+            }
+            // The end of the removed command now coincides with the end of the one before it.
+            core::mem::replace(&mut previous, *b) != *b
+        });
+    }
+    pub(crate) fn request_flush(&mut self) {
+        self.batching.flush_due = true;
+    }
+    /// Take the next frame of commands, if it is time to send one.
+    pub(crate) fn take_frame(&mut self, cx: &mut Context<'_>) -> Option<String> {
+        if (self.commands_buf.is_empty() && !self.has_pending_deletes()) || self.awaiting_hello() {
+            return None;
+        }
+        let batching = &mut self.batching;
+        let len = self.commands_buf.len();
+        let full = batching.max_frame_bytes.is_some_and(|max| len >= max);
+        let waited = match batching.delay.as_mut() {
+            Some(DelaySleep(sleep)) => sleep.as_mut().poll(cx).is_ready(),
+            None => batching.max_delay.is_none() || self.timer.is_none(),
+        };
+        if !(batching.flush_due || full || waited) {
+            return None;
+        }
+        self.write_pending_deletes();
+        let batching = &mut self.batching;
+        let len = self.commands_buf.len();
+        let cut = match batching.max_frame_bytes {
+            Some(max) if len > max => {
+                // The last command that fits, or the first command alone if it does not.
+                let fitting = batching.boundaries.iter().take_while(|b| **b <= max).last();
+                fitting
+                    .or(batching.boundaries.front())
+                    .copied()
+                    .unwrap_or(len)
+            }
+            _ => len,
+        };
+        let rest = self.commands_buf.split_off(cut);
+        let frame = core::mem::replace(&mut self.commands_buf, rest);
+        batching.boundaries.retain_mut(|b| {
+            *b = b.saturating_sub(cut);
+            *b > 0
+        });
+        if self.commands_buf.is_empty() {
+            batching.flush_due = false;
+            batching.delay = None;
+        } else {
+            // The rest is due too.
+            batching.flush_due = true;
+            cx.waker().wake_by_ref();
+        }
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{boxed::Box, sync::Arc};
+    use core::{
+        sync::atomic::{AtomicBool, Ordering},
+        task::{Context, Poll},
+        time::Duration,
+    };
+    use futures_util::{StreamExt, future::poll_fn, task::noop_waker_ref};
+
+    use crate::{Browser, timer::Sleep};
+
+    #[test]
+    fn commands_wait_for_the_batch_delay() {
+        let fired = Arc::new(AtomicBool::new(false));
+        let timer = {
+            let fired = fired.clone();
+            move |_| -> Sleep {
+                let fired = fired.clone();
+                Box::pin(poll_fn(move |_| match fired.load(Ordering::SeqCst) {
+                    true => Poll::Ready(()),
+                    false => Poll::Pending,
+                }))
+            }
+        };
+        let mut browser = Browser::new()
+            .with_timer(timer)
+            .with_max_delay(Duration::from_millis(5));
+        let mut cx = Context::from_waker(noop_waker_ref());
+        browser.run_raw_code(format_args!("a()"));
+        browser.run_raw_code(format_args!("b()"));
+        assert!(browser.poll_next_unpin(&mut cx).is_pending());
+        fired.store(true, Ordering::SeqCst);
+        assert_eq!(
+            browser.poll_next_unpin(&mut cx),
+            Poll::Ready(Some("{ a() }\n{ b() }\n".into()))
+        );
+
+        fired.store(false, Ordering::SeqCst);
+        browser.run_raw_code(format_args!("c()"));
+        assert!(browser.poll_next_unpin(&mut cx).is_pending());
+        browser.flush();
+        assert_eq!(
+            browser.poll_next_unpin(&mut cx),
+            Poll::Ready(Some("{ c() }\n".into()))
+        );
+    }
+
+    #[test]
+    fn oversized_buffer_is_split_between_commands() {
+        let mut browser = Browser::new().with_max_frame_bytes(20);
+        let mut cx = Context::from_waker(noop_waker_ref());
+        browser.run_raw_code(format_args!("a()"));
+        browser.run_raw_code(format_args!("b()"));
+        browser.run_raw_code(format_args!("multi();\nline()"));
+        browser.run_raw_code(format_args!("c()"));
+        let frames: alloc::vec::Vec<_> =
+            core::iter::from_fn(|| match browser.poll_next_unpin(&mut cx) {
+                Poll::Ready(frame) => frame,
+                Poll::Pending => None,
+            })
+            .collect();
+        assert_eq!(
+            frames,
+            ["{ a() }\n{ b() }\n", "{ multi();\nline() }\n", "{ c() }\n"]
+        );
+    }
+}
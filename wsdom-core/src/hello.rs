@@ -0,0 +1,241 @@
+use alloc::{format, string::String, vec::Vec};
+use serde::Deserialize;
+use sha3::Digest;
+
+use crate::{
+    command::{Encoding, OPCODES},
+    link::{Browser, BrowserInternal, Error},
+    protocol::ALL_METHODS,
+};
+
+/// The version of the protocol between [Browser] and the JS client.
+///
+/// Embedded in clients generated by `wsdom-gen`, which report it in their [Hello].
+pub const PROTOCOL_VERSION: u32 = 7;
+
+/// What the JS client reports about itself when it starts. See [Browser::hello].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Hello {
+    /// The client's [PROTOCOL_VERSION].
+    pub version: u32,
+    /// The encoding the client was generated for.
+    pub encoding: Encoding,
+    /// Options the client was generated with, like `"exceptions"`, `"capabilities"`, or `"rpc-timeout"`.
+    pub features: Vec<String>,
+    /// Members of the client's `_w` object.
+    pub members: Vec<String>,
+    /// The opcodes the client interprets.
+    pub opcodes: Vec<String>,
+    /// Hashes of the modules registered for [import][Browser::import].
+    pub imports: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct HelloFrame {
+    version: u32,
+    encoding: String,
+    features: Vec<String>,
+    members: Vec<String>,
+    #[serde(default)]
+    opcodes: Vec<String>,
+    imports: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Handshake {
+    required: bool,
+    hello: Option<Hello>,
+}
+
+impl Browser {
+    /// Hold every command until the JS client has said hello, and die with [Error::ProtocolMismatch]
+    /// if the client turns out not to speak this Browser's protocol.
+    ///
+    /// Clients generated by `wsdom-gen` say hello as soon as they are created.
+    /// Without the handshake, the hello is still checked when it arrives, but commands sent before it are not held back.
+    pub fn with_handshake(self, required: bool) -> Self {
+        self.0.lock().handshake.required = required;
+        self
+    }
+    /// The hello sent by the JS client, once it has arrived and been found compatible.
+    pub fn hello(&self) -> Option<Hello> {
+        self.0.lock().handshake.hello.clone()
+    }
+}
+
+impl BrowserInternal {
+    /// Whether commands must wait for the JS client's hello.
+    pub(crate) fn awaiting_hello(&self) -> bool {
+        self.handshake.required && self.handshake.hello.is_none()
+    }
+    pub(crate) fn receive_hello(&mut self, json: &str) {
+        let frame = match serde_json::from_str::<HelloFrame>(json) {
+            Ok(frame) => frame,
+            Err(e) => return self.kill(Error::DataDeserialize(e)),
+        };
+        let encoding = match &*frame.encoding {
+            "source" => Encoding::Source,
+            "opcode" => Encoding::Opcode,
+            other => {
+                return self.kill(Error::ProtocolMismatch(format!(
+                    "the client uses an unknown encoding `{other}`"
+                )));
+            }
+        };
+        let hello = Hello {
+            version: frame.version,
+            encoding,
+            features: frame.features,
+            members: frame.members,
+            opcodes: frame.opcodes,
+            imports: frame.imports,
+        };
+        if let Err(mismatch) = self.check_hello(&hello) {
+            return self.kill(Error::ProtocolMismatch(mismatch));
+        }
+        self.handshake.hello = Some(hello);
+        self.wake_outgoing();
+    }
+    fn check_hello(&self, hello: &Hello) -> Result<(), String> {
+        if hello.version != PROTOCOL_VERSION {
+            return Err(format!(
+                "the client speaks protocol version {}, not {PROTOCOL_VERSION}",
+                hello.version
+            ));
+        }
+        if hello.encoding != self.encoding {
+            return Err(format!(
+                "the client decodes {:?} commands, but the Browser sends {:?}",
+                hello.encoding, self.encoding
+            ));
+        }
+        // Opcodes do not go through `_w`, so opcode clients are checked for the opcodes themselves.
+        match self.encoding {
+            Encoding::Source => {
+                for method in ALL_METHODS {
+                    let name = self.names.property(method);
+                    if !hello.members.iter().any(|member| member == name) {
+                        return Err(format!("the client has no protocol member `{name}`"));
+                    }
+                }
+            }
+            Encoding::Opcode => {
+                for opcode in OPCODES {
+                    if !hello.opcodes.iter().any(|known| known == opcode) {
+                        return Err(format!("the client has no opcode `{opcode}`"));
+                    }
+                }
+            }
+        }
+        for name in self.imports.keys() {
+            check_import(hello, name)?;
+        }
+        Ok(())
+    }
+    /// Kill the Browser if the JS client has said hello without registering module `name`.
+    pub(crate) fn check_import(&mut self, name: &str) {
+        if let Some(Err(mismatch)) = self.handshake.hello.as_ref().map(|h| check_import(h, name)) {
+            self.kill(Error::ProtocolMismatch(mismatch));
+        }
+    }
+}
+
+/// The hash identifying module `name` in [Hello::imports].
+pub(crate) fn import_hash(name: &str) -> String {
+    hex::encode(sha3::Sha3_256::digest(name.as_bytes()))
+}
+
+fn check_import(hello: &Hello, name: &str) -> Result<(), String> {
+    match hello.imports.contains(&import_hash(name)) {
+        true => Ok(()),
+        false => Err(format!("the client has no module `{name}` to import")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::task::{Context, Poll};
+    use futures_util::{StreamExt, task::noop_waker_ref};
+
+    use super::PROTOCOL_VERSION;
+    use crate::{Browser, Encoding, Error};
+
+    fn hello(version: u32, members: &str) -> alloc::string::String {
+        alloc::format!(
+            r#"h{{"version":{version},"encoding":"source","features":[],"members":[{members}],"imports":[]}}"#
+        )
+    }
+
+    const MEMBERS: &str =
+        r#""a","g","s","d","dm","dr","r","rp","c","e","k","z","b","rb","rc","cw","x""#;
+
+    fn opcode_hello(opcodes: &str) -> alloc::string::String {
+        alloc::format!(
+            r#"h{{"version":{PROTOCOL_VERSION},"encoding":"opcode","features":[],"members":[],"opcodes":[{opcodes}],"imports":[]}}"#
+        )
+    }
+
+    const OPCODES: &str = r#""f","n","m","c","g","p","s","o","d","dm","dr","r","k","z","x","rp","aw","at","cb","cs","acb","acr","acs","b","rb","rc","cw""#;
+
+    #[test]
+    fn commands_wait_for_a_compatible_hello() {
+        let mut browser = Browser::new().with_handshake(true);
+        let mut cx = Context::from_waker(noop_waker_ref());
+        browser.run_raw_code(format_args!("a()"));
+        assert!(browser.poll_next_unpin(&mut cx).is_pending());
+        browser.receive_incoming_message(hello(PROTOCOL_VERSION, MEMBERS));
+        assert_eq!(browser.hello().unwrap().members.len(), 17);
+        assert_eq!(
+            browser.poll_next_unpin(&mut cx),
+            Poll::Ready(Some("{ a() }\n".into()))
+        );
+    }
+
+    #[test]
+    fn incompatible_clients_are_refused() {
+        let browser = Browser::new().with_handshake(true);
+        browser.receive_incoming_message(hello(PROTOCOL_VERSION, r#""g","s""#));
+        assert!(matches!(
+            browser.take_error(),
+            Some(Error::ProtocolMismatch(reason)) if reason.contains("`d`")
+        ));
+
+        let browser = Browser::new();
+        browser.receive_incoming_message(hello(PROTOCOL_VERSION + 1, MEMBERS));
+        assert!(matches!(
+            browser.take_error(),
+            Some(Error::ProtocolMismatch(_))
+        ));
+
+        let browser = Browser::new().with_encoding(Encoding::Opcode);
+        browser.receive_incoming_message(hello(PROTOCOL_VERSION, MEMBERS));
+        assert!(matches!(
+            browser.take_error(),
+            Some(Error::ProtocolMismatch(_))
+        ));
+
+        let browser = Browser::new().with_encoding(Encoding::Opcode);
+        browser.receive_incoming_message(opcode_hello(OPCODES));
+        assert!(browser.take_error().is_none());
+
+        // Clients from before async callbacks, and from before callback options.
+        for opcode in ["acb", "cw"] {
+            let browser = Browser::new().with_encoding(Encoding::Opcode);
+            let opcodes = OPCODES.replace(&alloc::format!(r#","{opcode}""#), "");
+            browser.receive_incoming_message(opcode_hello(&opcodes));
+            assert!(matches!(
+                browser.take_error(),
+                Some(Error::ProtocolMismatch(reason)) if reason.contains(&alloc::format!("`{opcode}`"))
+            ));
+        }
+
+        let browser = Browser::new();
+        browser.receive_incoming_message(hello(PROTOCOL_VERSION, MEMBERS));
+        browser.import("missing");
+        assert!(matches!(
+            browser.take_error(),
+            Some(Error::ProtocolMismatch(reason)) if reason.contains("`missing`")
+        ));
+    }
+}
@@ -1,26 +1,36 @@
 use core::{
-    fmt::Write,
     future::{Future, IntoFuture},
+    panic::Location,
     task::Poll,
+    time::Duration,
 };
 
 use alloc::{borrow::ToOwned, string::String};
 
-use crate::{
-    Browser,
-    js_types::JsValue,
-    link::RetrievalState,
-    protocol::{DEL, ERR, GET, REP, SET},
-};
+use crate::{Browser, RetrieveError, js_types::JsValue, link::RetrievalState, timer::Deadline};
 
 pub struct Await {
     browser: Browser,
     ret_id: u64,
+    /// Zero once the outcome has been taken out of the cell.
     cell_id: u64,
+    location: &'static Location<'static>,
+}
+
+impl Await {
+    /// Fail with [RetrieveError::TimedOut] if the promise has not settled `timeout` after the first poll.
+    ///
+    /// Needs a [timer][Browser::with_timer].
+    pub fn timeout(self, timeout: Duration) -> Timeout {
+        Timeout {
+            inner: self,
+            deadline: Deadline::new(Some(timeout)),
+        }
+    }
 }
 
 impl Future for Await {
-    type Output = JsValue;
+    type Output = Result<JsValue, RetrieveError>;
 
     fn poll(
         self: core::pin::Pin<&mut Self>,
@@ -29,63 +39,108 @@ impl Future for Await {
         let this = self.get_mut();
         let mut link = this.browser.0.lock();
         let ret_id = this.ret_id;
-        match link.retrievals.entry(ret_id) {
-            hashbrown::hash_map::Entry::Occupied(mut occ) => {
-                let state = occ.get_mut();
-
+        if link.is_dead() {
+            // Nothing will settle the promise now.
+            link.retrievals.remove(&ret_id);
+            return Poll::Ready(Err(RetrieveError::Disconnected));
+        }
+        match link.retrievals.get_mut(&ret_id) {
+            Some(state) => {
                 let new_waker = cx.waker();
                 if !state.waker.will_wake(new_waker) {
                     state.waker = new_waker.to_owned();
                 }
 
                 if state.times != 0 {
+                    link.retrievals.remove(&ret_id);
                     let val_id = link.get_new_id();
-                    let cell_id = this.cell_id;
-                    writeln!(
-                        link.raw_commands_buf(),
-                        "{{var v = {GET}({cell_id}).$;(v.r?{ERR}:{SET})({val_id}, v.e);{DEL}({cell_id});}};"
-                    )
-                    .unwrap();
+                    link.set_location(val_id, Some(this.location));
+                    link.count_value(val_id, this.location);
+                    let cell_id = core::mem::take(&mut this.cell_id);
+                    link.emit_await_take(val_id, cell_id);
                     link.wake_outgoing_lazy();
-                    Poll::Ready(JsValue {
+                    Poll::Ready(Ok(JsValue {
                         browser: this.browser.clone(),
                         id: val_id,
-                    })
+                    }))
                 } else {
+                    // The await command may still be waiting for a batch.
+                    link.wake_outgoing();
                     Poll::Pending
                 }
             }
-            hashbrown::hash_map::Entry::Vacant(vac) => {
-                vac.insert(RetrievalState {
-                    waker: cx.waker().to_owned(),
-                    last_value: String::new(),
-                    times: 0,
-                });
-                Poll::Pending
-            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for Await {
+    fn drop(&mut self) {
+        let mut link = self.browser.0.lock();
+        link.retrievals.remove(&self.ret_id);
+        if self.cell_id != 0 {
+            // Not taken yet; the promise may still settle, but nobody will read the cell.
+            link.emit_delete(self.cell_id);
+            link.wake_outgoing_lazy();
+        }
+    }
+}
+
+/// An [Await] with a deadline, created by [Await::timeout].
+pub struct Timeout {
+    inner: Await,
+    deadline: Deadline,
+}
+
+impl Future for Timeout {
+    type Output = Result<JsValue, RetrieveError>;
+
+    fn poll(
+        self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<Self::Output> {
+        let this = self.get_mut();
+        if let Poll::Ready(value) = core::pin::Pin::new(&mut this.inner).poll(cx) {
+            return Poll::Ready(value);
+        }
+        let link = this.inner.browser.0.lock();
+        if this.deadline.poll_elapsed(&link, cx) {
+            // Dropping `inner` frees the cell; the late notification is discarded.
+            Poll::Ready(Err(RetrieveError::TimedOut))
+        } else {
+            Poll::Pending
         }
     }
 }
+
 impl IntoFuture for JsValue {
-    type Output = JsValue;
+    type Output = Result<JsValue, RetrieveError>;
 
     type IntoFuture = Await;
 
+    #[track_caller]
     fn into_future(self) -> Self::IntoFuture {
         let mut link = self.browser.0.lock();
         let ret_id = link.get_new_id();
         let cell_id = link.get_new_id();
         let id = self.id;
-        writeln!(
-            link.raw_commands_buf(),
-            "{SET}({cell_id},{{}}); try{{Promise.prototype.then.call({GET}({id}),function(e) {{{GET}({cell_id}).$ = {{e,r:0}}; {REP}({ret_id}, 0) }},function(e) {{{GET}({cell_id}).$ = {{e,r:1}}; {REP}({ret_id}, 0) }})}}catch($){{{GET}({cell_id}).$ = {{e:e,r:1}}; {REP}({ret_id}, 0)}};"
-        )
-        .unwrap();
+        link.emit_await(cell_id, ret_id, id);
         link.wake_outgoing_lazy();
+        // Registered now, so the notification is kept even if it arrives before the first poll.
+        link.retrievals.insert(
+            ret_id,
+            RetrievalState {
+                waker: futures_util::task::noop_waker_ref().to_owned(),
+                last_value: String::new(),
+                times: 0,
+                payloads: None,
+            },
+        );
         return Await {
             browser: self.browser.clone(),
             ret_id,
             cell_id,
+            location: Location::caller(),
         };
     }
 }
@@ -10,27 +10,40 @@ Each time `f` is called like `f(arg)`,
 the Rust stream will yield the `arg` object.
 
 ```rust
-# use wsdom_core::Browser;
-async fn example(browser: &Browser, button: wsdom::dom::HTMLButtonElement) {
-    let (mut stream, func) = wsdom::callback::new_callback::<wsdom::dom::MouseEvent>(&browser);
-    button.add_event_listener(&"click", &func, &wsdom::undefined());
+# use px_wsdom_core::{Browser, UseInJsCode, js_types::JsObject};
+async fn example(browser: &Browser, button: &JsObject) {
+    let (mut stream, func) = px_wsdom_core::callback::new_callback::<JsObject>(&browser);
+    button.js_call_method("addEventListener", [&"click" as &dyn UseInJsCode, &func], false);
 
     use futures_util::StreamExt;
-    let _click_event: Option<wsdom::dom::MouseEvent> = stream.next().await;
+    let _click_event: Option<JsObject> = stream.next().await;
     println!("the button was clicked!");
 }
 ```
+
+The function returns `undefined`. For APIs that expect an async handler, [new_async_callback] makes a function that
+returns a `Promise`, and the stream yields a [Responder] along with each argument to settle it with.
+
+Work that cannot wait for a roundtrip, like `preventDefault()`, goes in the [CallbackOptions]
+given to [new_callback_with_options], which the JS function applies before queueing each event.
+
+Retrieving a property of each event, like `clientX`, takes another roundtrip.
+[new_callback_with] has the JS function read such properties when it is called and send them along,
+so the stream yields them right away; [new_payload_callback] sends only the properties.
 */
 
-use core::{fmt::Write, marker::PhantomData, pin::Pin, task::Poll};
+use core::{marker::PhantomData, panic::Location, pin::Pin, task::Poll, time::Duration};
 
-use alloc::{borrow::ToOwned, boxed::Box, string::String};
+use alloc::{borrow::ToOwned, boxed::Box, collections::VecDeque, string::String, vec::Vec};
+use serde::{Serialize, de::DeserializeOwned};
 
 use crate::{
-    js::value::JsValue,
+    js::{object::JsObject, value::JsValue},
     js_cast::JsCast,
-    link::{Browser, RetrievalState},
-    protocol::{DEL, GET, REP, SET},
+    link::{Browser, BrowserInternal, RetrievalState},
+    retrieve::RetrieveError,
+    serialize::UseInJsCode,
+    tagged,
 };
 
 /// Listens for JavaScript callbacks.
@@ -44,6 +57,10 @@ pub struct Callback<E> {
     ret_id: u64,
     browser: Browser,
     consumed: usize,
+    /// Whether the function was made by [new_async_callback], queueing a settle function with each argument.
+    responders: bool,
+    /// Where the callback was created, which its arguments are attributed to.
+    location: &'static Location<'static>,
     _phantom: PhantomData<Pin<Box<E>>>,
 }
 
@@ -54,44 +71,74 @@ impl<E: JsCast> futures_core::Stream for Callback<E> {
         self: Pin<&mut Self>,
         cx: &mut core::task::Context<'_>,
     ) -> Poll<Option<Self::Item>> {
-        let this = self.get_mut();
+        self.get_mut()
+            .poll_call(cx)
+            .map(|call| call.map(|(event, _)| event))
+    }
+}
+
+impl<E: JsCast> Callback<E> {
+    /// Take the next call's argument, and its settle function if the callback has [Responder]s.
+    fn poll_call(
+        &mut self,
+        cx: &mut core::task::Context<'_>,
+    ) -> Poll<Option<(E, Option<JsValue>)>> {
+        self.poll_ready(cx)
+            .map(|ready| ready.map(|_| self.take_argument()))
+    }
+
+    /// Move the argument of the call [poll_ready][Self::poll_ready] counted into a new slot.
+    fn take_argument(&mut self) -> (E, Option<JsValue>) {
+        let mut link = self.browser.0.lock();
+        let val_id = link.get_new_id();
+        link.count_value(val_id, self.location);
+        let arr_id = self.arr_id;
+        let settle = if self.responders {
+            let settle_id = link.get_new_id();
+            link.count_value(settle_id, self.location);
+            link.emit_async_callback_shift(val_id, settle_id, arr_id);
+            Some(JsValue {
+                id: settle_id,
+                browser: self.browser.to_owned(),
+            })
+        } else {
+            link.emit_callback_shift(val_id, arr_id);
+            None
+        };
+        link.wake_outgoing_lazy();
+        let event = JsCast::unchecked_from_js(JsValue {
+            id: val_id,
+            browser: self.browser.to_owned(),
+        });
+        (event, settle)
+    }
+}
+
+impl<E> Callback<E> {
+    /// Count the next call, along with its payload if the callback has one.
+    fn poll_ready(&mut self, cx: &mut core::task::Context<'_>) -> Poll<Option<Option<String>>> {
+        let this = self;
         let mut link = this.browser.0.lock();
         let ret_id = this.ret_id;
-        match link.retrievals.entry(ret_id) {
-            hashbrown::hash_map::Entry::Occupied(mut occ) => {
-                let state = occ.get_mut();
-
-                let new_waker = cx.waker();
-                if !state.waker.will_wake(new_waker) {
-                    state.waker = new_waker.to_owned();
-                }
-
-                if state.times > this.consumed {
-                    this.consumed += 1;
-                    let val_id = link.get_new_id();
-                    let arr_id = this.arr_id;
-                    writeln!(
-                        link.raw_commands_buf(),
-                        "{SET}({val_id}, {GET}({arr_id}).shift());"
-                    )
-                    .unwrap();
-                    link.wake_outgoing_lazy();
-                    Poll::Ready(Some(JsCast::unchecked_from_js(JsValue {
-                        id: val_id,
-                        browser: this.browser.to_owned(),
-                    })))
-                } else {
-                    Poll::Pending
-                }
-            }
-            hashbrown::hash_map::Entry::Vacant(vac) => {
-                vac.insert(RetrievalState {
-                    waker: cx.waker().to_owned(),
-                    last_value: String::new(),
-                    times: 0,
-                });
-                Poll::Pending
-            }
+        if link.is_dead() {
+            return Poll::Ready(None);
+        }
+        // Registered by every constructor, and only removed when the callback is dropped.
+        let Some(state) = link.retrievals.get_mut(&ret_id) else {
+            return Poll::Pending;
+        };
+
+        let new_waker = cx.waker();
+        if !state.waker.will_wake(new_waker) {
+            state.waker = new_waker.to_owned();
+        }
+
+        if state.times > this.consumed {
+            this.consumed += 1;
+            let payload = state.payloads.as_mut().and_then(VecDeque::pop_front);
+            Poll::Ready(Some(payload))
+        } else {
+            Poll::Pending
         }
     }
 }
@@ -100,8 +147,34 @@ impl<E> Drop for Callback<E> {
         let mut link = self.browser.0.lock();
         let ret_id = self.ret_id;
         link.retrievals.remove(&ret_id);
+        link.uncount_callback(ret_id);
         let arr_id = self.arr_id;
-        writeln!(link.raw_commands_buf(), "{DEL}({arr_id});").unwrap();
+        if self.responders {
+            // Calls not yielded yet have no Responder to settle them.
+            link.emit_async_callback_reject(
+                arr_id,
+                "WSDOM: the AsyncCallback was dropped without responding",
+            );
+            link.wake_outgoing_lazy();
+        }
+        link.emit_delete(arr_id);
+    }
+}
+
+impl BrowserInternal {
+    /// Start counting the calls of a new callback.
+    ///
+    /// Registered now, so calls are counted even if they arrive before the first poll.
+    fn register_callback(&mut self, ret_id: u64, projected: bool) {
+        self.retrievals.insert(
+            ret_id,
+            RetrievalState {
+                waker: futures_util::task::noop_waker_ref().to_owned(),
+                last_value: String::new(),
+                times: 0,
+                payloads: projected.then(VecDeque::new),
+            },
+        );
     }
 }
 
@@ -109,24 +182,569 @@ impl<E> Drop for Callback<E> {
 ///
 /// The returned Callback object is a stream. Every time the returned function is called,
 /// the stream will yield the call argument as value.
+#[track_caller]
 pub fn new_callback<E>(browser: &Browser) -> (Callback<E>, JsValue) {
+    new_callback_with_options(browser, &CallbackOptions::default())
+}
+
+/// What the JS function made by [new_callback_with_options] does with an event before queueing it for Rust.
+///
+/// All of it happens synchronously in JS, while the event is being dispatched,
+/// so it works for things that cannot wait for a roundtrip, like keeping a form from submitting.
+/// Events are filtered first; only events that pass are acted on, then throttled or debounced.
+///
+/// ```rust
+/// # use px_wsdom_core::callback::CallbackOptions;
+/// # use core::time::Duration;
+/// let submit = CallbackOptions::new().prevent_default();
+/// let enter = CallbackOptions::new().keys(["Enter"]).target("input.search");
+/// let moves = CallbackOptions::new().throttle(Duration::from_millis(50));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallbackOptions {
+    #[serde(skip_serializing_if = "core::ops::Not::not")]
+    prevent_default: bool,
+    #[serde(skip_serializing_if = "core::ops::Not::not")]
+    stop_propagation: bool,
+    #[serde(skip_serializing_if = "core::ops::Not::not")]
+    once: bool,
+    /// Milliseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    throttle: Option<f64>,
+    /// Milliseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    debounce: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keys: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<String>,
+}
+
+impl CallbackOptions {
+    /// Options that queue every event as it is, like [new_callback].
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Call `preventDefault()` on each event.
+    pub fn prevent_default(mut self) -> Self {
+        self.prevent_default = true;
+        self
+    }
+    /// Call `stopPropagation()` on each event.
+    pub fn stop_propagation(mut self) -> Self {
+        self.stop_propagation = true;
+        self
+    }
+    /// Handle only the first event; later calls do nothing.
+    pub fn once(mut self) -> Self {
+        self.once = true;
+        self
+    }
+    /// Queue at most one event per `interval`, dropping the ones in between.
+    pub fn throttle(mut self, interval: Duration) -> Self {
+        self.throttle = Some(interval.as_secs_f64() * 1000.0);
+        self
+    }
+    /// Queue an event only once `interval` has passed without another one, dropping the ones before it.
+    pub fn debounce(mut self, interval: Duration) -> Self {
+        self.debounce = Some(interval.as_secs_f64() * 1000.0);
+        self
+    }
+    /// Handle only keyboard events whose `key` is one of `keys`, like `"Enter"` or `"Escape"`.
+    pub fn keys<'a>(mut self, keys: impl IntoIterator<Item = &'a str>) -> Self {
+        self.keys = Some(keys.into_iter().map(ToOwned::to_owned).collect());
+        self
+    }
+    /// Handle only events whose `target` is, or is inside, an element matching the CSS `selector`.
+    pub fn target(mut self, selector: &str) -> Self {
+        self.target = Some(selector.to_owned());
+        self
+    }
+}
+
+/// Like [new_callback], but the JS function applies `options` to each event before queueing it.
+///
+/// ```rust
+/// # use px_wsdom_core::{Browser, UseInJsCode, callback::{CallbackOptions, new_callback_with_options}, js_types::JsObject};
+/// async fn example(browser: &Browser, form: &JsObject) {
+///     let options = CallbackOptions::new().prevent_default();
+///     let (mut submits, func) = new_callback_with_options::<JsObject>(browser, &options);
+///     form.js_call_method("addEventListener", [&"submit" as &dyn UseInJsCode, &func], false);
+///
+///     use futures_util::StreamExt;
+///     let _submit = submits.next().await;
+///     println!("the form was submitted, but the page did not navigate away");
+/// }
+/// ```
+#[track_caller]
+pub fn new_callback_with_options<E>(
+    browser: &Browser,
+    options: &CallbackOptions,
+) -> (Callback<E>, JsValue) {
+    let mut link = browser.0.lock();
+    let arr_id = link.get_new_id();
+    let ret_id = link.get_new_id();
+    let func_id = link.get_new_value_id();
+    let func = JsValue {
+        browser: browser.to_owned(),
+        id: func_id,
+    };
+    link.count_callback(ret_id, Location::caller());
+    link.register_callback(ret_id, false);
+    link.emit_callback(arr_id, func_id, ret_id);
+    if *options != CallbackOptions::default() {
+        link.emit_wrap_callback(func_id, options);
+    }
+    link.wake_outgoing_lazy();
+    let callback = Callback {
+        browser: browser.to_owned(),
+        ret_id,
+        arr_id,
+        consumed: 0,
+        responders: false,
+        location: Location::caller(),
+        _phantom: PhantomData,
+    };
+    (callback, func)
+}
+
+/// Listens for calls of a JavaScript function that returns a `Promise`, so each can be answered.
+///
+/// This implements the [Stream][futures_core::Stream] trait;
+/// the stream yields the argument of each call along with the [Responder] that settles the promise it returned.
+///
+/// Dropping it rejects the promises of the calls it has not yielded yet.
+///
+/// The [new_async_callback] function creates an AsyncCallback.
+pub struct AsyncCallback<E>(Callback<E>);
+
+impl<E: JsCast> futures_core::Stream for AsyncCallback<E> {
+    type Item = (E, Responder);
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        self.get_mut().0.poll_call(cx).map(|call| {
+            call.map(|(event, settle)| {
+                let settle = settle.map(JsCast::unchecked_from_js);
+                (event, Responder { settle })
+            })
+        })
+    }
+}
+
+/// Settles the `Promise` returned by one call of a function made by [new_async_callback].
+///
+/// Dropping a Responder without [resolving][Self::resolve] or [rejecting][Self::reject] rejects the promise,
+/// so the JS side is never left waiting.
+pub struct Responder {
+    settle: Option<JsObject>,
+}
+
+impl Responder {
+    /// Resolve the promise with `value`.
+    #[track_caller]
+    pub fn resolve(mut self, value: &dyn UseInJsCode) {
+        self.settle(false, value);
+    }
+    /// Reject the promise with `error`.
+    #[track_caller]
+    pub fn reject(mut self, error: &dyn UseInJsCode) {
+        self.settle(true, error);
+    }
+    #[track_caller]
+    fn settle(&mut self, rejected: bool, value: &dyn UseInJsCode) {
+        if let Some(settle) = self.settle.take() {
+            settle.js_call_self([&rejected as &dyn UseInJsCode, value], false);
+        }
+    }
+}
+
+impl Drop for Responder {
+    fn drop(&mut self) {
+        self.settle(true, &"WSDOM: the Responder was dropped without responding");
+    }
+}
+
+/// Create a new AsyncCallback and a corresponding JavaScript function that returns a `Promise`.
+///
+/// Every time the returned function is called, the stream yields the call argument
+/// and a [Responder]; the promise the call returned settles when the Responder is used.
+///
+/// ```rust
+/// # use px_wsdom_core::{Browser, js_types::JsObject};
+/// use futures_util::StreamExt;
+/// async fn example(browser: &Browser, worker: &JsObject) {
+///     let (mut calls, handler) = px_wsdom_core::callback::new_async_callback::<JsObject>(browser);
+///     worker.js_set_field(&"onfetch", &handler);
+///     while let Some((request, responder)) = calls.next().await {
+///         let url: String = request.js_get_field(&"url").retrieve().await.unwrap();
+///         responder.resolve(&url.len());
+///     }
+/// }
+/// ```
+#[track_caller]
+pub fn new_async_callback<E>(browser: &Browser) -> (AsyncCallback<E>, JsValue) {
     let mut link = browser.0.lock();
     let arr_id = link.get_new_id();
     let ret_id = link.get_new_id();
-    let func_id = link.get_new_id();
+    let func_id = link.get_new_value_id();
     let func = JsValue {
         browser: browser.to_owned(),
         id: func_id,
     };
-    writeln!(link.raw_commands_buf(),
-"{SET}({arr_id}, []); {SET}({func_id}, function(e) {{ {GET}({arr_id}).push(e); {REP}({ret_id}, 0) }});").unwrap();
+    link.count_callback(ret_id, Location::caller());
+    link.register_callback(ret_id, false);
+    link.emit_async_callback(arr_id, func_id, ret_id);
     link.wake_outgoing_lazy();
     let callback = Callback {
         browser: browser.to_owned(),
         ret_id,
         arr_id,
         consumed: 0,
+        responders: true,
+        location: Location::caller(),
+        _phantom: PhantomData,
+    };
+    (AsyncCallback(callback), func)
+}
+
+/// Listens for JavaScript callbacks, receiving some properties of each argument along with the call.
+///
+/// This implements the [Stream][futures_core::Stream] trait;
+/// the stream yields the properties, deserialized as `T`, and the argument.
+/// Properties that do not deserialize as `T` come as [RetrieveError::Deserialize],
+/// still along with their argument; the stream carries on with the next call.
+///
+/// The [new_callback_with] function creates a ProjectedCallback.
+pub struct ProjectedCallback<T, E> {
+    callback: Callback<E>,
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned, E: JsCast> futures_core::Stream for ProjectedCallback<T, E> {
+    type Item = (Result<T, RetrieveError>, E);
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let callback = &mut self.get_mut().callback;
+        let Poll::Ready(ready) = callback.poll_payload(cx) else {
+            return Poll::Pending;
+        };
+        Poll::Ready(ready.map(|payload| (payload, callback.take_argument().0)))
+    }
+}
+
+/// Listens for JavaScript callbacks, receiving only some properties of each argument.
+///
+/// This implements the [Stream][futures_core::Stream] trait;
+/// the stream yields the properties, deserialized as `T`.
+/// The argument itself stays in JS, so no further commands are sent.
+/// Properties that do not deserialize as `T` come as [RetrieveError::Deserialize].
+///
+/// The [new_payload_callback] function creates a PayloadCallback.
+pub struct PayloadCallback<T> {
+    callback: Callback<()>,
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> futures_core::Stream for PayloadCallback<T> {
+    type Item = Result<T, RetrieveError>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        self.get_mut().callback.poll_payload(cx)
+    }
+}
+
+impl<E> Callback<E> {
+    /// Count the next call and deserialize its payload.
+    ///
+    /// Only this call fails if its payload does not deserialize as `T`, so the caller must still
+    /// take the call's argument.
+    fn poll_payload<T: DeserializeOwned>(
+        &mut self,
+        cx: &mut core::task::Context<'_>,
+    ) -> Poll<Option<Result<T, RetrieveError>>> {
+        let payload = match self.poll_ready(cx) {
+            Poll::Ready(Some(payload)) => payload.unwrap_or_default(),
+            Poll::Ready(None) => return Poll::Ready(None),
+            Poll::Pending => return Poll::Pending,
+        };
+        let json = payload.split_once(':').map_or("", |(_, json)| json);
+        Poll::Ready(Some(
+            tagged::from_str(json).map_err(RetrieveError::Deserialize),
+        ))
+    }
+}
+
+/// Like [new_callback], but the JS function also reads the properties at `paths` off each argument
+/// and sends them with the call, so they arrive without another roundtrip.
+///
+/// A path is a property name, or several joined with `.`, like `"target.value"`;
+/// a missing property reads as `undefined` instead of throwing.
+/// The values arrive in the order of `paths`, as an array deserialized as `T`,
+/// so `T` is usually a tuple or a struct with one field per path.
+///
+/// ```rust
+/// # use px_wsdom_core::{Browser, UseInJsCode, js_types::{JsObject, JsValue}};
+/// async fn example(browser: &Browser, canvas: &JsObject) {
+///     let (mut clicks, func) =
+///         px_wsdom_core::callback::new_callback_with::<(f64, f64), JsValue>(browser, &["clientX", "clientY"]);
+///     canvas.js_call_method("addEventListener", [&"click" as &dyn UseInJsCode, &func], false);
+///
+///     use futures_util::StreamExt;
+///     while let Some((payload, _event)) = clicks.next().await {
+///         let Ok((x, y)) = payload else { continue };
+///         println!("clicked at {x}, {y}");
+///     }
+/// }
+/// ```
+#[track_caller]
+pub fn new_callback_with<T, E>(
+    browser: &Browser,
+    paths: &[&str],
+) -> (ProjectedCallback<T, E>, JsValue) {
+    let (callback, func) = new_projected_callback(browser, paths, true);
+    let callback = ProjectedCallback {
+        callback,
+        _phantom: PhantomData,
+    };
+    (callback, func)
+}
+
+/// Like [new_callback_with], but only the properties are sent; the arguments are never queued.
+///
+/// ```rust
+/// # use px_wsdom_core::{Browser, UseInJsCode, js_types::JsObject};
+/// async fn example(browser: &Browser, input: &JsObject) {
+///     let (mut values, func) =
+///         px_wsdom_core::callback::new_payload_callback::<(String,)>(browser, &["target.value"]);
+///     input.js_call_method("addEventListener", [&"input" as &dyn UseInJsCode, &func], false);
+///
+///     use futures_util::StreamExt;
+///     while let Some(payload) = values.next().await {
+///         let Ok((value,)) = payload else { continue };
+///         println!("the input now holds {value:?}");
+///     }
+/// }
+/// ```
+#[track_caller]
+pub fn new_payload_callback<T>(browser: &Browser, paths: &[&str]) -> (PayloadCallback<T>, JsValue) {
+    let (callback, func) = new_projected_callback(browser, paths, false);
+    let callback = PayloadCallback {
+        callback,
         _phantom: PhantomData,
     };
     (callback, func)
 }
+
+#[track_caller]
+fn new_projected_callback<E>(
+    browser: &Browser,
+    paths: &[&str],
+    events: bool,
+) -> (Callback<E>, JsValue) {
+    let mut link = browser.0.lock();
+    let arr_id = link.get_new_id();
+    let ret_id = link.get_new_id();
+    let func_id = link.get_new_value_id();
+    let func = JsValue {
+        browser: browser.to_owned(),
+        id: func_id,
+    };
+    link.count_callback(ret_id, Location::caller());
+    link.register_callback(ret_id, true);
+    link.emit_projected_callback(arr_id, func_id, ret_id, paths, events);
+    link.wake_outgoing_lazy();
+    let callback = Callback {
+        browser: browser.to_owned(),
+        ret_id,
+        arr_id,
+        consumed: 0,
+        responders: false,
+        location: Location::caller(),
+        _phantom: PhantomData,
+    };
+    (callback, func)
+}
+
+#[cfg(test)]
+mod tests {
+    use core::task::{Context, Poll};
+    use futures_util::{StreamExt, task::noop_waker_ref};
+
+    use alloc::string::String;
+
+    use super::{
+        CallbackOptions, new_async_callback, new_callback_with, new_callback_with_options,
+        new_payload_callback,
+    };
+    use crate::{
+        Browser, Capabilities, Encoding, RetrieveError, js_types::JsValue, test_support::sent,
+    };
+
+    #[test]
+    fn async_callback_settles_promises() {
+        let mut browser = Browser::new();
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let (mut calls, _func) = new_async_callback::<JsValue>(&browser);
+        assert!(calls.poll_next_unpin(&mut cx).is_pending());
+        let frame = sent(&mut browser);
+        assert!(frame.contains("return new Promise"));
+
+        browser.receive_incoming_message("p3:0".into());
+        browser.receive_incoming_message("p3:0".into());
+        let Poll::Ready(Some((_event, responder))) = calls.poll_next_unpin(&mut cx) else {
+            panic!("call not yielded");
+        };
+        responder.resolve(&7);
+        let Poll::Ready(Some((_event, responder))) = calls.poll_next_unpin(&mut cx) else {
+            panic!("call not yielded");
+        };
+        drop(responder);
+        assert_eq!(
+            sent(&mut browser),
+            "{var t = _w.g(2).shift();_w.s(5, t[0]);_w.s(6, t[1]);};\n\
+             try{_w.s(7,(_w.g(6))(false,7,))}catch($){_w.e(7,$)};\n\
+             {var t = _w.g(2).shift();_w.s(8, t[0]);_w.s(9, t[1]);};\n\
+             try{_w.s(10,(_w.g(9))(true,\"WSDOM: the Responder was dropped without responding\",))}catch($){_w.e(10,$)};\n\
+             _w.dm([7,6,10,9]);\n"
+        );
+    }
+
+    #[test]
+    fn async_calls_before_the_first_poll_are_kept() {
+        let mut browser = Browser::new().with_encoding(Encoding::Opcode);
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let (mut calls, _func) = new_async_callback::<JsValue>(&browser);
+        sent(&mut browser);
+        browser.receive_incoming_message("p3:0".into());
+        browser.receive_incoming_message("p3:0".into());
+        let Poll::Ready(Some((_event, responder))) = calls.poll_next_unpin(&mut cx) else {
+            panic!("call not yielded");
+        };
+        responder.resolve(&1);
+        sent(&mut browser);
+
+        // The second call was never yielded, so dropping the stream rejects it.
+        drop(calls);
+        assert_eq!(
+            sent(&mut browser),
+            "[\"acr\",2,\"WSDOM: the AsyncCallback was dropped without responding\"]\n\
+             [\"dm\",[2]]\n"
+        );
+    }
+
+    #[test]
+    fn options_wrap_the_function() {
+        let options = CallbackOptions::new()
+            .prevent_default()
+            .throttle(core::time::Duration::from_millis(50))
+            .keys(["Enter"]);
+        let mut browser = Browser::new();
+        let (_calls, _func) = new_callback_with_options::<JsValue>(&browser, &options);
+        let frame = sent(&mut browser);
+        assert!(frame.ends_with(
+            "_w.cw(4,{\"preventDefault\":true,\"throttle\":50.0,\"keys\":[\"Enter\"]});\n"
+        ));
+
+        let mut browser = Browser::new().with_encoding(Encoding::Opcode);
+        let (_calls, _func) =
+            new_callback_with_options::<JsValue>(&browser, &CallbackOptions::new().once());
+        assert_eq!(
+            sent(&mut browser),
+            "[\"cb\",2,4,3]\n[\"cw\",4,{\"once\":true}]\n"
+        );
+    }
+
+    #[test]
+    fn calls_before_the_first_poll_are_kept() {
+        let mut browser = Browser::new();
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let options = CallbackOptions::new().prevent_default();
+        let (mut calls, _func) = new_callback_with_options::<JsValue>(&browser, &options);
+        sent(&mut browser);
+        browser.receive_incoming_message("p3:0".into());
+        browser.receive_incoming_message("p3:0".into());
+        assert!(calls.poll_next_unpin(&mut cx).is_ready());
+        assert!(calls.poll_next_unpin(&mut cx).is_ready());
+        assert!(calls.poll_next_unpin(&mut cx).is_pending());
+    }
+
+    #[test]
+    fn projected_callback_ships_payloads() {
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let mut browser = Browser::new();
+        let (mut clicks, _func) = new_callback_with::<(f64, Option<String>), JsValue>(
+            &browser,
+            &["clientX", "target.value"],
+        );
+        assert!(sent(&mut browser).ends_with(
+            "_w.s(4, function(e) { _w.g(2).push(e); _w.r(3, [e?.[\"clientX\"],e?.[\"target\"]?.[\"value\"],]) });\n"
+        ));
+
+        // Both calls arrive before the stream is first polled.
+        browser.receive_incoming_message("p3:[1.5,\"hi\"]".into());
+        browser.receive_incoming_message("p3:[2,{\"$\":\"u\"}]".into());
+        let Poll::Ready(Some((payload, _event))) = clicks.poll_next_unpin(&mut cx) else {
+            panic!("call not yielded");
+        };
+        assert_eq!(payload.unwrap(), (1.5, Some("hi".into())));
+        let Poll::Ready(Some((payload, _event))) = clicks.poll_next_unpin(&mut cx) else {
+            panic!("call not yielded");
+        };
+        assert_eq!(payload.unwrap(), (2.0, None));
+        assert!(clicks.poll_next_unpin(&mut cx).is_pending());
+
+        let mut browser = Browser::new().with_encoding(Encoding::Opcode);
+        let (mut values, _func) = new_payload_callback::<(String,)>(&browser, &["target.value"]);
+        assert!(values.poll_next_unpin(&mut cx).is_pending());
+        assert_eq!(
+            sent(&mut browser),
+            "[\"cb\",2,4,3,[\"target.value\"],false]\n"
+        );
+        browser.receive_incoming_message("p3:[\"hi\"]".into());
+        let Poll::Ready(Some(payload)) = values.poll_next_unpin(&mut cx) else {
+            panic!("call not yielded");
+        };
+        assert_eq!(payload.unwrap(), ("hi".into(),));
+        // Only the payload was needed, so nothing more is sent.
+        assert_eq!(sent(&mut browser), "");
+    }
+
+    #[test]
+    fn mismatched_payloads_fail_only_their_call() {
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let browser = Browser::new();
+        let (mut clicks, _func) = new_callback_with::<(f64,), JsValue>(&browser, &["clientX"]);
+        browser.receive_incoming_message("p3:[\"left\"]".into());
+        browser.receive_incoming_message("p3:[2]".into());
+        let Poll::Ready(Some((payload, first))) = clicks.poll_next_unpin(&mut cx) else {
+            panic!("call not yielded");
+        };
+        assert!(matches!(payload, Err(RetrieveError::Deserialize(_))));
+        let Poll::Ready(Some((payload, second))) = clicks.poll_next_unpin(&mut cx) else {
+            panic!("call not yielded");
+        };
+        assert_eq!(payload.unwrap(), (2.0,));
+        // Each call still comes with its own argument.
+        assert_ne!(first.id, second.id);
+        assert!(browser.take_error().is_none());
+    }
+
+    #[test]
+    fn projected_paths_need_capabilities() {
+        let browser = Browser::new()
+            .with_encoding(Encoding::Opcode)
+            .with_capabilities(Capabilities::new().allow_property("target"));
+        let (_values, _func) = new_payload_callback::<(String,)>(&browser, &["target.value"]);
+        assert!(browser.0.lock().is_dead());
+    }
+}
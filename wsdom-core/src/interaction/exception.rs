@@ -0,0 +1,212 @@
+/*!
+Reporting of JS exceptions.
+
+Exceptions thrown by commands are normally stored in the value the command was creating,
+and only surface if that value is later retrieved.
+A WSDOM client generated with exception reporting enabled (see `wsdom-gen`)
+also sends every such exception back to Rust, where it can be read from [Browser::exceptions].
+
+```rust
+# use px_wsdom_core::Browser;
+async fn log_exceptions(browser: &Browser) {
+    use futures_util::StreamExt;
+    let mut exceptions = browser.exceptions();
+    while let Some(exception) = exceptions.next().await {
+        println!("{}: {}", exception.name, exception.message);
+    }
+}
+```
+*/
+
+use core::{pin::Pin, task::Poll};
+
+use alloc::{
+    borrow::ToOwned,
+    collections::VecDeque,
+    string::String,
+    sync::{Arc, Weak},
+};
+use core::{panic::Location, task::Waker};
+use serde::Deserialize;
+use spin::Mutex;
+
+use crate::link::{Browser, BrowserInternal, Error};
+
+pub(crate) type ExceptionListener = Weak<Mutex<ExceptionQueue>>;
+
+/// An exception thrown on the JS side.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct JsException {
+    /// The id of the value the exception was stored in, in place of the value the command would have created.
+    #[serde(skip)]
+    pub id: u64,
+    /// The exception's `name`, like `TypeError`, or its type if it is not an object.
+    pub name: String,
+    /// The exception's `message`, or the exception itself converted to string.
+    pub message: String,
+    /// The exception's `stack`, if it has one.
+    pub stack: Option<String>,
+    /// Where in Rust the failing value was created,
+    /// if the Browser was created [with_locations][Browser::with_locations].
+    #[serde(skip)]
+    pub location: Option<&'static Location<'static>>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ExceptionQueue {
+    waker: Option<Waker>,
+    queue: VecDeque<JsException>,
+    closed: bool,
+}
+
+impl ExceptionQueue {
+    /// End the stream once the queue is drained.
+    pub(crate) fn close(&mut self) {
+        self.closed = true;
+        if let Some(waker) = self.waker.as_ref() {
+            waker.wake_by_ref();
+        }
+    }
+}
+
+/// A stream of [JsException]s reported by the JS client.
+///
+/// Created by [Browser::exceptions].
+pub struct Exceptions {
+    state: Arc<Mutex<ExceptionQueue>>,
+}
+
+impl Browser {
+    /// Listen for exceptions reported by the JS client.
+    ///
+    /// The JS client must be generated with exception reporting enabled,
+    /// otherwise the returned stream never yields anything.
+    /// Each stream yields every exception reported after it was created,
+    /// and ends when the Browser is [closed][Browser::close].
+    pub fn exceptions(&self) -> Exceptions {
+        let mut link = self.0.lock();
+        let state = Arc::new(Mutex::new(ExceptionQueue {
+            closed: link.is_dead(),
+            ..ExceptionQueue::default()
+        }));
+        link.exception_listeners
+            .retain(|listener| listener.strong_count() > 0);
+        link.exception_listeners.push(Arc::downgrade(&state));
+        Exceptions { state }
+    }
+}
+
+impl futures_core::Stream for Exceptions {
+    type Item = JsException;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let mut state = self.state.lock();
+        match state.queue.pop_front() {
+            Some(exception) => Poll::Ready(Some(exception)),
+            None if state.closed => Poll::Ready(None),
+            None => {
+                let new_waker = cx.waker();
+                if !state.waker.as_ref().is_some_and(|w| new_waker.will_wake(w)) {
+                    state.waker = Some(new_waker.to_owned());
+                }
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl BrowserInternal {
+    /// Handle an `e<id>:<json>` message from the JS client.
+    pub(crate) fn receive_exception(&mut self, message: &str) {
+        let Some((id, json)) = message
+            .split_once(':')
+            .and_then(|(id, json)| Some((id.parse::<u64>().ok()?, json)))
+        else {
+            return;
+        };
+        if self.exception_listeners.is_empty() {
+            return;
+        }
+        let exception = match serde_json::from_str::<JsException>(json) {
+            Ok(exception) => JsException {
+                id,
+                location: self.location(id),
+                ..exception
+            },
+            Err(e) => return self.kill(Error::DataDeserialize(e)),
+        };
+        // Deliver to every live stream, forgetting the dropped ones.
+        self.exception_listeners.retain(|listener| {
+            let Some(state) = listener.upgrade() else {
+                return false;
+            };
+            let mut state = state.lock();
+            state.queue.push_back(exception.clone());
+            if let Some(waker) = state.waker.as_ref() {
+                waker.wake_by_ref();
+            }
+            true
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::task::{Context, Poll};
+    use futures_util::{StreamExt, task::noop_waker_ref};
+
+    use super::JsException;
+    use crate::Browser;
+
+    #[test]
+    fn reported_exceptions_reach_every_stream() {
+        let browser = Browser::new();
+        browser.receive_incoming_message(
+            r#"e2:{"name":"TypeError","message":"too early","stack":null}"#.into(),
+        );
+        let mut first = browser.exceptions();
+        let mut second = browser.exceptions();
+        browser.receive_incoming_message(
+            r#"e3:{"name":"TypeError","message":"x is not a function","stack":"at <anonymous>"}"#
+                .into(),
+        );
+        let expected = JsException {
+            id: 3,
+            name: "TypeError".into(),
+            message: "x is not a function".into(),
+            stack: Some("at <anonymous>".into()),
+            location: None,
+        };
+        let mut cx = Context::from_waker(noop_waker_ref());
+        for stream in [&mut first, &mut second] {
+            assert_eq!(
+                stream.poll_next_unpin(&mut cx),
+                Poll::Ready(Some(expected.clone()))
+            );
+            assert_eq!(stream.poll_next_unpin(&mut cx), Poll::Pending);
+        }
+        assert!(browser.take_error().is_none());
+    }
+
+    #[test]
+    fn exceptions_carry_locations() {
+        let browser = Browser::new().with_locations(true);
+        let mut exceptions = browser.exceptions();
+        let (value, line) = (browser.call_function("f", [], false), line!());
+        let location = value.location().unwrap();
+        assert_eq!((location.file(), location.line()), (file!(), line));
+
+        browser.receive_incoming_message(alloc::format!(
+            r#"e{}:{{"name":"Error","message":"","stack":null}}"#,
+            value.id
+        ));
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let Poll::Ready(Some(exception)) = exceptions.poll_next_unpin(&mut cx) else {
+            panic!("exception not reported");
+        };
+        assert_eq!(exception.location, Some(location));
+    }
+}
@@ -1,2 +1,3 @@
 pub mod r#await;
 pub mod callback;
+pub mod exception;
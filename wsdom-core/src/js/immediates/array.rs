@@ -1,3 +1,4 @@
+use alloc::vec::Vec;
 use core::fmt::Write;
 
 use crate::serialize::UseInJsCode;
@@ -7,12 +8,38 @@ where
     U: UseInJsCode,
 {
     fn serialize_to(&self, buf: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        buf.write_char('[')?;
-        for item in self.iter() {
+        serialize_array(self.iter().map(|item| *item as &dyn UseInJsCode), buf)
+    }
+}
+
+/// An array of values whose number is only known at runtime.
+pub(crate) struct ArrayOf<'a>(pub(crate) Vec<&'a dyn UseInJsCode>);
+
+impl UseInJsCode for ArrayOf<'_> {
+    fn serialize_to(&self, buf: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        serialize_array(self.0.iter().copied(), buf)
+    }
+}
+
+fn serialize_array<'a>(
+    items: impl Iterator<Item = &'a dyn UseInJsCode>,
+    buf: &mut core::fmt::Formatter<'_>,
+) -> core::fmt::Result {
+    if buf.alternate() {
+        buf.write_str("[\"a\",[")?;
+        for (i, item) in items.enumerate() {
+            if i != 0 {
+                buf.write_char(',')?;
+            }
             item.serialize_to(buf)?;
-            buf.write_char(',')?;
         }
-        buf.write_char(']')?;
-        Ok(())
+        return buf.write_str("]]");
+    }
+    buf.write_char('[')?;
+    for item in items {
+        item.serialize_to(buf)?;
+        buf.write_char(',')?;
     }
+    buf.write_char(']')?;
+    Ok(())
 }
@@ -1,4 +1,4 @@
-mod array;
+pub(crate) mod array;
 mod nullable;
 mod primitives;
 
@@ -8,7 +8,11 @@ pub struct NullImmediate;
 
 impl UseInJsCode for NullImmediate {
     fn serialize_to(&self, buf: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        RawCodeImmediate("null").serialize_to(buf)
+        if buf.alternate() {
+            buf.write_str("[\"l\",null]")
+        } else {
+            RawCodeImmediate("null").serialize_to(buf)
+        }
     }
 }
 
@@ -18,7 +22,11 @@ impl<T> ToJs<JsNullable<T>> for NullImmediate {}
 pub struct UndefinedImmediate;
 impl UseInJsCode for UndefinedImmediate {
     fn serialize_to(&self, buf: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        RawCodeImmediate("undefined").serialize_to(buf)
+        if buf.alternate() {
+            buf.write_str("[\"u\"]")
+        } else {
+            RawCodeImmediate("undefined").serialize_to(buf)
+        }
     }
 }
 
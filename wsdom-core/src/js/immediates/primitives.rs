@@ -15,6 +15,21 @@ macro_rules! impl_use_in_js {
     };
 }
 
+/// Integers are [JsNumber]s, so even the 64-bit ones [SerdeToJs] would send as `BigInt`s are sent as a `Number`.
+macro_rules! impl_use_in_js_integer {
+    ($name:ident) => {
+        impl UseInJsCode for $name {
+            fn serialize_to(&self, buf: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                match buf.alternate() {
+                    false => write!(buf, "{self}"),
+                    true => write!(buf, "[\"l\",{self}]"),
+                }
+            }
+        }
+        impl ToJs<JsValue> for $name {}
+    };
+}
+
 impl ToJs<JsBoolean> for bool {}
 impl_use_in_js!(bool);
 impl ToJs<JsString> for str {}
@@ -42,13 +57,13 @@ impl ToJs<JsNumber> for f64 {}
 
 impl_use_in_js!(f64);
 impl_use_in_js!(f32);
-impl_use_in_js!(usize);
-impl_use_in_js!(u64);
-impl_use_in_js!(u32);
-impl_use_in_js!(u16);
-impl_use_in_js!(u8);
-impl_use_in_js!(isize);
-impl_use_in_js!(i64);
-impl_use_in_js!(i32);
-impl_use_in_js!(i16);
-impl_use_in_js!(i8);
+impl_use_in_js_integer!(usize);
+impl_use_in_js_integer!(u64);
+impl_use_in_js_integer!(u32);
+impl_use_in_js_integer!(u16);
+impl_use_in_js_integer!(u8);
+impl_use_in_js_integer!(isize);
+impl_use_in_js_integer!(i64);
+impl_use_in_js_integer!(i32);
+impl_use_in_js_integer!(i16);
+impl_use_in_js_integer!(i8);
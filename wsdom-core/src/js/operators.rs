@@ -1,62 +1,101 @@
 use super::primitives::{JsBoolean, JsNumber, JsString};
 use super::value::JsValue;
 use crate::link::Browser;
-use crate::serialize::{UseInJsCode, UseInJsCodeWriter};
+use crate::serialize::UseInJsCode;
 use crate::{JsCast, ToJs};
 
+impl Browser {
+    #[track_caller]
+    fn operator(&self, op: &str, operands: &[&dyn UseInJsCode]) -> JsValue {
+        let mut link = self.0.lock();
+        let out_id = link.get_new_value_id();
+        link.emit_operator(out_id, op, operands);
+        link.wake_outgoing_lazy();
+        JsValue {
+            id: out_id,
+            browser: self.clone(),
+        }
+    }
+}
+
 #[rustfmt::skip]
 impl Browser {
     /// The JavaScript `+` operator
-    pub fn op_add           (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsValue    {self.value_from_raw_code(format_args!("(({}) + ({}))",     UseInJsCodeWriter(a), UseInJsCodeWriter(b))).unchecked_into()}
+    #[track_caller]
+    pub fn op_add           (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsValue    {self.operator("+",   &[a, b]).unchecked_into()}
     /// The JavaScript `-` operator
-    pub fn op_sub           (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsNumber   {self.value_from_raw_code(format_args!("(({}) - ({}))",     UseInJsCodeWriter(a), UseInJsCodeWriter(b))).unchecked_into()}
+    #[track_caller]
+    pub fn op_sub           (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsNumber   {self.operator("-",   &[a, b]).unchecked_into()}
     /// The JavaScript `*` operator
-    pub fn op_mul           (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsNumber   {self.value_from_raw_code(format_args!("(({}) * ({}))",     UseInJsCodeWriter(a), UseInJsCodeWriter(b))).unchecked_into()}
+    #[track_caller]
+    pub fn op_mul           (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsNumber   {self.operator("*",   &[a, b]).unchecked_into()}
     /// The JavaScript `/` operator
-    pub fn op_div           (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsNumber   {self.value_from_raw_code(format_args!("(({}) / ({}))",     UseInJsCodeWriter(a), UseInJsCodeWriter(b))).unchecked_into()}
+    #[track_caller]
+    pub fn op_div           (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsNumber   {self.operator("/",   &[a, b]).unchecked_into()}
     /// The JavaScript `%` operator
-    pub fn op_remainder     (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsNumber   {self.value_from_raw_code(format_args!("(({}) % ({}))",     UseInJsCodeWriter(a), UseInJsCodeWriter(b))).unchecked_into()}
+    #[track_caller]
+    pub fn op_remainder     (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsNumber   {self.operator("%",   &[a, b]).unchecked_into()}
     /// The JavaScript `**` operator
-    pub fn op_exponent      (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsNumber   {self.value_from_raw_code(format_args!("(({}) ** ({}))",    UseInJsCodeWriter(a), UseInJsCodeWriter(b))).unchecked_into()}
+    #[track_caller]
+    pub fn op_exponent      (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsNumber   {self.operator("**",  &[a, b]).unchecked_into()}
     /// The JavaScript `&` operator
-    pub fn op_bitwise_and   (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsNumber   {self.value_from_raw_code(format_args!("(({}) & ({}))",     UseInJsCodeWriter(a), UseInJsCodeWriter(b))).unchecked_into()}
+    #[track_caller]
+    pub fn op_bitwise_and   (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsNumber   {self.operator("&",   &[a, b]).unchecked_into()}
     /// The JavaScript `|` operator
-    pub fn op_bitwise_or    (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsNumber   {self.value_from_raw_code(format_args!("(({}) | ({}))",     UseInJsCodeWriter(a), UseInJsCodeWriter(b))).unchecked_into()}
+    #[track_caller]
+    pub fn op_bitwise_or    (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsNumber   {self.operator("|",   &[a, b]).unchecked_into()}
     /// The JavaScript `^` operator
-    pub fn op_bitwise_xor   (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsNumber   {self.value_from_raw_code(format_args!("(({}) ^ ({}))",     UseInJsCodeWriter(a), UseInJsCodeWriter(b))).unchecked_into()}
+    #[track_caller]
+    pub fn op_bitwise_xor   (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsNumber   {self.operator("^",   &[a, b]).unchecked_into()}
     /// The JavaScript `<<` operator
-    pub fn op_lshift2       (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsNumber   {self.value_from_raw_code(format_args!("(({}) << ({}))",    UseInJsCodeWriter(a), UseInJsCodeWriter(b))).unchecked_into()}
+    #[track_caller]
+    pub fn op_lshift2       (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsNumber   {self.operator("<<",  &[a, b]).unchecked_into()}
     /// The JavaScript `>>` operator
-    pub fn op_rshift2       (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsNumber   {self.value_from_raw_code(format_args!("(({}) >> ({}))",    UseInJsCodeWriter(a), UseInJsCodeWriter(b))).unchecked_into()}
+    #[track_caller]
+    pub fn op_rshift2       (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsNumber   {self.operator(">>",  &[a, b]).unchecked_into()}
     /// The JavaScript `>>>` operator
-    pub fn op_rshift3       (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsNumber   {self.value_from_raw_code(format_args!("(({}) >>> ({}))",   UseInJsCodeWriter(a), UseInJsCodeWriter(b))).unchecked_into()}
+    #[track_caller]
+    pub fn op_rshift3       (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsNumber   {self.operator(">>>", &[a, b]).unchecked_into()}
     /// The JavaScript `&&` operator
-    pub fn op_logical_and   (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsValue    {self.value_from_raw_code(format_args!("(({}) && ({}))",    UseInJsCodeWriter(a), UseInJsCodeWriter(b))).unchecked_into()}
+    #[track_caller]
+    pub fn op_logical_and   (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsValue    {self.operator("&&",  &[a, b]).unchecked_into()}
     /// The JavaScript `||` operator
-    pub fn op_logical_or    (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsValue    {self.value_from_raw_code(format_args!("(({}) || ({}))",    UseInJsCodeWriter(a), UseInJsCodeWriter(b))).unchecked_into()}
+    #[track_caller]
+    pub fn op_logical_or    (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsValue    {self.operator("||",  &[a, b]).unchecked_into()}
     /// The JavaScript `==` operator
-    pub fn op_eq2           (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsBoolean  {self.value_from_raw_code(format_args!("(({}) == ({}))",    UseInJsCodeWriter(a), UseInJsCodeWriter(b))).unchecked_into()}
+    #[track_caller]
+    pub fn op_eq2           (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsBoolean  {self.operator("==",  &[a, b]).unchecked_into()}
     /// The JavaScript `!=` operator
-    pub fn op_neq1          (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsBoolean  {self.value_from_raw_code(format_args!("(({}) != ({}))",    UseInJsCodeWriter(a), UseInJsCodeWriter(b))).unchecked_into()}
+    #[track_caller]
+    pub fn op_neq1          (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsBoolean  {self.operator("!=",  &[a, b]).unchecked_into()}
     /// The JavaScript `===` operator
-    pub fn op_eq3           (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsBoolean  {self.value_from_raw_code(format_args!("(({}) === ({}))",   UseInJsCodeWriter(a), UseInJsCodeWriter(b))).unchecked_into()}
+    #[track_caller]
+    pub fn op_eq3           (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsBoolean  {self.operator("===", &[a, b]).unchecked_into()}
     /// The JavaScript `!==` operator
-    pub fn op_neq2          (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsBoolean  {self.value_from_raw_code(format_args!("(({}) !== ({}))",   UseInJsCodeWriter(a), UseInJsCodeWriter(b))).unchecked_into()}
+    #[track_caller]
+    pub fn op_neq2          (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsBoolean  {self.operator("!==", &[a, b]).unchecked_into()}
     /// The JavaScript `>` operator
-    pub fn op_gt            (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsBoolean  {self.value_from_raw_code(format_args!("(({}) > ({}))",     UseInJsCodeWriter(a), UseInJsCodeWriter(b))).unchecked_into()}
+    #[track_caller]
+    pub fn op_gt            (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsBoolean  {self.operator(">",   &[a, b]).unchecked_into()}
     /// The JavaScript `>=` operator
-    pub fn op_gte           (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsBoolean  {self.value_from_raw_code(format_args!("(({}) >= ({}))",    UseInJsCodeWriter(a), UseInJsCodeWriter(b))).unchecked_into()}
+    #[track_caller]
+    pub fn op_gte           (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsBoolean  {self.operator(">=",  &[a, b]).unchecked_into()}
     /// The JavaScript `<` operator
-    pub fn op_lt            (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsBoolean  {self.value_from_raw_code(format_args!("(({}) < ({}))",     UseInJsCodeWriter(a), UseInJsCodeWriter(b))).unchecked_into()}
+    #[track_caller]
+    pub fn op_lt            (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsBoolean  {self.operator("<",   &[a, b]).unchecked_into()}
     /// The JavaScript `<=` operator
-    pub fn op_lte           (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsBoolean  {self.value_from_raw_code(format_args!("(({}) <= ({}))",    UseInJsCodeWriter(a), UseInJsCodeWriter(b))).unchecked_into()}
+    #[track_caller]
+    pub fn op_lte           (&self, a: &dyn UseInJsCode, b: &dyn UseInJsCode) -> JsBoolean  {self.operator("<=",  &[a, b]).unchecked_into()}
 
     /// The JavaScript `-` operator
-    pub fn op_neg           (&self, a: &dyn UseInJsCode) -> JsNumber    {self.value_from_raw_code(format_args!("(-({}))", UseInJsCodeWriter(a))).unchecked_into()}
+    #[track_caller]
+    pub fn op_neg           (&self, a: &dyn UseInJsCode) -> JsNumber    {self.operator("-", &[a]).unchecked_into()}
     /// The JavaScript `~` operator
-    pub fn op_bitwise_not   (&self, a: &dyn UseInJsCode) -> JsNumber    {self.value_from_raw_code(format_args!("(~({}))", UseInJsCodeWriter(a))).unchecked_into()}
+    #[track_caller]
+    pub fn op_bitwise_not   (&self, a: &dyn UseInJsCode) -> JsNumber    {self.operator("~", &[a]).unchecked_into()}
     /// The JavaScript `!` operator
-    pub fn op_logical_not   (&self, a: &dyn UseInJsCode) -> JsBoolean   {self.value_from_raw_code(format_args!("(!({}))", UseInJsCodeWriter(a))).unchecked_into()}
+    #[track_caller]
+    pub fn op_logical_not   (&self, a: &dyn UseInJsCode) -> JsBoolean   {self.operator("!", &[a]).unchecked_into()}
 }
 
 macro_rules! impl_number_op {
@@ -64,6 +103,7 @@ macro_rules! impl_number_op {
         impl<'a, T: ToJs<JsNumber>> $trait_name<&'a T> for &'a JsNumber {
             type Output = JsNumber;
 
+            #[track_caller]
             fn $method_name(self, rhs: &'a T) -> Self::Output {
                 self.browser.$wsdom_name(self, rhs).unchecked_into()
             }
@@ -86,6 +126,7 @@ impl_number_op!(BitXor, bitxor, op_bitwise_xor);
 impl<'a, T: ToJs<JsString>> Add<&'a T> for &'a JsString {
     type Output = JsString;
 
+    #[track_caller]
     fn add(self, rhs: &'a T) -> Self::Output {
         self.browser.op_add(self, rhs).unchecked_into()
     }
@@ -93,6 +134,7 @@ impl<'a, T: ToJs<JsString>> Add<&'a T> for &'a JsString {
 impl<'a> Neg for &'a JsNumber {
     type Output = JsNumber;
 
+    #[track_caller]
     fn neg(self) -> Self::Output {
         self.browser.op_neg(self)
     }
@@ -1,10 +1,15 @@
+use core::panic::Location;
+
+use crate::command::ValueRef;
 use crate::js_cast::JsCast;
 use crate::link::Browser;
-use crate::protocol::{DEL, GET, SET};
-use core::fmt::Write;
 
 /// Represents a value that exists on the JavaScript side.
 /// Value can be anything - number, string, object, undefined, null, ...
+///
+/// Cloning a JsValue is cheap: clones share the same slot on the JS client,
+/// which is freed when the last of them is dropped.
+/// Use [duplicate][Self::duplicate] for a distinct slot.
 #[derive(Debug)]
 pub struct JsValue {
     pub(crate) id: u64,
@@ -15,18 +20,44 @@ impl Drop for JsValue {
     fn drop(&mut self) {
         let self_id = self.id;
         let mut link = self.browser.0.lock();
-        writeln!(link.raw_commands_buf(), "{DEL}({self_id});",).unwrap();
+        if let Some(clones) = link.clones.get_mut(&self_id) {
+            // Other handles still use the slot.
+            *clones -= 1;
+            if *clones == 0 {
+                link.clones.remove(&self_id);
+            }
+            return;
+        }
+        link.uncount_value(self_id);
+        link.emit_delete(self_id);
         link.wake_outgoing_lazy();
     }
 }
 
 impl Clone for JsValue {
     fn clone(&self) -> Self {
+        *self.browser.0.lock().clones.entry(self.id).or_default() += 1;
+        Self {
+            id: self.id,
+            browser: self.browser.clone(),
+        }
+    }
+}
+
+impl JsValue {
+    /// Copy this value into a new slot on the JS client.
+    ///
+    /// Unlike [Clone], which shares the slot, this sends a command and gives the copy its own slot.
+    #[track_caller]
+    pub fn duplicate(&self) -> Self {
         let self_id = self.id;
         let out_id = {
             let mut link = self.browser.0.lock();
             let out_id = link.get_new_id();
-            writeln!(link.raw_commands_buf(), "{SET}({out_id},{GET}({self_id}));").unwrap();
+            let location = link.location(self_id);
+            link.set_location(out_id, location);
+            link.count_value(out_id, Location::caller());
+            link.emit_value(out_id, &ValueRef(self_id));
             link.wake_outgoing_lazy();
             out_id
         };
@@ -35,13 +66,16 @@ impl Clone for JsValue {
             browser: self.browser.clone(),
         }
     }
-}
-
-impl JsValue {
     // const MAX_ID: u64 = (1 << 53) - 1;
     pub fn browser(&self) -> &Browser {
         &self.browser
     }
+    /// Where in Rust this value was created.
+    ///
+    /// Returns `None` unless the Browser was created [with_locations][Browser::with_locations].
+    pub fn location(&self) -> Option<&'static Location<'static>> {
+        self.browser.0.lock().location(self.id)
+    }
 }
 
 impl AsRef<JsValue> for JsValue {
@@ -59,3 +93,28 @@ impl JsCast for JsValue {
         val
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{Browser, test_support::sent};
+
+    #[test]
+    fn clones_share_a_slot() {
+        let mut browser = Browser::new();
+        let value = browser.call_function("f", [], false);
+        sent(&mut browser);
+        let clone = value.clone();
+        assert_eq!(clone.id, value.id);
+        drop(value);
+        assert_eq!(sent(&mut browser), "");
+        let copy = clone.duplicate();
+        drop(clone);
+        assert_eq!(
+            sent(&mut browser),
+            "try{_w.s(3,_w.g(2))}catch($){_w.e(3,$)};\n_w.dm([2]);\n"
+        );
+        drop(copy);
+        assert_eq!(sent(&mut browser), "_w.dm([3]);\n");
+        assert!(browser.0.lock().clones.is_empty());
+    }
+}
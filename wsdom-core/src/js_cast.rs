@@ -43,13 +43,16 @@ pin_project_lite::pin_project! {
         pub phantom: PhantomData<U>
     }
 }
-impl<T: Future<Output: JsCast>, U: JsCast> Future for Cast<T, U> {
-    type Output = U;
+impl<T: Future<Output = Result<V, E>>, V: JsCast, E, U: JsCast> Future for Cast<T, U> {
+    type Output = Result<U, E>;
 
     fn poll(
         self: core::pin::Pin<&mut Self>,
         cx: &mut core::task::Context<'_>,
     ) -> core::task::Poll<Self::Output> {
-        self.project().value.poll(cx).map(|a| a.unchecked_into())
+        self.project()
+            .value
+            .poll(cx)
+            .map(|a| a.map(JsCast::unchecked_into))
     }
 }
@@ -0,0 +1,202 @@
+use core::{cmp::Reverse, fmt, panic::Location, time::Duration};
+
+use alloc::vec::Vec;
+use hashbrown::HashMap;
+use std::time::Instant;
+
+use crate::link::Browser;
+
+/// The kind of handle tracked by the [leak tracker][Browser::leak_report].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HandleKind {
+    /// A [JsValue][crate::js_types::JsValue] (clones count once, as they share a slot).
+    Value,
+    /// A [Callback][crate::callback::Callback] stream.
+    Callback,
+}
+
+/// Live handles created at one place in Rust.
+#[derive(Clone, Debug)]
+pub struct AllocationSite {
+    pub location: &'static Location<'static>,
+    pub kind: HandleKind,
+    /// How many handles created here are alive.
+    pub live: usize,
+    /// How long the oldest of them has been alive.
+    pub oldest: Duration,
+}
+
+/// Live handles grouped by where they were created. Returned by [Browser::leak_report].
+///
+/// Prints as a short summary of the oldest and most numerous sites.
+#[derive(Clone, Debug, Default)]
+pub struct LeakReport {
+    pub sites: Vec<AllocationSite>,
+}
+
+impl LeakReport {
+    /// Sites by the age of their oldest live handle, oldest first.
+    pub fn oldest(&self) -> Vec<&AllocationSite> {
+        let mut sites: Vec<_> = self.sites.iter().collect();
+        sites.sort_by_key(|site| Reverse(site.oldest));
+        sites
+    }
+    /// Sites by their number of live handles, most first.
+    pub fn most_numerous(&self) -> Vec<&AllocationSite> {
+        let mut sites: Vec<_> = self.sites.iter().collect();
+        sites.sort_by_key(|site| Reverse(site.live));
+        sites
+    }
+    /// The number of live handles of all sites.
+    pub fn live(&self) -> usize {
+        self.sites.iter().map(|site| site.live).sum()
+    }
+}
+
+impl fmt::Display for LeakReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const SHOWN: usize = 5;
+        writeln!(f, "{} live handles", self.live())?;
+        writeln!(f, "most numerous:")?;
+        for site in self.most_numerous().into_iter().take(SHOWN) {
+            writeln!(f, "  {} {:?} at {}", site.live, site.kind, site.location)?;
+        }
+        writeln!(f, "oldest:")?;
+        for site in self.oldest().into_iter().take(SHOWN) {
+            writeln!(
+                f,
+                "  {:?} {:?} at {}",
+                site.oldest, site.kind, site.location
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Creation site and time of every live handle, by slot or callback id.
+#[derive(Debug, Default)]
+pub(crate) struct Leaks {
+    handles: HashMap<u64, Handle>,
+    warn_at: Option<usize>,
+    warned: bool,
+}
+
+#[derive(Debug)]
+struct Handle {
+    kind: HandleKind,
+    location: &'static Location<'static>,
+    created: Instant,
+}
+
+impl Browser {
+    /// Group the live [JsValue][crate::js_types::JsValue]s and [Callback][crate::callback::Callback]s
+    /// of this Browser by where they were created.
+    ///
+    /// Needs the `leak-tracker` feature. Values are attributed to the Rust code that created them
+    /// (through `#[track_caller]`); values handed out by WSDOM itself, like callback arguments,
+    /// are attributed to the callback's creation site where known.
+    ///
+    /// ```rust
+    /// # use px_wsdom_core::Browser;
+    /// fn check(browser: &Browser) {
+    ///     let report = browser.leak_report();
+    ///     if let Some(site) = report.oldest().first() {
+    ///         println!("{} handles from {} alive for {:?}", site.live, site.location, site.oldest);
+    ///     }
+    /// }
+    /// ```
+    pub fn leak_report(&self) -> LeakReport {
+        self.0.lock().leaks.report()
+    }
+    /// Print a [leak report][Self::leak_report] to stderr when the number of live handles reaches `threshold`.
+    ///
+    /// Warns once each time the count crosses the threshold. Needs the `leak-tracker` feature.
+    pub fn with_leak_warning(self, threshold: usize) -> Self {
+        self.0.lock().leaks.warn_at = Some(threshold);
+        self
+    }
+}
+
+impl Leaks {
+    pub(crate) fn track(
+        &mut self,
+        id: u64,
+        kind: HandleKind,
+        location: &'static Location<'static>,
+    ) {
+        self.handles.insert(
+            id,
+            Handle {
+                kind,
+                location,
+                created: Instant::now(),
+            },
+        );
+        if let Some(threshold) = self.warn_at {
+            if self.handles.len() >= threshold && !self.warned {
+                self.warned = true;
+                std::eprintln!("wsdom: {threshold} live handles reached\n{}", self.report());
+            }
+        }
+    }
+    pub(crate) fn untrack(&mut self, id: u64) {
+        self.handles.remove(&id);
+        if self
+            .warn_at
+            .is_some_and(|threshold| self.handles.len() < threshold)
+        {
+            self.warned = false;
+        }
+    }
+    fn report(&self) -> LeakReport {
+        let now = Instant::now();
+        let mut sites: HashMap<(&'static Location<'static>, HandleKind), AllocationSite> =
+            HashMap::new();
+        for handle in self.handles.values() {
+            let age = now.duration_since(handle.created);
+            let site = sites
+                .entry((handle.location, handle.kind))
+                .or_insert(AllocationSite {
+                    location: handle.location,
+                    kind: handle.kind,
+                    live: 0,
+                    oldest: Duration::ZERO,
+                });
+            site.live += 1;
+            site.oldest = site.oldest.max(age);
+        }
+        LeakReport {
+            sites: sites.into_values().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Browser, callback::new_callback, js_types::JsValue};
+
+    use super::HandleKind;
+
+    #[test]
+    fn report_groups_live_handles_by_site() {
+        let browser = Browser::new();
+        let line = line!() + 1;
+        let make = || browser.call_function("f", [], false);
+        let values: alloc::vec::Vec<_> = (0..3).map(|_| make()).collect();
+        let clone = values[0].clone();
+        let (callback, func) = new_callback::<JsValue>(&browser);
+        drop(func);
+
+        let report = browser.leak_report();
+        assert_eq!(report.live(), 4);
+        let top = report.most_numerous()[0];
+        assert_eq!((top.kind, top.live), (HandleKind::Value, 3));
+        assert_eq!(top.location.line(), line);
+
+        drop((values, callback));
+        let report = browser.leak_report();
+        assert_eq!(report.live(), 1);
+        drop(clone);
+        assert_eq!(browser.leak_report().live(), 0);
+    }
+}
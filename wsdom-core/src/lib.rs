@@ -1,25 +1,57 @@
 #![no_std]
 pub extern crate alloc;
+#[cfg(feature = "leak-tracker")]
+extern crate std;
+mod backpressure;
+mod batch;
+mod binary;
+mod capabilities;
+mod checkpoint;
+mod command;
+mod deletes;
+mod flush;
+mod hello;
 mod interaction;
 mod internal;
 mod js;
 mod js_cast;
+#[cfg(feature = "leak-tracker")]
+mod leaks;
 mod link;
 mod operations;
 mod protocol;
 mod retrieve;
 mod rpc;
+mod scope;
 mod serialize;
+pub mod session;
+mod stats;
+pub mod tagged;
+#[cfg(test)]
+mod test_support;
+mod timer;
 
+pub use backpressure::{Overflow, Ready};
+pub use batch::{RetrieveFields, RetrieveMany};
+pub use binary::{ByteStream, RetrieveBytes};
+pub use capabilities::Capabilities;
+pub use checkpoint::Checkpoint;
+pub use command::Encoding;
+pub use hello::{Hello, PROTOCOL_VERSION};
 pub use link::{Browser, Error, RpcCellAM};
 /// Protocol-member name resolution for hosts that property-mangle WSDOM's
 /// private `_w` runtime object.
 pub mod protocol_names {
     pub use super::protocol::{
-        WsdomMethod, call, member, protocol_call_with_names, validate_host_method_names,
+        ALL_METHODS, WsdomMethod, call, member, protocol_call_with_names,
+        validate_host_method_names,
     };
 }
+pub use retrieve::{Retrieve, RetrieveError, RetrieveFuture};
 pub use rpc::{Endpoint, Lock, Reply, Request, RpcDeserialize, RpcHandle};
+pub use scope::Scope;
+pub use stats::{RemoteHeapSize, Stats};
+pub use timer::{Sleep, Timer};
 
 pub mod js_types {
     //! Stubs for primitive JS types including number, string, null, undefined, object.
@@ -32,8 +64,11 @@ pub mod js_types {
 }
 pub use interaction::r#await;
 pub use interaction::callback;
+pub use interaction::exception;
 pub use js_cast::{Cast, JsCast};
-pub use serialize::{ToJs, UseInJsCode};
+#[cfg(feature = "leak-tracker")]
+pub use leaks::{AllocationSite, HandleKind, LeakReport};
+pub use serialize::{SerdeToJs, ToJs, UseInJsCode};
 pub mod immediates {
     pub use super::js::immediates::{null, undefined};
 }
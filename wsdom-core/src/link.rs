@@ -3,14 +3,32 @@ use alloc::{
     collections::{BTreeMap, VecDeque},
     string::String,
     sync::Arc,
+    vec::Vec,
 };
+use core::panic::Location;
 use core::task::{Poll, Waker};
+use core::time::Duration;
 use hashbrown::HashMap;
 use spin::Mutex;
 
 use futures_core::Stream;
 
+use crate::backpressure::BufferLimit;
+use crate::binary::ByteStreamState;
+use crate::capabilities::Capabilities;
+use crate::command::Encoding;
+use crate::deletes::Deletes;
+use crate::flush::Batching;
+use crate::hello::Handshake;
+use crate::interaction::exception::ExceptionListener;
 use crate::js_types::JsValue;
+#[cfg(feature = "leak-tracker")]
+use crate::leaks::Leaks;
+use crate::protocol::HostNames;
+use crate::scope::Scopes;
+use crate::session::Session;
+use crate::stats::Counters;
+use crate::timer::SharedTimer;
 
 /// A WSDOM client.
 ///
@@ -33,7 +51,8 @@ use crate::js_types::JsValue;
 ///     You must take items from the stream and send it to the WSDOM JS client
 ///     over WebSocket or other transport of your choice.
 /// -   Browser has a `receive_incoming_message(msg: String)` method.
-///     Everything sent by the WSDOM JS client must be fed into this method.
+///     Everything sent by the WSDOM JS client must be fed into this method,
+///     except binary frames, which go to `receive_incoming_binary`.
 ///
 /// The `counter-manual` example in our repo shows manual usage with Tokio.
 #[derive(Clone, Debug)]
@@ -46,6 +65,7 @@ impl Browser {
     pub fn new() -> Self {
         let link = BrowserInternal {
             retrievals: HashMap::new(),
+            byte_streams: HashMap::new(),
             last_id: 1,
             commands_buf: String::new(),
             outgoing_waker: None,
@@ -53,9 +73,72 @@ impl Browser {
             imports: BTreeMap::new(),
             rpc_state: BTreeMap::new(),
             pure_values: BTreeMap::new(),
+            encoding: Encoding::Source,
+            capabilities: None,
+            exception_listeners: Vec::new(),
+            locations: None,
+            timer: None,
+            default_timeout: None,
+            session: None,
+            buffer_limit: None,
+            ready_wakers: Vec::new(),
+            batching: Batching::default(),
+            deletes: Deletes::default(),
+            clones: HashMap::new(),
+            scopes: Scopes::default(),
+            counters: Counters::default(),
+            handshake: Handshake::default(),
+            names: Arc::default(),
+            #[cfg(feature = "leak-tracker")]
+            leaks: Leaks::default(),
         };
         Self(Arc::new(Mutex::new(link)))
     }
+    /// Select how commands are encoded for the JS client.
+    ///
+    /// The default is [Encoding::Source]. The JS client must be generated for the same encoding,
+    /// and the encoding should be selected before any value is created on this Browser.
+    ///
+    /// ```rust
+    /// # use px_wsdom_core::{Browser, Encoding};
+    /// let browser = Browser::new().with_encoding(Encoding::Opcode);
+    /// ```
+    pub fn with_encoding(self, encoding: Encoding) -> Self {
+        self.0.lock().encoding = encoding;
+        self
+    }
+    /// The encoding selected with [with_encoding][Self::with_encoding].
+    pub fn encoding(&self) -> Encoding {
+        self.0.lock().encoding
+    }
+    /// Restrict what this Browser may reach on the JS client.
+    ///
+    /// Every command is checked against the [Capabilities] before it is sent.
+    /// A command that reaches something not allowed kills the Browser with [Error::CapabilityDenied].
+    /// Raw code ([run_raw_code][Self::run_raw_code]) cannot be checked and is always denied.
+    ///
+    /// ```rust
+    /// # use px_wsdom_core::{Browser, Capabilities, Encoding};
+    /// let browser = Browser::new()
+    ///     .with_encoding(Encoding::Opcode)
+    ///     .with_capabilities(Capabilities::new().allow_global("document"));
+    /// ```
+    pub fn with_capabilities(self, capabilities: Capabilities) -> Self {
+        self.0.lock().capabilities = Some(capabilities);
+        self
+    }
+    /// Record where in Rust each JS value was created, for debugging.
+    ///
+    /// When enabled, every command that creates a value remembers the [Location] of the Rust code that called it
+    /// (through `#[track_caller]`, so calls made through generated stubs point at your code).
+    /// The location is then available from [JsValue::location],
+    /// from the [RetrieveError::Thrown][crate::RetrieveError::Thrown] of a failed retrieval, and from reported [JsException][crate::exception::JsException]s.
+    ///
+    /// Setters and [run_raw_code][Self::run_raw_code] do not create values, so their failures cannot be attributed.
+    pub fn with_locations(self, enabled: bool) -> Self {
+        self.0.lock().locations = enabled.then(HashMap::new);
+        self
+    }
     /// Returns whether two browser handles represent the same WSDOM connection.
     ///
     /// Remote value IDs are meaningful only within this connection; hosts using
@@ -70,6 +153,21 @@ impl Browser {
     pub fn receive_incoming_message(&self, message: String) {
         self.0.lock().receive(message);
     }
+    /// Close the connection, for example because the underlying transport is gone.
+    ///
+    /// The Browser dies with [Error::Disconnected], and every pending retrieval, await, callback stream,
+    /// and RPC endpoint is woken: retrievals and awaits fail with [RetrieveError::Disconnected][crate::RetrieveError::Disconnected]
+    /// and streams end, so tasks waiting on the client can finish.
+    ///
+    /// Integration libraries (like `wsdom-axum`) call this when the socket closes.
+    /// Does nothing if the Browser is already dead.
+    pub fn close(&self, reason: impl Into<String>) {
+        self.0.lock().kill(Error::Disconnected(reason.into()));
+    }
+    /// Whether the Browser has been [closed][Self::close] or has died from an error.
+    pub fn is_closed(&self) -> bool {
+        self.0.lock().is_dead()
+    }
     /// If the Browser has errored, this will return the error.
     ///
     /// The [Error] type is not [Clone], so after the first call returning `Some(_)`,
@@ -110,10 +208,18 @@ impl futures_core::Stream for Browser {
         {
             link.outgoing_waker = Some(new_waker.to_owned());
         }
-        if !link.commands_buf.is_empty() {
-            Poll::Ready(Some(core::mem::take(&mut link.commands_buf)))
+        let frame = if link.session.is_some() {
+            link.next_session_frame(cx)
         } else {
-            Poll::Pending
+            link.take_frame(cx)
+        };
+        link.wake_ready();
+        match frame {
+            Some(frame) => {
+                link.count_sent(&frame);
+                Poll::Ready(Some(frame))
+            }
+            None => Poll::Pending,
         }
     }
 }
@@ -121,6 +227,8 @@ impl futures_core::Stream for Browser {
 pub struct RpcCell {
     pub waker: Waker,
     pub queue: VecDeque<String>,
+    /// Set when the Browser dies; the stream ends once the queue is drained.
+    pub closed: bool,
 }
 #[derive(Clone, Debug)]
 pub struct RpcCellAM(pub Arc<Mutex<RpcCell>>);
@@ -136,6 +244,7 @@ impl Stream for RpcCellAM {
         let mut lock = this.0.lock();
         match lock.queue.pop_front() {
             Some(v) => return Poll::Ready(Some(v)),
+            None if lock.closed => return Poll::Ready(None),
             None => {}
         };
 
@@ -151,22 +260,55 @@ impl Stream for RpcCellAM {
 #[derive(Debug)]
 pub struct BrowserInternal {
     pub(crate) retrievals: HashMap<u64, RetrievalState>,
+    pub(crate) byte_streams: HashMap<u64, ByteStreamState>,
     last_id: u64,
-    commands_buf: String,
+    pub(crate) commands_buf: String,
     outgoing_waker: Option<Waker>,
     dead: ErrorState,
     pub(crate) imports: BTreeMap<String, u64>,
     pub(crate) rpc_state: BTreeMap<String, RpcCellAM>,
     pub(crate) pure_values: BTreeMap<String, JsValue>,
+    pub(crate) encoding: Encoding,
+    pub(crate) capabilities: Option<Capabilities>,
+    pub(crate) exception_listeners: Vec<ExceptionListener>,
+    pub(crate) locations: Option<HashMap<u64, &'static Location<'static>>>,
+    pub(crate) timer: Option<SharedTimer>,
+    pub(crate) default_timeout: Option<Duration>,
+    pub(crate) session: Option<Session>,
+    pub(crate) buffer_limit: Option<BufferLimit>,
+    pub(crate) ready_wakers: Vec<Waker>,
+    pub(crate) batching: Batching,
+    pub(crate) deletes: Deletes,
+    /// How many more handles share a slot, for slots with more than one.
+    pub(crate) clones: HashMap<u64, usize>,
+    pub(crate) scopes: Scopes,
+    pub(crate) counters: Counters,
+    pub(crate) handshake: Handshake,
+    pub(crate) names: Arc<HostNames>,
+    #[cfg(feature = "leak-tracker")]
+    pub(crate) leaks: Leaks,
 }
 
 /// Error that could happen in WSDOM.
 ///
-/// Currently, the only errors that could happen are from [serde] serialization and deserialization.
+/// Most errors come from [serde] serialization and deserialization.
 #[derive(Debug)]
 pub enum Error {
     CommandSerialize(core::fmt::Error),
     DataDeserialize(serde_json::Error),
+    /// Arbitrary JavaScript code was sent to a Browser using [Encoding::Opcode].
+    RawCodeInOpcodeMode,
+    /// The Browser was [closed][Browser::close], with this reason.
+    Disconnected(String),
+    /// A command reached a global, constructor, method, or property not allowed by the
+    /// Browser's [Capabilities]. Holds the denied name.
+    CapabilityDenied(String),
+    /// The outgoing buffer reached the limit set with [Browser::with_buffer_limit], which this holds,
+    /// under [Overflow::Error][crate::Overflow::Error] or [Overflow::Kill][crate::Overflow::Kill].
+    BufferFull(usize),
+    /// The JS client's [hello][crate::Hello] shows it does not speak this Browser's protocol,
+    /// for example because it was generated by another version of `wsdom-gen`. Holds what is wrong.
+    ProtocolMismatch(String),
 }
 #[derive(Debug)]
 enum ErrorState {
@@ -180,10 +322,22 @@ pub(crate) struct RetrievalState {
     pub(crate) waker: Waker,
     pub(crate) last_value: String,
     pub(crate) times: usize,
+    /// Every reply not yet taken, oldest first, for callbacks that ship a payload with each call.
+    pub(crate) payloads: Option<VecDeque<String>>,
 }
 
 impl BrowserInternal {
     pub fn receive(&mut self, message: String) {
+        self.count_received(message.len());
+        if self.session.is_none() {
+            self.receive_message(&message);
+        } else if let Some(message) = self.receive_session_frame(&message) {
+            self.receive_message(message);
+        }
+        // Acknowledgements free buffer space.
+        self.wake_ready();
+    }
+    fn receive_message(&mut self, message: &str) {
         if let Some(message) = message.strip_prefix("p") {
             match message
                 .split_once(':')
@@ -193,13 +347,29 @@ impl BrowserInternal {
                     Some(s) => {
                         s.times += 1;
                         s.last_value = message.to_owned();
+                        if let Some(payloads) = &mut s.payloads {
+                            payloads.push_back(message.to_owned());
+                        }
                         s.waker.wake_by_ref();
                     }
-                    _ => {}
+                    None if self.byte_streams.contains_key(&id) => {
+                        self.end_byte_stream(id, message)
+                    }
+                    // Nobody is waiting anymore, for example because the retrieval timed out.
+                    _ => self.discard_reply(message),
                 },
                 None => {}
             }
         }
+        if let Some(base64) = message.strip_prefix("B") {
+            self.receive_binary_base64(base64);
+        }
+        if let Some(json) = message.strip_prefix("h") {
+            self.receive_hello(json);
+        }
+        if let Some(message) = message.strip_prefix("e") {
+            self.receive_exception(message);
+        }
         if let Some(message) = message.strip_prefix("r") {
             match message.split_once(':') {
                 Some((id, v)) => match self.rpc_state.get(id) {
@@ -221,19 +391,78 @@ impl BrowserInternal {
         self.last_id += 1;
         self.last_id
     }
+    pub(crate) fn ids_allocated(&self) -> u64 {
+        // Ids start at 2.
+        self.last_id - 1
+    }
+    /// Get a new id for a slot that will hold a value created by the caller,
+    /// recording the caller's location if [with_locations][Browser::with_locations] is enabled.
+    #[track_caller]
+    pub(crate) fn get_new_value_id(&mut self) -> u64 {
+        let id = self.get_new_id();
+        self.set_location(id, Some(Location::caller()));
+        self.count_value(id, Location::caller());
+        id
+    }
+    pub(crate) fn location(&self, id: u64) -> Option<&'static Location<'static>> {
+        self.locations.as_ref()?.get(&id).copied()
+    }
+    pub(crate) fn set_location(&mut self, id: u64, location: Option<&'static Location<'static>>) {
+        if let (Some(locations), Some(location)) = (self.locations.as_mut(), location) {
+            locations.insert(id, location);
+        }
+    }
     pub(crate) fn kill(&mut self, err: Error) {
         if matches!(self.dead, ErrorState::NoError) {
             self.dead = ErrorState::Error(err);
+            // Nothing more will be sent.
+            self.commands_buf = String::new();
+            self.clear_pending_deletes();
+            self.wake_all();
+        }
+    }
+    pub(crate) fn is_dead(&self) -> bool {
+        !matches!(self.dead, ErrorState::NoError)
+    }
+    /// Wake everything waiting on the JS client, so it can notice the Browser is dead.
+    fn wake_all(&mut self) {
+        for state in self.retrievals.values() {
+            state.waker.wake_by_ref();
         }
+        for state in self.byte_streams.values() {
+            state.wake();
+        }
+        for cell in self.rpc_state.values() {
+            let mut cell = cell.0.lock();
+            cell.closed = true;
+            cell.waker.wake_by_ref();
+        }
+        for listener in self.exception_listeners.drain(..) {
+            if let Some(state) = listener.upgrade() {
+                state.lock().close();
+            }
+        }
+        self.wake_ready();
+        self.wake_outgoing();
     }
+    /// Wake the writer to send the buffered commands now, because something waits for the JS client.
     pub(crate) fn wake_outgoing(&mut self) {
+        self.mark_command_boundary();
+        self.request_flush();
+        self.wake_writer();
+    }
+    /// Wake the writer for fire-and-forget commands, which may wait to be sent in a batch.
+    pub(crate) fn wake_outgoing_lazy(&mut self) {
+        self.mark_command_boundary();
+        if self.should_wake_lazily() {
+            self.wake_writer();
+        }
+    }
+    fn wake_writer(&mut self) {
         if let Some(waker) = self.outgoing_waker.as_ref() {
             waker.wake_by_ref();
         }
     }
-    pub(crate) fn wake_outgoing_lazy(&mut self) {
-        self.wake_outgoing();
-    }
 }
 
 struct InvalidReturn;
@@ -248,3 +477,55 @@ impl core::fmt::Display for InvalidReturn {
     }
 }
 impl core::error::Error for InvalidReturn {}
+
+#[cfg(test)]
+mod tests {
+    use core::{
+        future::IntoFuture,
+        task::{Context, Poll},
+    };
+    use futures_util::{FutureExt, StreamExt, task::noop_waker_ref};
+
+    use crate::{Browser, Error, RetrieveError, callback::new_callback, js_types::JsValue};
+
+    #[test]
+    fn close_ends_everything_pending() {
+        let browser = Browser::new();
+        let value = browser.call_function("f", [], false);
+        let mut retrieval = value.retrieve_json();
+        let mut awaiting = browser.call_function("g", [], false).into_future();
+        let (mut callback, _func) = new_callback::<JsValue>(&browser);
+        let mut rpc = browser.new_rpc::<u64, u64>("f");
+        let mut exceptions = browser.exceptions();
+        let mut cx = Context::from_waker(noop_waker_ref());
+        assert!(retrieval.poll_unpin(&mut cx).is_pending());
+        assert!(awaiting.poll_unpin(&mut cx).is_pending());
+        assert!(callback.poll_next_unpin(&mut cx).is_pending());
+        assert!(rpc.poll_next_unpin(&mut cx).is_pending());
+
+        browser.close("tab closed");
+        assert!(browser.is_closed());
+        assert!(matches!(
+            retrieval.poll_unpin(&mut cx),
+            Poll::Ready(Err(RetrieveError::Disconnected))
+        ));
+        assert!(matches!(
+            awaiting.poll_unpin(&mut cx),
+            Poll::Ready(Err(RetrieveError::Disconnected))
+        ));
+        assert!(matches!(
+            browser.sync().poll_unpin(&mut cx),
+            Poll::Ready(Err(RetrieveError::Disconnected))
+        ));
+        assert!(matches!(
+            callback.poll_next_unpin(&mut cx),
+            Poll::Ready(None)
+        ));
+        assert!(matches!(rpc.poll_next_unpin(&mut cx), Poll::Ready(None)));
+        assert_eq!(exceptions.poll_next_unpin(&mut cx), Poll::Ready(None));
+        assert!(matches!(
+            browser.take_error(),
+            Some(Error::Disconnected(reason)) if reason == "tab closed"
+        ));
+    }
+}
@@ -1,27 +1,27 @@
 use crate::{
     Endpoint, RpcHandle,
+    command::Callee,
+    hello::import_hash,
     js::{object::JsObject, value::JsValue},
     js_cast::JsCast,
-    link::{Browser, Error, RpcCell},
-    protocol::{ERR, GET, IMPORT, SET},
+    link::{Browser, RpcCell},
     retrieve::RetrieveFuture,
-    serialize::{ToJs, UseInJsCode, UseInJsCodeWriter},
+    serialize::{ToJs, UseInJsCode},
 };
 use alloc::{borrow::ToOwned, sync::Arc};
 use core::{
-    fmt::Write,
     marker::PhantomData,
     // sync::{Arc, Mutex},
     task::Waker,
 };
 use futures_util::task::noop_waker_ref;
-use sha3::Digest;
 use spin::Mutex;
 
 impl Browser {
     /// Creates a new RPC endpoint
     pub fn new_rpc<T, C>(&self, a: &str) -> crate::RpcHandle<Endpoint<T, C>> {
         let mut lock = self.0.lock();
+        let closed = lock.is_dead();
         let a = lock
             .rpc_state
             .entry(a.to_owned())
@@ -29,6 +29,7 @@ impl Browser {
                 crate::RpcCellAM(Arc::new(Mutex::new(RpcCell {
                     waker: noop_waker_ref().clone(),
                     queue: Default::default(),
+                    closed,
                 })))
             })
             .clone();
@@ -43,7 +44,7 @@ impl Browser {
     /// Call a standalone JavaScript function.
     ///
     /// ```rust
-    /// # use wsdom_core::Browser;
+    /// # use px_wsdom_core::Browser;
     /// fn example(browser: Browser) {
     ///     let _return_value = browser.call_function(
     ///         "alert",
@@ -59,19 +60,20 @@ impl Browser {
     /// If you still want to use `call_function`,
     /// be aware that the first argument (`function_name`) is NOT escaped.
     /// Do NOT allow user-supplied function name.
+    #[track_caller]
     pub fn call_function<'a>(
         &'a self,
         function_name: &'a str,
         args: impl IntoIterator<Item = &'a dyn UseInJsCode>,
         last_arg_variadic: bool,
     ) -> JsValue {
-        self.call_function_inner(&format_args!("{}", function_name), args, last_arg_variadic)
+        self.call_function_inner(Callee::Function(function_name), args, last_arg_variadic)
     }
 
     /// Call constructor for a class.
     ///
     /// ```rust
-    /// # use wsdom_core::Browser;
+    /// # use px_wsdom_core::Browser;
     /// fn example(browser: Browser) {
     ///     let _regexp_object = browser.call_constructor(
     ///         "RegExp",
@@ -87,43 +89,28 @@ impl Browser {
     /// If you still want to use `call_constructor`,
     /// be aware that the first argument (`class_name`) is NOT escaped.
     /// Do NOT allow user-supplied class name.
+    #[track_caller]
     pub fn call_constructor<'a>(
         &'a self,
         class_name: &'a str,
         args: impl IntoIterator<Item = &'a dyn UseInJsCode>,
         last_arg_variadic: bool,
     ) -> JsValue {
-        self.call_function_inner(&format_args!("new {}", class_name), args, last_arg_variadic)
+        self.call_function_inner(Callee::Constructor(class_name), args, last_arg_variadic)
     }
 
+    #[track_caller]
     fn call_function_inner<'a>(
         &'a self,
-        function: &core::fmt::Arguments<'_>,
+        callee: Callee<'_>,
         args: impl IntoIterator<Item = &'a dyn UseInJsCode>,
         last_arg_variadic: bool,
     ) -> JsValue {
         let id = {
             let mut link = self.0.lock();
-            let out_id = link.get_new_id();
-            write!(link.raw_commands_buf(), "try{{{SET}({out_id},{function}(").unwrap();
-            let mut iter = args.into_iter().peekable();
-            while let Some(arg) = iter.next() {
-                let arg = UseInJsCodeWriter(arg);
-                let res = if last_arg_variadic && iter.peek().is_none() {
-                    write!(link.raw_commands_buf(), "...{arg},")
-                } else {
-                    write!(link.raw_commands_buf(), "{arg},")
-                };
-                if let Err(e) = res {
-                    link.kill(Error::CommandSerialize(e));
-                }
-            }
-            write!(
-                link.raw_commands_buf(),
-                "))}}catch($){{{ERR}({out_id},$)}};\n"
-            )
-            .unwrap();
-            link.wake_outgoing();
+            let out_id = link.get_new_value_id();
+            link.emit_call(out_id, callee, args, last_arg_variadic);
+            link.wake_outgoing_lazy();
             out_id
         };
         JsValue {
@@ -135,19 +122,13 @@ impl Browser {
     /// Get a field in an object.
     ///
     /// This returns the value of `base_obj[property]`.
+    #[track_caller]
     pub fn get_field(&self, base_obj: &dyn UseInJsCode, property: &dyn UseInJsCode) -> JsValue {
         let browser = self.clone();
         let id = {
             let mut link = browser.0.lock();
-            let out_id = link.get_new_id();
-            let base_obj = UseInJsCodeWriter(base_obj);
-            let property = UseInJsCodeWriter(property);
-            if let Err(e) = writeln!(
-                link.raw_commands_buf(),
-                "try{{{SET}({out_id},({base_obj})[{property}])}}catch($){{{ERR}({out_id},$)}};"
-            ) {
-                link.kill(Error::CommandSerialize(e));
-            }
+            let out_id = link.get_new_value_id();
+            link.emit_get(out_id, base_obj, property);
             link.wake_outgoing_lazy();
             out_id
         };
@@ -164,46 +145,47 @@ impl Browser {
         value: &dyn UseInJsCode,
     ) {
         let mut link = self.0.lock();
-        let (base_obj, property, value) = (
-            UseInJsCodeWriter(base_obj),
-            UseInJsCodeWriter(property),
-            UseInJsCodeWriter(value),
-        );
-        if let Err(e) = writeln!(link.raw_commands_buf(), "({base_obj})[{property}]={value};") {
-            link.kill(Error::CommandSerialize(e));
-        }
-        link.wake_outgoing();
+        link.emit_set_field(base_obj, property, value);
+        link.wake_outgoing_lazy();
     }
 
     /// Create a new value on the JavaScript side from a [ToJs] type.
+    #[track_caller]
     pub fn new_value<'a, T: JsCast>(&'a self, value: &'a dyn ToJs<T>) -> T {
-        let val = self.value_from_raw_code(format_args!("{}", UseInJsCodeWriter(value)));
-        JsCast::unchecked_from_js(val)
+        let mut link = self.0.lock();
+        let out_id = link.get_new_value_id();
+        link.emit_value(out_id, value);
+        link.wake_outgoing_lazy();
+        JsCast::unchecked_from_js(JsValue {
+            id: out_id,
+            browser: self.to_owned(),
+        })
     }
 
     /// Executes arbitrary JavaScript code.
     ///
     /// Don't use this unless you really have to.
+    ///
+    /// Arbitrary code cannot be sent in [Encoding::Opcode][crate::Encoding::Opcode] mode;
+    /// calling this kills the Browser with [Error::RawCodeInOpcodeMode][crate::Error::RawCodeInOpcodeMode].
     pub fn run_raw_code<'a>(&'a self, code: core::fmt::Arguments<'a>) {
         let mut link = self.0.lock();
-        if let Err(e) = writeln!(link.raw_commands_buf(), "{{ {code} }}") {
-            link.kill(Error::CommandSerialize(e));
-        }
-        link.wake_outgoing();
+        link.emit_raw(code);
+        link.wake_outgoing_lazy();
     }
 
     /// Executes arbitrary JavaScript expression and return the result.
     ///
     /// Don't use this unless you really have to.
+    ///
+    /// In [Encoding::Opcode][crate::Encoding::Opcode] mode, only global names such as `document` or `Math.PI` are accepted;
+    /// any other code kills the Browser with [Error::RawCodeInOpcodeMode][crate::Error::RawCodeInOpcodeMode].
+    #[track_caller]
     pub fn value_from_raw_code<'a>(&'a self, code: core::fmt::Arguments<'a>) -> JsValue {
         let mut link = self.0.lock();
-        let out_id = link.get_new_id();
-        writeln!(
-            link.raw_commands_buf(),
-            "try{{{SET}({out_id},{code})}}catch($){{{ERR}({out_id},$)}};"
-        )
-        .unwrap();
-        link.wake_outgoing();
+        let out_id = link.get_new_value_id();
+        link.emit_raw_value(out_id, code);
+        link.wake_outgoing_lazy();
         JsValue {
             id: out_id,
             browser: self.to_owned(),
@@ -213,6 +195,7 @@ impl Browser {
     /// Executesand caches  arbitrary JavaScript expression and return the result.
     ///
     /// Don't use this unless you really have to.
+    #[track_caller]
     pub fn value_from_pure_raw_code(&self, x: &str) -> JsValue {
         let mut link = self.0.lock();
         let a = match link.pure_values.get(x).cloned() {
@@ -227,24 +210,23 @@ impl Browser {
     }
 
     /// Gets an import from the available ones
+    #[track_caller]
     pub fn import(&self, name: &str) -> JsValue {
         let browser = self.clone();
         let mut link = self.0.lock();
         let a = match link.imports.get(name).cloned() {
             None => {
-                let out_id = link.get_new_id();
-                writeln!(
-                    link.raw_commands_buf(),
-                    "try{{{SET}({out_id},{IMPORT}._{})}}catch($){{{ERR}({out_id},$)}};",
-                    hex::encode(&sha3::Sha3_256::digest(name.as_bytes()))
-                )
-                .unwrap();
-                link.wake_outgoing();
+                let out_id = link.get_new_value_id();
+                link.check_import(name);
+                link.emit_import(out_id, &import_hash(name));
+                link.wake_outgoing_lazy();
                 link.imports.insert(name.to_owned(), out_id.clone());
                 out_id
             }
             Some(a) => a,
         };
+        // The cache keeps its own share of the slot, so dropping the import does not free it.
+        *link.clones.entry(a).or_default() += 1;
         return JsValue { id: a, browser };
     }
 }
@@ -255,14 +237,31 @@ impl JsValue {
     ) -> RetrieveFuture<'_, U> {
         RetrieveFuture::new(self.id, &self.browser)
     }
+    /// Retrieve this value from the JS side and deserialize it straight into `T`.
+    ///
+    /// The value is sent as JSON, so `T` can be any type that deserializes from what `JSON.stringify` gives.
+    ///
+    /// ```rust
+    /// # use px_wsdom_core::js_types::JsValue;
+    /// #[derive(serde::Deserialize)]
+    /// struct Point {
+    ///     x: f64,
+    ///     y: f64,
+    /// }
+    /// async fn example(point: &JsValue) {
+    ///     let Point { x, y } = point.retrieve::<Point>().await.unwrap();
+    /// }
+    /// ```
+    pub fn retrieve<T: serde::de::DeserializeOwned>(&self) -> RetrieveFuture<'_, T> {
+        self.retrieve_and_deserialize()
+    }
     /// Retrive this value from the JS side to the Rust side.
     /// Returns Future whose output is a [serde_json::Value].
     ///
     /// ```rust
-    /// # use wsdom::Browser;
-    /// # use wsdom::dom::HTMLInputElement;
-    /// async fn example(input: &HTMLInputElement) {
-    ///     let _val = input.get_value().retrieve_json().await;
+    /// # use px_wsdom_core::js_types::JsValue;
+    /// async fn example(value: &JsValue) {
+    ///     let _val = value.retrieve_json().await;
     /// }
     /// ```
     pub fn retrieve_json(&self) -> RetrieveFuture<'_, serde_json::Value> {
@@ -277,29 +276,22 @@ impl JsObject {
     /// Use `js_get_field` only when needed
     ///
     /// ```rust
-    /// # use wsdom_core::Browser;
-    /// # use wsdom_core::js_types::*;
-    /// fn example(browser: Browser) {
-    ///     // you can get `window["location"]["href"]` like this
-    ///     let href: JsValue = wsdom::dom::location(&browser).js_get_field(&"href");
+    /// # use px_wsdom_core::js_types::*;
+    /// fn example(location: &JsObject) {
+    ///     // you can get `location["href"]` like this
+    ///     let _href: JsValue = location.js_get_field(&"href");
     ///
-    ///     // but you should use built-in getters instead
-    ///     let href: JsString = wsdom::dom::location(&browser).get_href();
+    ///     // but with the `wsdom` crate you should use the built-in getter instead:
+    ///     // `let href: JsString = location.get_href();`
     /// }
     /// ```
+    #[track_caller]
     pub fn js_get_field(&self, property: &dyn UseInJsCode) -> JsValue {
         let browser = self.browser.clone();
         let id = {
             let mut link = browser.0.lock();
-            let out_id = link.get_new_id();
-            let self_id = self.id;
-            let property = UseInJsCodeWriter(property);
-            if let Err(e) = writeln!(
-                link.raw_commands_buf(),
-                "try{{{SET}({out_id},{GET}({self_id})[{property}])}}catch($){{{ERR}({out_id},$)}};"
-            ) {
-                link.kill(Error::CommandSerialize(e));
-            }
+            let out_id = link.get_new_value_id();
+            link.emit_get(out_id, self, property);
             link.wake_outgoing_lazy();
             out_id
         };
@@ -312,27 +304,19 @@ impl JsObject {
     /// Use `js_set_field` only when needed
     ///
     /// ```rust
-    /// # use wsdom_core::Browser;
-    /// # use wsdom_core::js_types::*;
-    /// fn example(browser: Browser) {
-    ///     // you can set `window["location"]["href"]` like this
-    ///     wsdom::dom::location(&browser).js_set_field(&"href", &"https://example.com/");
+    /// # use px_wsdom_core::js_types::*;
+    /// fn example(location: &JsObject) {
+    ///     // you can set `location["href"]` like this
+    ///     location.js_set_field(&"href", &"https://example.com/");
     ///
-    ///     // but you should use built-in setters instead
-    ///     wsdom::dom::location(&browser).set_href(&"https://example.com");
+    ///     // but with the `wsdom` crate you should use the built-in setter instead:
+    ///     // `location.set_href(&"https://example.com");`
     /// }
     /// ```
     pub fn js_set_field(&self, property: &dyn UseInJsCode, value: &dyn UseInJsCode) {
-        let self_id = self.id;
         let mut link = self.browser.0.lock();
-        let (property, value) = (UseInJsCodeWriter(property), UseInJsCodeWriter(value));
-        if let Err(e) = writeln!(
-            link.raw_commands_buf(),
-            "{GET}({self_id})[{property}]={value};"
-        ) {
-            link.kill(Error::CommandSerialize(e));
-        }
-        link.wake_outgoing();
+        link.emit_set_field(self, property, value);
+        link.wake_outgoing_lazy();
     }
 
     /// Call a method on this object.
@@ -340,29 +324,28 @@ impl JsObject {
     /// Most types in WSDOM already come with safe Rust wrappers for their methods, so you should use those instead.
     ///
     /// ```rust
-    /// # use wsdom_core::Browser;
-    /// fn example(browser: &Browser) {
-    ///     let console = wsdom::dom::console(browser);
+    /// # use px_wsdom_core::js_types::JsObject;
+    /// fn example(console: &JsObject) {
     ///     // you can call console.log like this
     ///     console.js_call_method("log", [&"hello" as &_], false);
-    ///     
-    ///     // but the better way is to use
-    ///     wsdom::dom::console(&browser).log(&[&"Hello" as &_]);
+    ///
+    ///     // but with the `wsdom` crate the better way is to use
+    ///     // `console.log(&[&"Hello" as &_]);`
     /// }
     /// ```
     ///
     /// Be aware that the first argument (`method_name`) is NOT escaped.
     ///
     /// Set `last_arg_variadic` to `true` if you want to "spread" the last argument as `obj.method(arg1, arg2, ...arg3)`.
+    #[track_caller]
     pub fn js_call_method<'a>(
         &'a self,
         method_name: &'a str,
         args: impl IntoIterator<Item = &'a dyn UseInJsCode>,
         last_arg_variadic: bool,
     ) -> JsValue {
-        let self_id = self.id;
         self.browser.call_function_inner(
-            &format_args!("{GET}({self_id}).{method_name}"),
+            Callee::Method(self.id, method_name),
             args,
             last_arg_variadic,
         )
@@ -370,17 +353,14 @@ impl JsObject {
     /// Call this object: `obj()`.
     ///
     /// Most types in WSDOM already come with safe Rust wrappers for their methods, so you should use those instead.
+    #[track_caller]
     pub fn js_call_self<'a>(
         &'a self,
         args: impl IntoIterator<Item = &'a dyn UseInJsCode>,
         last_arg_variadic: bool,
     ) -> JsValue {
-        let self_id = self.id;
-        self.browser.call_function_inner(
-            &format_args!("({GET}({self_id}))"),
-            args,
-            last_arg_variadic,
-        )
+        self.browser
+            .call_function_inner(Callee::Value(self.id), args, last_arg_variadic)
     }
 }
 
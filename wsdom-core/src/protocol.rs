@@ -1,8 +1,13 @@
-use alloc::string::{String, ToString};
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+};
 use core::fmt;
 
 use portal_jit_host_names::{HostMethodNames, PropertyAccess};
 
+use crate::link::Browser;
+
 /// WSDOM-owned semantic keys for the private `_w` protocol object.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum WsdomMethod {
@@ -15,6 +20,14 @@ pub enum WsdomMethod {
     Import,
     RpcReply,
     Allocate,
+    Checkpoint,
+    DeleteMany,
+    DeleteRanges,
+    HeapSize,
+    Bytes,
+    ReplyBytes,
+    ReplyBytesCredit,
+    WrapCallback,
 }
 
 impl fmt::Display for WsdomMethod {
@@ -29,25 +42,44 @@ impl fmt::Display for WsdomMethod {
             Self::Import => "x",
             Self::RpcReply => "rp",
             Self::Allocate => "a",
+            Self::Checkpoint => "k",
+            Self::DeleteMany => "dm",
+            Self::DeleteRanges => "dr",
+            Self::HeapSize => "z",
+            Self::Bytes => "b",
+            Self::ReplyBytes => "rb",
+            Self::ReplyBytesCredit => "rc",
+            Self::WrapCallback => "cw",
         })
     }
 }
 
+/// Every protocol member, in declaration order. A JS client must provide all of them.
+pub const ALL_METHODS: [WsdomMethod; 17] = [
+    WsdomMethod::Get,
+    WsdomMethod::Delete,
+    WsdomMethod::Set,
+    WsdomMethod::Reply,
+    WsdomMethod::Error,
+    WsdomMethod::Catch,
+    WsdomMethod::Import,
+    WsdomMethod::RpcReply,
+    WsdomMethod::Allocate,
+    WsdomMethod::Checkpoint,
+    WsdomMethod::DeleteMany,
+    WsdomMethod::DeleteRanges,
+    WsdomMethod::HeapSize,
+    WsdomMethod::Bytes,
+    WsdomMethod::ReplyBytes,
+    WsdomMethod::ReplyBytesCredit,
+    WsdomMethod::WrapCallback,
+];
+
 /// Resolve WSDOM's complete protocol ABI before a custom host mapping is used.
 pub fn validate_host_method_names<N: HostMethodNames<WsdomMethod>>(
     names: &N,
 ) -> Result<(), String> {
-    for method in [
-        WsdomMethod::Get,
-        WsdomMethod::Delete,
-        WsdomMethod::Set,
-        WsdomMethod::Reply,
-        WsdomMethod::Error,
-        WsdomMethod::Catch,
-        WsdomMethod::Import,
-        WsdomMethod::RpcReply,
-        WsdomMethod::Allocate,
-    ] {
+    for method in ALL_METHODS {
         names.property(method).map_err(|err| err.to_string())?;
     }
     Ok(())
@@ -90,6 +122,123 @@ pub const CATCH: &str = "_w.c";
 pub const IMPORT: &str = "_w.x";
 pub const REPLY: &str = "_w.rp";
 pub const ALLOC: &str = "_w.a";
+pub const CHECKPOINT: &str = "_w.k";
+pub const DEL_MANY: &str = "_w.dm";
+pub const DEL_RANGES: &str = "_w.dr";
+pub const HEAP_SIZE: &str = "_w.z";
+pub const BYTES: &str = "_w.b";
+pub const REPLY_BYTES: &str = "_w.rb";
+pub const REPLY_BYTES_CREDIT: &str = "_w.rc";
+pub const WRAP_CALLBACK: &str = "_w.cw";
+
+/// The protocol members a [Browser] emits, resolved once by [Browser::with_host_names].
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct HostNames {
+    /// The property name of each member, in [ALL_METHODS] order.
+    properties: [String; ALL_METHODS.len()],
+    pub(crate) get: String,
+    pub(crate) del: String,
+    pub(crate) set: String,
+    pub(crate) rep: String,
+    pub(crate) err: String,
+    pub(crate) catch: String,
+    pub(crate) import: String,
+    pub(crate) reply: String,
+    pub(crate) checkpoint: String,
+    pub(crate) del_many: String,
+    pub(crate) del_ranges: String,
+    pub(crate) heap_size: String,
+    pub(crate) bytes: String,
+    pub(crate) reply_bytes: String,
+    pub(crate) reply_bytes_credit: String,
+    pub(crate) wrap_callback: String,
+}
+
+impl HostNames {
+    fn resolve<N: HostMethodNames<WsdomMethod>>(names: &N) -> Result<Self, String> {
+        validate_host_method_names(names)?;
+        let member = |method| member(names, method);
+        Ok(Self {
+            properties: ALL_METHODS.map(|method| {
+                names
+                    .property(method)
+                    .expect("WSDOM host names must be validated")
+                    .name()
+                    .to_string()
+            }),
+            get: member(WsdomMethod::Get),
+            del: member(WsdomMethod::Delete),
+            set: member(WsdomMethod::Set),
+            rep: member(WsdomMethod::Reply),
+            err: member(WsdomMethod::Error),
+            catch: member(WsdomMethod::Catch),
+            import: member(WsdomMethod::Import),
+            reply: member(WsdomMethod::RpcReply),
+            checkpoint: member(WsdomMethod::Checkpoint),
+            del_many: member(WsdomMethod::DeleteMany),
+            del_ranges: member(WsdomMethod::DeleteRanges),
+            heap_size: member(WsdomMethod::HeapSize),
+            bytes: member(WsdomMethod::Bytes),
+            reply_bytes: member(WsdomMethod::ReplyBytes),
+            reply_bytes_credit: member(WsdomMethod::ReplyBytesCredit),
+            wrap_callback: member(WsdomMethod::WrapCallback),
+        })
+    }
+    /// The property name the JS client must provide for `method`.
+    pub(crate) fn property(&self, method: WsdomMethod) -> &str {
+        &self.properties[method as usize]
+    }
+}
+
+impl Default for HostNames {
+    fn default() -> Self {
+        Self {
+            properties: ALL_METHODS.map(|method| method.to_string()),
+            get: GET.into(),
+            del: DEL.into(),
+            set: SET.into(),
+            rep: REP.into(),
+            err: ERR.into(),
+            catch: CATCH.into(),
+            import: IMPORT.into(),
+            reply: REPLY.into(),
+            checkpoint: CHECKPOINT.into(),
+            del_many: DEL_MANY.into(),
+            del_ranges: DEL_RANGES.into(),
+            heap_size: HEAP_SIZE.into(),
+            bytes: BYTES.into(),
+            reply_bytes: REPLY_BYTES.into(),
+            reply_bytes_credit: REPLY_BYTES_CREDIT.into(),
+            wrap_callback: WRAP_CALLBACK.into(),
+        }
+    }
+}
+
+impl Browser {
+    /// Emit protocol calls with the members of `names` instead of the canonical ones,
+    /// for a JS client whose `_w` object is property-mangled.
+    ///
+    /// The client must be generated with the same mapping (`wsdom_gen::Options::host_names`).
+    /// Only affects [Encoding::Source][crate::Encoding::Source]; opcodes do not go through `_w`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `names` does not resolve every [WsdomMethod].
+    ///
+    /// ```rust
+    /// # use portal_jit_host_names::MappedHostMethodNames;
+    /// # use px_wsdom_core::{Browser, protocol_names::ALL_METHODS};
+    /// let names = MappedHostMethodNames::new(
+    ///     ALL_METHODS.map(|method| (method.to_string(), format!("$w{method}"))),
+    /// );
+    /// let browser = Browser::new().with_host_names(&names);
+    /// ```
+    pub fn with_host_names<N: HostMethodNames<WsdomMethod>>(self, names: &N) -> Self {
+        let names = HostNames::resolve(names).expect("WSDOM host names must be complete");
+        self.0.lock().names = Arc::new(names);
+        self
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -104,6 +253,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn canonical_host_names_match_the_constants() {
+        assert_eq!(
+            HostNames::resolve(&CanonicalHostMethodNames).unwrap(),
+            HostNames::default()
+        );
+    }
+
     #[test]
     fn mapped_protocol_access_is_safe() {
         let names = MappedHostMethodNames::new([
@@ -116,6 +273,14 @@ mod tests {
             ("x".into(), "x".into()),
             ("rp".into(), "rp".into()),
             ("a".into(), "a".into()),
+            ("k".into(), "k".into()),
+            ("dm".into(), "dm".into()),
+            ("dr".into(), "dr".into()),
+            ("z".into(), "z".into()),
+            ("b".into(), "b".into()),
+            ("rb".into(), "rb".into()),
+            ("rc".into(), "rc".into()),
+            ("cw".into(), "cw".into()),
         ]);
         validate_host_method_names(&names).unwrap();
         assert_eq!(call(&names, WsdomMethod::Get, "1"), "_w[\"not-a-name\"](1)");
@@ -2,39 +2,116 @@ use alloc::borrow::ToOwned;
 use alloc::boxed::Box;
 use alloc::string::String;
 use core::marker::PhantomData;
-use core::{fmt::Write, future::Future, pin::Pin, task::Poll};
+use core::panic::Location;
+use core::time::Duration;
+use core::{future::Future, pin::Pin, task::Poll};
 use spin::Mutex;
 
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
 use crate::Browser;
-use crate::js_types::JsValue;
-use crate::link::{BrowserInternal, Error, RetrievalState};
-use crate::protocol::{CATCH, GET, REP};
+use crate::js_cast::JsCast;
+use crate::js_types::{JsBoolean, JsNullable, JsNumber, JsString, JsValue};
+use crate::link::{BrowserInternal, RetrievalState};
+use crate::tagged;
+use crate::timer::Deadline;
 
 /// A [Future] for retrieving value from the JS side to the Rust side.
 ///
-/// If something goes wrong (for example if the network disconnects), this Future will simply pend forever.
+/// The value arrives in the [tagged encoding][crate::tagged], so `undefined`, `NaN`, `BigInt`s, `Map`s,
+/// typed arrays, and the like deserialize faithfully.
+///
+/// If the Browser is [closed][Browser::close] (for example because the network disconnected)
+/// before the value arrives, this Future fails with [RetrieveError::Disconnected].
+/// With a [timeout][Self::timeout], it also fails with [RetrieveError::TimedOut].
 pub struct RetrieveFuture<'a, T: DeserializeOwned> {
     pub(crate) id: u64,
     pub(crate) ret_id: u64,
     // pub(crate) error_slot: u64,
     pub(crate) link: &'a Browser,
+    deadline: Deadline,
     _phantom: PhantomData<Pin<Box<T>>>,
 }
 
 impl<'a, T: DeserializeOwned> RetrieveFuture<'a, T> {
     pub(crate) fn new(id: u64, link: &'a Browser) -> Self {
+        let timeout = link.0.lock().default_timeout;
         Self {
             id,
             ret_id: 0,
             // error_slot: 0,
             link,
+            deadline: Deadline::new(timeout),
             _phantom: PhantomData,
         }
     }
+    /// Fail with [RetrieveError::TimedOut] if the value has not arrived `timeout` after the first poll,
+    /// overriding the Browser's [default timeout][Browser::with_default_timeout].
+    ///
+    /// Needs a [timer][Browser::with_timer]. A reply arriving after the deadline is discarded.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.deadline = Deadline::new(Some(timeout));
+        self
+    }
+}
+/// JS types with a natural Rust counterpart to [retrieve][Retrieve::retrieve] into.
+///
+/// Implemented for the primitives, for [JsNullable] (as an [Option]), and for `Array` (as a [Vec](alloc::vec::Vec)).
+/// Implement it for a class generated with `load_custom_ts!` to retrieve it as your own type:
+///
+/// ```rust,ignore
+/// // point.d.ts: `interface Point { x: number; y: number; }`
+/// px_wsdom::load_custom_ts!("point.d.ts");
+///
+/// #[derive(serde::Deserialize)]
+/// struct RustPoint {
+///     x: f64,
+///     y: f64,
+/// }
+/// impl px_wsdom::Retrieve for Point {
+///     type Output = RustPoint;
+/// }
+/// ```
+///
+/// Retrieving a value that does not deserialize as the Output fails with [RetrieveError::Deserialize].
+pub trait Retrieve: JsCast {
+    /// The Rust type this JS type is retrieved as.
+    type Output: DeserializeOwned;
+    /// Retrieve this value from the JS side as [Self::Output].
+    fn retrieve(&self) -> RetrieveFuture<'_, Self::Output> {
+        AsRef::<JsValue>::as_ref(self).retrieve()
+    }
+}
+
+impl Retrieve for JsBoolean {
+    type Output = bool;
+}
+impl Retrieve for JsString {
+    type Output = String;
+}
+impl Retrieve for JsNumber {
+    type Output = f64;
 }
+impl<T: Retrieve> Retrieve for JsNullable<T> {
+    type Output = Option<T::Output>;
+}
+
+/// Why a value could not be retrieved.
+#[derive(Debug)]
+pub enum RetrieveError {
+    /// The JS code computing the value threw. This holds the thrown exception.
+    Thrown(JsValue),
+    /// The Browser was closed or died before the JS client replied.
+    /// [Browser::take_error] tells why.
+    Disconnected,
+    /// The JS client did not reply before the deadline.
+    TimedOut,
+    /// The value arrived, but did not deserialize as the requested Rust type.
+    /// Only this retrieval fails; the Browser carries on.
+    Deserialize(serde_json::Error),
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(untagged)]
 enum ResI<T> {
@@ -42,22 +119,20 @@ enum ResI<T> {
     Error { error: u64 },
 }
 impl<'a, T: DeserializeOwned> Future for RetrieveFuture<'a, T> {
-    type Output = Result<T, JsValue>;
+    type Output = Result<T, RetrieveError>;
     fn poll(self: Pin<&mut Self>, cx: &mut core::task::Context<'_>) -> Poll<Self::Output> {
         let this = self.get_mut();
         let mut link = this.link.0.lock();
+        let dead = link.is_dead();
         match this.ret_id {
+            0 if dead => Poll::Ready(Err(RetrieveError::Disconnected)),
             0 => {
                 let ret_id = link.get_new_id();
                 this.ret_id = ret_id;
                 let this_id = this.id;
                 // this.error_slot = link.get_new_id();
                 // let error_slot = this.error_slot;
-                writeln!(
-                    link.raw_commands_buf(),
-                    "{REP}({ret_id},{CATCH}({this_id}));"
-                )
-                .unwrap();
+                link.emit_reply(ret_id, this_id);
                 link.wake_outgoing();
                 link.retrievals.insert(
                     ret_id,
@@ -65,12 +140,23 @@ impl<'a, T: DeserializeOwned> Future for RetrieveFuture<'a, T> {
                         waker: cx.waker().to_owned(),
                         last_value: String::new(),
                         times: 0,
+                        payloads: None,
                     },
                 );
+                if this.deadline.poll_elapsed(&link, cx) {
+                    link.retrievals.remove(&ret_id);
+                    this.ret_id = 0;
+                    return Poll::Ready(Err(RetrieveError::TimedOut));
+                }
                 Poll::Pending
             }
             ret_id => match link.retrievals.entry(ret_id) {
                 hashbrown::hash_map::Entry::Occupied(mut occ) => match occ.get_mut() {
+                    RetrievalState { times: 0, .. } if dead => {
+                        occ.remove();
+                        this.ret_id = 0;
+                        Poll::Ready(Err(RetrieveError::Disconnected))
+                    }
                     RetrievalState {
                         waker, times: 0, ..
                     } => {
@@ -78,31 +164,54 @@ impl<'a, T: DeserializeOwned> Future for RetrieveFuture<'a, T> {
                         if !waker.will_wake(new_waker) {
                             *waker = new_waker.to_owned();
                         }
+                        if this.deadline.poll_elapsed(&link, cx) {
+                            // Forget the retrieval; a late reply will be discarded.
+                            link.retrievals.remove(&ret_id);
+                            this.ret_id = 0;
+                            return Poll::Ready(Err(RetrieveError::TimedOut));
+                        }
                         Poll::Pending
                     }
                     RetrievalState { last_value, .. } => {
                         let v = core::mem::take(last_value);
                         occ.remove();
                         let v = v.split_once(':').unwrap().1;
-                        match serde_json::from_str(v) {
+                        let v = serde_json::from_str(v).and_then(|v| match v {
+                            ResI::Value { value } => {
+                                tagged::from_value(&value).map(|value| ResI::Value { value })
+                            }
+                            ResI::Error { error } => Ok(ResI::Error { error }),
+                        });
+                        match v {
                             Ok(v) => {
                                 let v = match v {
                                     ResI::Value { value } => Ok(value),
-                                    ResI::Error { error } => Err(JsValue {
-                                        id: error,
-                                        browser: this.link.clone(),
-                                    }),
+                                    ResI::Error { error } => {
+                                        let location = link.location(this.id);
+                                        link.set_location(error, location);
+                                        link.count_value(
+                                            error,
+                                            location.unwrap_or(Location::caller()),
+                                        );
+                                        Err(RetrieveError::Thrown(JsValue {
+                                            id: error,
+                                            browser: this.link.clone(),
+                                        }))
+                                    }
                                 };
                                 this.ret_id = 0;
                                 Poll::Ready(v)
                             }
                             Err(e) => {
-                                link.kill(Error::DataDeserialize(e));
-                                Poll::Pending
+                                this.ret_id = 0;
+                                Poll::Ready(Err(RetrieveError::Deserialize(e)))
                             }
                         }
                     }
                 },
+                hashbrown::hash_map::Entry::Vacant(_) if dead => {
+                    Poll::Ready(Err(RetrieveError::Disconnected))
+                }
                 hashbrown::hash_map::Entry::Vacant(_) => Poll::Pending,
             },
         }
@@ -122,3 +231,90 @@ impl<'a, T: DeserializeOwned> Drop for RetrieveFuture<'a, T> {
         }
     }
 }
+
+impl BrowserInternal {
+    /// Handle a `p<id>:<json>` reply nobody is waiting for.
+    ///
+    /// If the value threw, the JS client stored the exception in a new slot for us; free it.
+    pub(crate) fn discard_reply(&mut self, message: &str) {
+        let Some((_, json)) = message.split_once(':') else {
+            return;
+        };
+        if let Ok(ResI::<serde::de::IgnoredAny>::Error { error }) = serde_json::from_str(json) {
+            self.emit_delete(error);
+            self.wake_outgoing_lazy();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::task::{Context, Poll};
+    use futures_util::{FutureExt, task::noop_waker_ref};
+
+    use super::Retrieve;
+    use crate::{
+        Browser, JsCast, RetrieveError,
+        js_types::{JsNullable, JsNumber},
+        test_support::sent,
+    };
+
+    /// Take the next frame and reply `json` to the retrieval in it.
+    fn reply(browser: &mut Browser, json: &str) {
+        let frame = sent(browser);
+        let ret_id = frame
+            .rsplit("_w.r(")
+            .next()
+            .unwrap()
+            .split(',')
+            .next()
+            .unwrap();
+        browser.receive_incoming_message(alloc::format!("p{ret_id}:{json}"));
+    }
+
+    #[test]
+    fn retrieve_into_rust_types() {
+        let mut browser = Browser::new();
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let pair = browser.call_function("pair", [], false);
+        let mut retrieval = pair.retrieve::<(f64, alloc::string::String)>();
+        assert!(retrieval.poll_unpin(&mut cx).is_pending());
+        reply(&mut browser, r#"{"value":[1.5,"a"]}"#);
+        assert!(matches!(
+            retrieval.poll_unpin(&mut cx),
+            Poll::Ready(Ok((1.5, s))) if s == "a"
+        ));
+
+        let maybe: JsNullable<JsNumber> = browser.call_function("f", [], false).unchecked_into();
+        let mut retrieval = Retrieve::retrieve(&maybe);
+        assert!(retrieval.poll_unpin(&mut cx).is_pending());
+        reply(&mut browser, r#"{"value":{"$":"u"}}"#);
+        assert!(matches!(
+            retrieval.poll_unpin(&mut cx),
+            Poll::Ready(Ok(None))
+        ));
+        let mut retrieval = maybe.retrieve();
+        assert!(retrieval.poll_unpin(&mut cx).is_pending());
+        reply(&mut browser, r#"{"value":2}"#);
+        assert!(matches!(
+            retrieval.poll_unpin(&mut cx),
+            Poll::Ready(Ok(Some(2.0)))
+        ));
+    }
+
+    #[test]
+    fn mismatched_type_fails_only_the_retrieval() {
+        let mut browser = Browser::new();
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let value = browser.call_function("f", [], false);
+        let mut retrieval = value.retrieve::<f64>();
+        assert!(retrieval.poll_unpin(&mut cx).is_pending());
+        reply(&mut browser, r#"{"value":"not a number"}"#);
+        assert!(matches!(
+            retrieval.poll_unpin(&mut cx),
+            Poll::Ready(Err(RetrieveError::Deserialize(_)))
+        ));
+        assert!(!browser.is_closed());
+        assert!(browser.0.lock().retrievals.is_empty());
+    }
+}
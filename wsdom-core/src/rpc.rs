@@ -1,14 +1,13 @@
 use core::error::Error;
 use core::marker::PhantomData;
+use core::panic::Location;
 use core::task::Poll;
 
 use futures_core::Stream;
 
 use crate::js_types::JsValue;
-use crate::{Browser, UseInJsCode, link::RpcCellAM, serialize::UseInJsCodeWriter};
+use crate::{Browser, UseInJsCode, link::RpcCellAM};
 
-use crate::protocol::REPLY;
-use core::fmt::Write;
 pin_project_lite::pin_project! {
 
 pub struct RpcHandle<C> {
@@ -26,13 +25,8 @@ impl<C: UseInJsCode> RpcHandle<Reply<C>> {
     pub fn reply(self, c: C) {
         let mut link = self.browser.0.lock();
         let id = self.data.id;
-        writeln!(
-            link.raw_commands_buf(),
-            "{REPLY}({id},{})",
-            UseInJsCodeWriter(&c)
-        )
-        .unwrap();
-        link.wake_outgoing_lazy();
+        link.emit_rpc_reply(id, &c);
+        link.wake_outgoing();
     }
 }
 pub struct Request<T, C> {
@@ -111,6 +105,7 @@ impl RpcDeserialize for JsValue {
         lock: &Lock,
     ) -> Result<(Self, &'a str), ()> {
         let (v, a) = u64::deser(a, browser, recv, lock)?;
+        browser.0.lock().count_value(v, Location::caller());
         return Ok((
             JsValue {
                 id: v,
@@ -0,0 +1,200 @@
+use alloc::{collections::BTreeSet, vec::Vec};
+
+use crate::{
+    js_cast::JsCast,
+    link::{Browser, BrowserInternal},
+};
+
+/// Values [adopted][Self::adopt] during a [Browser::scope], freed together when it ends.
+///
+/// A value that must outlive the scope has to be [promoted][Self::promote].
+pub struct Scope {
+    browser: Browser,
+    id: u64,
+}
+
+/// The scopes of a Browser that have not ended.
+#[derive(Debug, Default)]
+pub(crate) struct Scopes {
+    open: Vec<ScopeState>,
+    next_id: u64,
+}
+
+#[derive(Debug)]
+struct ScopeState {
+    id: u64,
+    members: BTreeSet<u64>,
+}
+
+impl Scopes {
+    fn get_mut(&mut self, id: u64) -> Option<&mut ScopeState> {
+        self.open.iter_mut().find(|scope| scope.id == id)
+    }
+}
+
+impl Browser {
+    /// Run `f`, then free every value it [adopted][Scope::adopt] with a single command.
+    ///
+    /// Dropping an adopted value inside the scope sends nothing; the JS client frees the whole scope at once,
+    /// as ranges of consecutive ids. This suits code that creates many short-lived values, like a render pass.
+    /// Values that must outlive the scope have to be [promoted][Scope::promote];
+    /// any other adopted value still held when the scope ends refers to a freed slot.
+    ///
+    /// Only adopted values belong to the scope, so values that other code creates through this Browser meanwhile,
+    /// on this thread or another, are freed as usual.
+    ///
+    /// ```rust
+    /// # use px_wsdom_core::{Browser, JsCast, UseInJsCode, js_types::JsObject};
+    /// fn draw(browser: &Browser, ctx: &JsObject) -> JsObject {
+    ///     browser.scope(|s| {
+    ///         let path: JsObject = s.adopt(browser.call_constructor("Path2D", [], false).unchecked_into());
+    ///         for i in 0..100 {
+    ///             // The return values of these calls are freed together at the end of the scope.
+    ///             s.adopt(path.js_call_method("rect", [&i as &dyn UseInJsCode, &i, &10, &10], false));
+    ///         }
+    ///         ctx.js_call_method("fill", [&path as &_], false);
+    ///         s.promote(path)
+    ///     })
+    /// }
+    /// ```
+    pub fn scope<R>(&self, f: impl FnOnce(&Scope) -> R) -> R {
+        let scope = {
+            let mut link = self.0.lock();
+            let scopes = &mut link.scopes;
+            scopes.next_id += 1;
+            let id = scopes.next_id;
+            scopes.open.push(ScopeState {
+                id,
+                members: BTreeSet::new(),
+            });
+            Scope {
+                browser: self.clone(),
+                id,
+            }
+        };
+        f(&scope)
+    }
+}
+
+impl Scope {
+    /// Free `value` when the scope ends, and send nothing if it is dropped before that.
+    ///
+    /// A value belongs to at most one scope; adopting it moves it out of any other.
+    /// Imports are cached by the Browser and are never adopted.
+    pub fn adopt<T: JsCast>(&self, value: T) -> T {
+        let id = value.as_ref().id;
+        let mut link = self.browser.0.lock();
+        if link.imports.values().any(|&import| import == id) {
+            return value;
+        }
+        link.remove_from_scope(id);
+        if let Some(scope) = link.scopes.get_mut(self.id) {
+            scope.members.insert(id);
+        }
+        value
+    }
+    /// Keep `value` alive after the scope ends.
+    ///
+    /// The value is then freed when it is dropped, like any value outside a scope.
+    /// To free it with another scope instead, [adopt][Self::adopt] it there.
+    pub fn promote<T: JsCast>(&self, value: T) -> T {
+        let id = value.as_ref().id;
+        if let Some(scope) = self.browser.0.lock().scopes.get_mut(self.id) {
+            scope.members.remove(&id);
+        }
+        value
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        let mut link = self.browser.0.lock();
+        let scopes = &mut link.scopes;
+        let Some(position) = scopes.open.iter().position(|scope| scope.id == self.id) else {
+            return;
+        };
+        let scope = scopes.open.remove(position);
+        if scope.members.is_empty() {
+            return;
+        }
+        link.emit_delete_ranges(&ranges(&scope.members));
+        link.wake_outgoing_lazy();
+    }
+}
+
+impl BrowserInternal {
+    fn remove_from_scope(&mut self, id: u64) {
+        for scope in &mut self.scopes.open {
+            scope.members.remove(&id);
+        }
+    }
+    /// Whether slot `id` will be freed by a scope, so dropping its value needs no command.
+    pub(crate) fn is_scoped(&self, id: u64) -> bool {
+        self.scopes
+            .open
+            .iter()
+            .any(|scope| scope.members.contains(&id))
+    }
+}
+
+/// Group sorted ids into inclusive ranges of consecutive ids.
+fn ranges(ids: &BTreeSet<u64>) -> Vec<(u64, u64)> {
+    let mut ranges: Vec<(u64, u64)> = Vec::new();
+    for &id in ids {
+        match ranges.last_mut() {
+            Some((_, to)) if *to + 1 == id => *to = id,
+            _ => ranges.push((id, id)),
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Browser, test_support::sent};
+
+    #[test]
+    fn scope_frees_its_values_as_ranges() {
+        let mut browser = Browser::new();
+        let kept = browser.scope(|s| {
+            let a = s.adopt(browser.call_function("a", [], false));
+            let kept = s.promote(s.adopt(browser.call_function("b", [], false)));
+            drop(a);
+            for _ in 0..3 {
+                s.adopt(browser.call_function("c", [], false));
+            }
+            kept
+        });
+        assert_eq!(
+            sent(&mut browser),
+            "try{_w.s(2,a())}catch($){_w.e(2,$)};\n\
+             try{_w.s(3,b())}catch($){_w.e(3,$)};\n\
+             try{_w.s(4,c())}catch($){_w.e(4,$)};\n\
+             try{_w.s(5,c())}catch($){_w.e(5,$)};\n\
+             try{_w.s(6,c())}catch($){_w.e(6,$)};\n\
+             _w.dr([[2,2],[4,6]]);\n"
+        );
+        drop(kept);
+        assert_eq!(sent(&mut browser), "_w.dm([3]);\n");
+    }
+
+    #[test]
+    fn scopes_free_only_adopted_values() {
+        let mut browser = Browser::new();
+        browser.scope(|outer| {
+            // Created meanwhile by other code, so freed on its own.
+            drop(browser.call_function("a", [], false));
+            browser.scope(|inner| {
+                let b = inner.adopt(browser.call_function("b", [], false));
+                outer.adopt(inner.promote(b));
+            });
+        });
+        assert_eq!(
+            sent(&mut browser),
+            "try{_w.s(2,a())}catch($){_w.e(2,$)};\n\
+             try{_w.s(3,b())}catch($){_w.e(3,$)};\n\
+             _w.dr([[3,3]]);\n\
+             _w.dm([2]);\n"
+        );
+    }
+}
@@ -1,30 +1,48 @@
 use serde::Serialize;
 
+use crate::command::ValueRef;
 use crate::js::value::JsValue;
-use crate::protocol::GET;
 
 /// For values that can be serialized to JS code:
 /// - Rust values that implement `serde::Serialize`
 /// - WRMI stubs ([JsValue]s)
 ///
 /// This trait is used by [ToJs].
+///
+/// When the formatter's alternate flag is set (`{:#}`), the value is being sent to an
+/// [opcode-mode][crate::Encoding::Opcode] client and must be written as a JSON operand instead of source:
+/// `["l",json]` for a literal, `["r",id]` for a held value, `["n","name"]` for a global,
+/// `["u"]` for `undefined`, or `["a",[operands...]]` for an array.
 pub trait UseInJsCode {
     fn serialize_to(&self, buf: &mut core::fmt::Formatter<'_>) -> core::fmt::Result;
 }
 
 impl UseInJsCode for JsValue {
     fn serialize_to(&self, buf: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        let self_id = self.id;
-        write!(buf, "{GET}({self_id})").unwrap();
-        Ok(())
+        ValueRef(self.id).serialize_to(buf)
     }
 }
 
+/// Sends any [Serialize] value to JS, in the [tagged encoding][crate::tagged] where JSON would lose information.
+///
+/// Integers of up to 32 bits become `Number`s, and 64- and 128-bit integers (including `usize` and `isize`)
+/// become `BigInt`s, even when they are small. Convert a value to a narrower type first to send it as a `Number`.
+///
+/// ```rust
+/// # use px_wsdom_core::{Browser, SerdeToJs, js_types::JsValue};
+/// fn example(browser: &Browser) {
+///     let ids: std::collections::BTreeMap<u64, &str> = [(u64::MAX, "max")].into();
+///     // A `Map` with a `BigInt` key.
+///     let _map: JsValue = browser.new_value(&SerdeToJs(&ids));
+/// }
+/// ```
 pub struct SerdeToJs<'a, T: ?Sized>(pub &'a T);
 
+impl<'a, T: Serialize + ?Sized> ToJs<JsValue> for SerdeToJs<'a, T> {}
+
 impl<'a, T: Serialize + ?Sized> UseInJsCode for SerdeToJs<'a, T> {
     fn serialize_to(&self, buf: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        buf.write_str(&serde_json::to_string(&self.0).map_err(|_| core::fmt::Error)?)
+        crate::tagged::write(self.0, buf)
     }
 }
 
@@ -39,7 +57,12 @@ impl<'a, T: UseInJsCode + ?Sized> core::fmt::Display for UseInJsCodeWriter<'a, T
 pub struct RawCodeImmediate<'a>(pub &'a str);
 impl<'a> UseInJsCode for RawCodeImmediate<'a> {
     fn serialize_to(&self, buf: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        buf.write_str(self.0)
+        if buf.alternate() {
+            // Opcode clients can only resolve global names, like `document` or `Math`.
+            write!(buf, "[\"n\",{}]", UseInJsCodeWriter(self.0))
+        } else {
+            buf.write_str(self.0)
+        }
     }
 }
 
@@ -0,0 +1,211 @@
+/*!
+Resumable sessions, so a client can reconnect without losing commands or replies.
+
+With a session (see [Browser::with_session]), every frame carries a sequence number and acknowledges the other side's,
+and each side keeps its unacknowledged frames. When the transport reconnects,
+the client sends a [Resume] frame with the session token, the integration routes the new connection
+to the existing Browser, and both sides replay what the other has not acknowledged.
+Frames that were already received are recognized by their sequence number and dropped,
+so every command runs exactly once and the JS client keeps its heap.
+
+Frames sent by Rust:
+- `t<token>` starts a session.
+- `m<seq>,<ack>:<payload>` carries commands.
+- `a<ack>` only acknowledges.
+
+Frames sent by JS:
+- `t<token>:<ack>` is the first frame of every connection. The token is empty for a new session.
+- `m<seq>,<ack>:<payload>` carries one message (a retrieval reply, an RPC call, or an exception report).
+- `a<ack>` only acknowledges.
+*/
+
+use core::task::Context;
+
+use alloc::{collections::VecDeque, format, string::String};
+
+use crate::link::{Browser, BrowserInternal};
+
+/// The first frame a resumable client sends on each connection.
+///
+/// Integrations parse it to find the Browser the client is reattaching to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Resume<'a> {
+    /// The token the client got at the start of the session, or empty for a new session.
+    pub token: &'a str,
+    /// The sequence number of the last frame the client received.
+    pub received: u64,
+}
+
+impl<'a> Resume<'a> {
+    /// Parse a `t<token>:<ack>` frame.
+    pub fn parse(frame: &'a str) -> Option<Self> {
+        let (token, received) = frame.strip_prefix('t')?.rsplit_once(':')?;
+        Some(Self {
+            token,
+            received: received.parse().ok()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Session {
+    token: String,
+    announced: bool,
+    /// Sequence number of the last frame queued for sending.
+    sent: u64,
+    /// Frames the client has not acknowledged yet, oldest first.
+    unacked: VecDeque<(u64, String)>,
+    /// How many of `unacked` went out on the current connection.
+    flushed: usize,
+    /// Sequence number of the last frame received in order.
+    received: u64,
+    ack_due: bool,
+}
+
+impl Browser {
+    /// Number frames and keep the unacknowledged ones, so a client that reconnects can resume this session.
+    ///
+    /// The `token` identifies the session to the reconnecting client, so it must be unguessable.
+    /// The JS client must be connected through a `WSDOMTransport` with `resumable: true`,
+    /// and the integration must route resumed connections back to this Browser
+    /// (see the [session][crate::session] module).
+    pub fn with_session(self, token: impl Into<String>) -> Self {
+        self.0.lock().session = Some(Session {
+            token: token.into(),
+            announced: false,
+            sent: 0,
+            unacked: VecDeque::new(),
+            flushed: 0,
+            received: 0,
+            ack_due: false,
+        });
+        self
+    }
+    /// The token given to [with_session][Self::with_session].
+    pub fn session_token(&self) -> Option<String> {
+        self.0.lock().session.as_ref().map(|s| s.token.clone())
+    }
+}
+
+impl BrowserInternal {
+    /// The next frame to send to the client, in a session.
+    pub(crate) fn next_session_frame(&mut self, cx: &mut Context<'_>) -> Option<String> {
+        let commands = self.take_frame(cx);
+        let session = self.session.as_mut()?;
+        if let Some(commands) = commands {
+            session.sent += 1;
+            session.unacked.push_back((session.sent, commands));
+        }
+        if !session.announced {
+            session.announced = true;
+            return Some(format!("t{}", session.token));
+        }
+        if let Some((seq, payload)) = session.unacked.get(session.flushed) {
+            session.flushed += 1;
+            session.ack_due = false;
+            return Some(format!("m{seq},{}:{payload}", session.received));
+        }
+        if core::mem::take(&mut session.ack_due) {
+            return Some(format!("a{}", session.received));
+        }
+        None
+    }
+    /// Unwrap a frame received in a session, returning the payload if it is new.
+    pub(crate) fn receive_session_frame<'a>(&mut self, frame: &'a str) -> Option<&'a str> {
+        let session = self.session.as_mut()?;
+        if let Some(resume) = Resume::parse(frame) {
+            if resume.token == session.token {
+                // A new connection: everything the client has not received must be sent again.
+                session.acknowledge(resume.received);
+                session.flushed = 0;
+                session.ack_due = true;
+                self.wake_outgoing();
+            }
+            return None;
+        }
+        if let Some(ack) = frame.strip_prefix('a') {
+            session.acknowledge(ack.parse().ok()?);
+            return None;
+        }
+        let (header, payload) = frame.strip_prefix('m')?.split_once(':')?;
+        let (seq, ack) = header.split_once(',')?;
+        let (seq, ack) = (seq.parse::<u64>().ok()?, ack.parse().ok()?);
+        session.acknowledge(ack);
+        // Replays of frames already received are only acknowledged again.
+        // Frames after a gap are dropped; the client replays them in order when it resumes.
+        session.ack_due = true;
+        self.wake_outgoing_lazy();
+        let session = self.session.as_mut()?;
+        if seq != session.received + 1 {
+            return None;
+        }
+        session.received = seq;
+        Some(payload)
+    }
+}
+
+impl Session {
+    pub(crate) fn unacked_len(&self) -> usize {
+        self.unacked.iter().map(|(_, payload)| payload.len()).sum()
+    }
+    fn acknowledge(&mut self, ack: u64) {
+        while self.unacked.front().is_some_and(|(seq, _)| *seq <= ack) {
+            self.unacked.pop_front();
+            self.flushed = self.flushed.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::task::{Context, Poll};
+    use futures_util::{FutureExt, task::noop_waker_ref};
+
+    use super::Resume;
+    use crate::{Browser, test_support::sent_frames};
+
+    #[test]
+    fn resume_replays_unacknowledged_frames_once() {
+        let mut browser = Browser::new().with_session("secret");
+        let value = browser.call_function("f", [], false);
+        let mut retrieval = value.retrieve_json();
+        let mut cx = Context::from_waker(noop_waker_ref());
+        assert!(retrieval.poll_unpin(&mut cx).is_pending());
+        assert_eq!(
+            sent_frames(&mut browser),
+            [
+                "tsecret",
+                "m1,0:try{_w.s(2,f())}catch($){_w.e(2,$)};\n_w.r(3,_w.c(2));\n"
+            ]
+        );
+        browser.receive_incoming_message("t:0".into());
+        browser.receive_incoming_message("m1,0:p3:{\"value\":1}".into());
+        // The same reply, replayed after a reconnect, is dropped.
+        browser.receive_incoming_message("m1,0:p3:{\"value\":1}".into());
+        assert!(matches!(
+            retrieval.poll_unpin(&mut cx),
+            Poll::Ready(Ok(v)) if v == 1
+        ));
+
+        browser.run_raw_code(format_args!("lost()"));
+        assert_eq!(sent_frames(&mut browser), ["m2,1:{ lost() }\n"]);
+        // The client reconnects, having received only the first frame.
+        assert_eq!(
+            Resume::parse("tsecret:1"),
+            Some(Resume {
+                token: "secret",
+                received: 1
+            })
+        );
+        browser.receive_incoming_message("tsecret:1".into());
+        browser.run_raw_code(format_args!("after()"));
+        assert_eq!(
+            sent_frames(&mut browser),
+            ["m2,1:{ lost() }\n", "m3,1:{ after() }\n"]
+        );
+        browser.receive_incoming_message("a3".into());
+        browser.receive_incoming_message("tsecret:3".into());
+        assert_eq!(sent_frames(&mut browser), ["a1"]);
+        assert!(browser.take_error().is_none());
+    }
+}
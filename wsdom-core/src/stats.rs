@@ -0,0 +1,243 @@
+use core::{future::Future, panic::Location, pin::Pin, task::Poll, time::Duration};
+
+use alloc::{borrow::ToOwned, collections::BTreeMap, string::String};
+use hashbrown::HashSet;
+
+#[cfg(feature = "leak-tracker")]
+use crate::leaks::HandleKind;
+use crate::{
+    link::{Browser, BrowserInternal, Error},
+    retrieve::RetrieveError,
+    timer::Deadline,
+};
+
+/// A snapshot of what a Browser holds and has exchanged with the JS client. Returned by [Browser::stats].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Stats {
+    /// Slots on the JS client referenced by at least one [JsValue][crate::js_types::JsValue].
+    pub live_values: usize,
+    /// Ids allocated so far, for values, retrievals, and other bookkeeping.
+    pub ids_allocated: u64,
+    /// Bytes taken from the Browser's [Stream][futures_core::Stream].
+    pub bytes_sent: u64,
+    /// Frames taken from the Browser's [Stream][futures_core::Stream].
+    pub frames_sent: u64,
+    /// Bytes of messages received from the JS client.
+    pub bytes_received: u64,
+    /// Messages received from the JS client.
+    pub frames_received: u64,
+    /// Retrievals, awaits, and [syncs][Browser::sync] waiting for a reply.
+    pub pending_retrievals: usize,
+    /// Callbacks whose [Callback][crate::callback::Callback] stream is alive.
+    pub callbacks: usize,
+    /// Requests queued in each RPC endpoint, by name.
+    pub rpc_queues: BTreeMap<String, usize>,
+}
+
+/// Running totals behind [Stats].
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    live_values: usize,
+    bytes_sent: u64,
+    frames_sent: u64,
+    bytes_received: u64,
+    frames_received: u64,
+    /// The retrieval ids of live callbacks, which are not pending retrievals.
+    callbacks: HashSet<u64>,
+}
+
+impl Browser {
+    /// Report what this Browser holds and has exchanged with the JS client so far.
+    ///
+    /// Useful for spotting leaks: `live_values` should stay flat over a loop that frees what it creates.
+    pub fn stats(&self) -> Stats {
+        let link = self.0.lock();
+        let counters = &link.counters;
+        Stats {
+            live_values: counters.live_values,
+            ids_allocated: link.ids_allocated(),
+            bytes_sent: counters.bytes_sent,
+            frames_sent: counters.frames_sent,
+            bytes_received: counters.bytes_received,
+            frames_received: counters.frames_received,
+            pending_retrievals: link
+                .retrievals
+                .keys()
+                .filter(|id| !counters.callbacks.contains(*id))
+                .count(),
+            callbacks: counters.callbacks.len(),
+            rpc_queues: link
+                .rpc_state
+                .iter()
+                .map(|(name, cell)| (name.to_owned(), cell.0.lock().queue.len()))
+                .collect(),
+        }
+    }
+    /// Ask the JS client how many slots it holds.
+    ///
+    /// Compare with [Stats::live_values] to find slots the JS client keeps but Rust no longer references
+    /// (or values created with [duplicate][crate::js_types::JsValue::duplicate] and forgotten).
+    /// The count includes internal slots, like those of pending awaits and callbacks,
+    /// and slots whose values were dropped but whose delete has not been sent yet.
+    ///
+    /// ```rust
+    /// # use px_wsdom_core::Browser;
+    /// async fn example(browser: &Browser) {
+    ///     let held = browser.remote_heap_size().await.unwrap();
+    ///     assert!(held >= browser.stats().live_values);
+    /// }
+    /// ```
+    pub fn remote_heap_size(&self) -> RemoteHeapSize {
+        let mut link = self.0.lock();
+        let ret_id = link.start_query();
+        link.emit_heap_size(ret_id);
+        link.wake_outgoing();
+        RemoteHeapSize {
+            ret_id,
+            browser: self.to_owned(),
+            deadline: Deadline::new(link.default_timeout),
+        }
+    }
+}
+
+/// A [Future] that resolves to the number of slots held by the JS client. Created by [Browser::remote_heap_size].
+pub struct RemoteHeapSize {
+    ret_id: u64,
+    browser: Browser,
+    deadline: Deadline,
+}
+
+impl RemoteHeapSize {
+    /// Fail with [RetrieveError::TimedOut] if the JS client has not replied `timeout` after the first poll,
+    /// overriding the Browser's [default timeout][Browser::with_default_timeout].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.deadline = Deadline::new(Some(timeout));
+        self
+    }
+}
+
+impl Future for RemoteHeapSize {
+    type Output = Result<usize, RetrieveError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut core::task::Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut link = this.browser.0.lock();
+        let value = match link.poll_query(this.ret_id, &mut this.deadline, cx) {
+            Poll::Ready(Ok(value)) => value,
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            Poll::Pending => return Poll::Pending,
+        };
+        let json = value.split_once(':').map_or("", |(_, json)| json);
+        match serde_json::from_str(json) {
+            Ok(size) => Poll::Ready(Ok(size)),
+            Err(e) => {
+                // The reply is malformed, so the client cannot be trusted anymore.
+                link.kill(Error::DataDeserialize(e));
+                Poll::Ready(Err(RetrieveError::Disconnected))
+            }
+        }
+    }
+}
+
+impl Drop for RemoteHeapSize {
+    fn drop(&mut self) {
+        self.browser.0.lock().retrievals.remove(&self.ret_id);
+    }
+}
+
+impl BrowserInternal {
+    /// Count a new handle to slot `id`, created by the code at `location`.
+    pub(crate) fn count_value(&mut self, id: u64, location: &'static Location<'static>) {
+        self.counters.live_values += 1;
+        #[cfg(feature = "leak-tracker")]
+        self.leaks.track(id, HandleKind::Value, location);
+        #[cfg(not(feature = "leak-tracker"))]
+        let _ = (id, location);
+    }
+    /// Count the end of the last handle to slot `id`.
+    pub(crate) fn uncount_value(&mut self, id: u64) {
+        self.counters.live_values = self.counters.live_values.saturating_sub(1);
+        #[cfg(feature = "leak-tracker")]
+        self.leaks.untrack(id);
+        #[cfg(not(feature = "leak-tracker"))]
+        let _ = id;
+    }
+    pub(crate) fn count_sent(&mut self, frame: &str) {
+        self.counters.frames_sent += 1;
+        self.counters.bytes_sent += frame.len() as u64;
+    }
+    pub(crate) fn count_received(&mut self, len: usize) {
+        self.counters.frames_received += 1;
+        self.counters.bytes_received += len as u64;
+    }
+    pub(crate) fn count_callback(&mut self, ret_id: u64, location: &'static Location<'static>) {
+        self.counters.callbacks.insert(ret_id);
+        #[cfg(feature = "leak-tracker")]
+        self.leaks.track(ret_id, HandleKind::Callback, location);
+        #[cfg(not(feature = "leak-tracker"))]
+        let _ = location;
+    }
+    pub(crate) fn uncount_callback(&mut self, ret_id: u64) {
+        self.counters.callbacks.remove(&ret_id);
+        #[cfg(feature = "leak-tracker")]
+        self.leaks.untrack(ret_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::task::{Context, Poll};
+    use futures_util::{FutureExt, task::noop_waker_ref};
+
+    use crate::{
+        Browser, RetrieveError, callback::new_callback, js_types::JsValue, test_support::sent,
+    };
+
+    #[test]
+    fn stats_follow_values_and_traffic() {
+        let mut browser = Browser::new();
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let a = browser.call_function("a", [], false);
+        let b = a.clone();
+        let c = a.duplicate();
+        let (_callback, func) = new_callback::<JsValue>(&browser);
+        let mut retrieval = c.retrieve_json();
+        assert!(retrieval.poll_unpin(&mut cx).is_pending());
+        let stats = browser.stats();
+        assert_eq!(stats.live_values, 3);
+        assert_eq!(stats.callbacks, 1);
+        assert_eq!(stats.pending_retrievals, 1);
+        drop((a, b, func));
+        assert_eq!(browser.stats().live_values, 1);
+
+        let frame = sent(&mut browser);
+        let mut heap_size = browser.remote_heap_size();
+        assert!(heap_size.poll_unpin(&mut cx).is_pending());
+        let query = sent(&mut browser);
+        assert!(query.ends_with("_w.z());\n"));
+        let ret_id = query.trim_start_matches("_w.r(").split(',').next().unwrap();
+        browser.receive_incoming_message(alloc::format!("p{ret_id}:5"));
+        assert!(matches!(heap_size.poll_unpin(&mut cx), Poll::Ready(Ok(5))));
+
+        let stats = browser.stats();
+        assert_eq!(stats.frames_sent, 2);
+        assert_eq!(stats.bytes_sent, (frame.len() + query.len()) as u64);
+        assert_eq!(stats.frames_received, 1);
+        assert_eq!(stats.pending_retrievals, 1);
+    }
+
+    #[test]
+    fn malformed_heap_size_fails() {
+        let browser = Browser::new();
+        let mut heap_size = browser.remote_heap_size();
+        let mut cx = Context::from_waker(noop_waker_ref());
+        assert!(heap_size.poll_unpin(&mut cx).is_pending());
+        browser.receive_incoming_message("p2:\"many\"".into());
+        assert!(matches!(
+            heap_size.poll_unpin(&mut cx),
+            Poll::Ready(Err(RetrieveError::Disconnected))
+        ));
+        assert!(browser.is_closed());
+    }
+}
@@ -0,0 +1,362 @@
+use alloc::{string::String, vec::Vec};
+use base64::Engine;
+use serde::{
+    Deserialize,
+    de::{
+        self, DeserializeOwned, Error as _, IntoDeserializer, Visitor,
+        value::{MapDeserializer, SeqDeserializer},
+    },
+    forward_to_deserialize_any,
+};
+use serde_json::{Error, Map, Value};
+
+/// Decode a value sent by the JS client in the [tagged encoding][super].
+pub fn from_str<T: DeserializeOwned>(json: &str) -> Result<T, Error> {
+    let value: Value = serde_json::from_str(json)?;
+    from_value(&value)
+}
+
+/// Decode a value in the [tagged encoding][super] that has already been parsed as JSON.
+pub fn from_value<'de, T: Deserialize<'de>>(value: &'de Value) -> Result<T, Error> {
+    T::deserialize(Deserializer(value))
+}
+
+/// A [serde::Deserializer] reading a parsed JSON value in the [tagged encoding][super].
+#[derive(Clone, Copy)]
+pub struct Deserializer<'de>(pub &'de Value);
+
+/// The name of [Raw], which the [Deserializer] hands the JSON value as is.
+const RAW: &str = "$wsdom::tagged::Raw";
+
+/// A value still in the tagged encoding, to be decoded later.
+pub(crate) struct Raw(pub(crate) Value);
+
+impl<'de> Deserialize<'de> for Raw {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct RawVisitor;
+        impl<'de> Visitor<'de> for RawVisitor {
+            type Value = Raw;
+            fn expecting(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                f.write_str("a tagged value")
+            }
+            fn visit_newtype_struct<D: de::Deserializer<'de>>(
+                self,
+                deserializer: D,
+            ) -> Result<Raw, D::Error> {
+                Value::deserialize(deserializer).map(Raw)
+            }
+        }
+        deserializer.deserialize_newtype_struct(RAW, RawVisitor)
+    }
+}
+
+/// A JSON value with its tag, if any, interpreted.
+enum Node<'de> {
+    Plain(&'de Value),
+    Undefined,
+    Float(f64),
+    BigInt(&'de str),
+    Date(&'de Value),
+    Map(&'de [Value]),
+    Seq(&'de [Value]),
+    Bytes(Vec<u8>),
+    Object(&'de Map<String, Value>),
+}
+
+impl<'de> Deserializer<'de> {
+    fn node(self) -> Result<Node<'de>, Error> {
+        let object = match self.0 {
+            Value::Object(object) => object,
+            value => return Ok(Node::Plain(value)),
+        };
+        let Some(tag) = object.get("$") else {
+            return Ok(Node::Object(object));
+        };
+        let v = object.get("v").unwrap_or(&Value::Null);
+        let str = || {
+            v.as_str()
+                .ok_or_else(|| Error::custom("tagged value is not a string"))
+        };
+        let array = || {
+            v.as_array()
+                .map(Vec::as_slice)
+                .ok_or_else(|| Error::custom("tagged value is not an array"))
+        };
+        Ok(match tag.as_str().unwrap_or_default() {
+            "u" => Node::Undefined,
+            "n" => Node::Float(match str()? {
+                "NaN" => f64::NAN,
+                "Infinity" => f64::INFINITY,
+                "-Infinity" => f64::NEG_INFINITY,
+                "-0" => -0.0,
+                other => other.parse().map_err(Error::custom)?,
+            }),
+            "b" => Node::BigInt(str()?),
+            "d" => Node::Date(v),
+            "m" => Node::Map(array()?),
+            "s" | "t" => Node::Seq(array()?),
+            "y" => Node::Bytes(
+                base64::engine::general_purpose::STANDARD
+                    .decode(str()?)
+                    .map_err(Error::custom)?,
+            ),
+            "o" => Node::Object(
+                v.as_object()
+                    .ok_or_else(|| Error::custom("tagged value is not an object"))?,
+            ),
+            other => return Err(Error::custom(alloc::format!("unknown tag `{other}`"))),
+        })
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.node()? {
+            Node::Plain(Value::Array(items)) => visitor.visit_seq(seq(items)),
+            Node::Plain(value) => de::Deserializer::deserialize_any(value, visitor),
+            Node::Undefined => visitor.visit_unit(),
+            Node::Float(f) => visitor.visit_f64(f),
+            Node::BigInt(digits) => {
+                if let Ok(n) = digits.parse::<u64>() {
+                    visitor.visit_u64(n)
+                } else if let Ok(n) = digits.parse::<i64>() {
+                    visitor.visit_i64(n)
+                } else if let Ok(n) = digits.parse::<u128>() {
+                    visitor.visit_u128(n)
+                } else if let Ok(n) = digits.parse::<i128>() {
+                    visitor.visit_i128(n)
+                } else {
+                    Err(Error::custom(alloc::format!(
+                        "BigInt {digits} is out of range"
+                    )))
+                }
+            }
+            Node::Date(time) => Deserializer(time).deserialize_any(visitor),
+            Node::Map(entries) => {
+                visitor.visit_map(MapDeserializer::new(entries.iter().map(|entry| {
+                    match entry.as_array().map(Vec::as_slice) {
+                        Some([key, value]) => (Deserializer(key), Deserializer(value)),
+                        _ => (Deserializer(&Value::Null), Deserializer(&Value::Null)),
+                    }
+                })))
+            }
+            Node::Seq(items) => visitor.visit_seq(seq(items)),
+            Node::Bytes(bytes) => visitor.visit_seq(SeqDeserializer::new(bytes.into_iter())),
+            Node::Object(object) => visitor.visit_map(MapDeserializer::new(
+                object
+                    .iter()
+                    .map(|(key, value)| (Key(key), Deserializer(value))),
+            )),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.node()? {
+            Node::Plain(Value::Null) | Node::Undefined => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.node()? {
+            Node::Bytes(bytes) => visitor.visit_byte_buf(bytes),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match name {
+            RAW => visitor.visit_newtype_struct(self.0),
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match (name, self.node()?) {
+            // How `std::time::SystemTime` deserializes.
+            ("SystemTime", Node::Date(time)) => {
+                let millis = match Deserializer(time).node()? {
+                    Node::Plain(Value::Number(n)) => n.as_f64().unwrap_or(f64::NAN),
+                    _ => f64::NAN,
+                };
+                if millis.is_nan() || millis < 0.0 {
+                    return Err(Error::custom("the Date is invalid or before the epoch"));
+                }
+                let secs = (millis / 1000.0) as u64;
+                let nanos = ((millis - secs as f64 * 1000.0) * 1_000_000.0) as u64;
+                visitor.visit_map(MapDeserializer::new(
+                    fields.iter().copied().zip([secs, nanos]),
+                ))
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.node()? {
+            Node::Plain(Value::String(variant)) => {
+                visitor.visit_enum(variant.as_str().into_deserializer())
+            }
+            Node::Object(object) if object.len() == 1 => {
+                let (variant, value) = object.iter().next().unwrap();
+                visitor.visit_enum(Variant {
+                    variant,
+                    value: Deserializer(value),
+                })
+            }
+            _ => Err(Error::custom(
+                "expected a string or an object with one key for an enum",
+            )),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        unit unit_struct seq tuple tuple_struct map identifier
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Deserializer<'de> {
+    type Deserializer = Self;
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+fn seq<'de>(
+    items: &'de [Value],
+) -> SeqDeserializer<impl Iterator<Item = Deserializer<'de>>, Error> {
+    SeqDeserializer::new(items.iter().map(Deserializer))
+}
+
+/// An object key, which may stand for a number or bool (as in a `HashMap<u32, _>`).
+#[derive(Clone, Copy)]
+struct Key<'de>(&'de str);
+
+impl<'de> IntoDeserializer<'de, Error> for Key<'de> {
+    type Deserializer = Self;
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! parse_key {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                match self.0.parse() {
+                    Ok(parsed) => visitor.$visit(parsed),
+                    Err(_) => self.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Key<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_borrowed_str(self.0)
+    }
+
+    parse_key! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_enum(self.0.into_deserializer())
+    }
+
+    forward_to_deserialize_any! {
+        char str string bytes byte_buf option unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// An externally tagged enum variant, `{"variant": value}`.
+struct Variant<'de> {
+    variant: &'de str,
+    value: Deserializer<'de>,
+}
+
+impl<'de> de::EnumAccess<'de> for Variant<'de> {
+    type Error = Error;
+    type Variant = Deserializer<'de>;
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), Error> {
+        let variant = seed.deserialize(Key(self.variant))?;
+        Ok((variant, self.value))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Deserializer<'de> {
+    type Error = Error;
+    fn unit_variant(self) -> Result<(), Error> {
+        de::Deserialize::deserialize(self)
+    }
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_any(self, visitor)
+    }
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_any(self, visitor)
+    }
+}
@@ -0,0 +1,117 @@
+//! The tagged JSON encoding used for values crossing between Rust and the JS client.
+//!
+//! Plain JSON cannot carry `undefined`, `NaN`, `Infinity`, `-0`, `BigInt`s, `Date`s, `Map`s, `Set`s,
+//! or binary data. In the tagged encoding these become objects with a `"$"` key naming what they are:
+//!
+//! | JS value | Tagged JSON | Rust value |
+//! |---|---|---|
+//! | `undefined` | `{"$":"u"}` | `()`, or `None` |
+//! | `NaN`, `Infinity`, `-Infinity`, `-0` | `{"$":"n","v":"NaN"}` | [f64] |
+//! | `BigInt` | `{"$":"b","v":"12345678901234567890"}` | any integer type it fits in |
+//! | `Date` | `{"$":"d","v":1700000000000}` | [f64] milliseconds since the epoch, or `std::time::SystemTime` |
+//! | `Map` | `{"$":"m","v":[[key,value],...]}` | a map, with keys of any type |
+//! | `Set` | `{"$":"s","v":[...]}` | a sequence or set |
+//! | `Uint8Array`, `ArrayBuffer`, `DataView` | `{"$":"y","v":"<base64>"}` | `Vec<u8>`, or bytes |
+//! | other typed arrays | `{"$":"t","k":"Float32Array","v":[...]}` | a sequence |
+//! | object with its own `"$"` key | `{"$":"o","v":{...}}` | a map or struct |
+//!
+//! Retrievals are always decoded this way; see [from_str].
+//!
+//! In the other direction, [SerdeToJs][crate::SerdeToJs] turns `NaN`, infinities, and `-0`
+//! into their JS counterparts, 64- and 128-bit integers (including `usize` and `isize`) into `BigInt`s whatever their value,
+//! maps whose keys are not strings into `Map`s, and bytes (like `serde_bytes::ByteBuf`) into `Uint8Array`s.
+//! Maps with string keys and structs still become plain objects.
+
+mod de;
+mod ser;
+
+pub(crate) use de::Raw;
+pub use de::{Deserializer, from_str, from_value};
+pub(crate) use ser::write;
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use alloc::{collections::BTreeMap, format, string::String, vec::Vec};
+    use std::time::{Duration, SystemTime};
+
+    use super::from_str;
+    use crate::serialize::{SerdeToJs, UseInJsCodeWriter};
+
+    #[test]
+    fn decodes_what_json_loses() {
+        let floats: Vec<f64> =
+            from_str(r#"[{"$":"n","v":"NaN"},{"$":"n","v":"Infinity"},{"$":"n","v":"-0"},1.5]"#)
+                .unwrap();
+        assert!(floats[0].is_nan() && floats[1] == f64::INFINITY && floats[2].is_sign_negative());
+        let map: BTreeMap<u64, String> =
+            from_str(r#"{"$":"m","v":[[1,"a"],[{"$":"b","v":"18446744073709551615"},"max"]]}"#)
+                .unwrap();
+        assert_eq!(map[&u64::MAX], "max");
+        let keyed: BTreeMap<u32, i128> =
+            from_str(r#"{"7":{"$":"b","v":"-1180591620717411303424"}}"#).unwrap();
+        assert_eq!(keyed[&7], -(1 << 70));
+        let bytes: Vec<u8> = from_str(r#"{"$":"y","v":"AP8H"}"#).unwrap();
+        assert_eq!(bytes, [0, 255, 7]);
+        let time: SystemTime = from_str(r#"{"$":"d","v":1700000000123}"#).unwrap();
+        assert_eq!(
+            time,
+            SystemTime::UNIX_EPOCH + Duration::from_millis(1700000000123)
+        );
+        let set: Vec<Option<u8>> = from_str(r#"{"$":"s","v":[1,{"$":"u"},null]}"#).unwrap();
+        assert_eq!(set, [Some(1), None, None]);
+
+        #[derive(serde::Deserialize)]
+        struct Odd {
+            #[serde(rename = "$")]
+            dollar: u8,
+            a: Option<u8>,
+        }
+        let odd: Odd = from_str(r#"{"$":"o","v":{"$":1,"a":{"$":"u"}}}"#).unwrap();
+        assert_eq!((odd.dollar, odd.a), (1, None));
+    }
+
+    #[test]
+    fn encodes_what_json_loses() {
+        fn both<T: serde::Serialize + ?Sized>(value: &T) -> (String, String) {
+            let value = UseInJsCodeWriter(&SerdeToJs(value));
+            (format!("{value}"), format!("{value:#}"))
+        }
+        assert_eq!(
+            both(&[f64::NAN, -0.0, 1.5]),
+            (
+                "[NaN,-0,1.5]".into(),
+                r#"["t",[{"$":"n","v":"NaN"},{"$":"n","v":"-0"},1.5]]"#.into()
+            )
+        );
+        assert_eq!(
+            both(&BTreeMap::from([(u64::MAX, "max")])),
+            (
+                r#"new Map([[18446744073709551615n,"max"]])"#.into(),
+                r#"["t",{"$":"m","v":[[{"$":"b","v":"18446744073709551615"},"max"]]}]"#.into()
+            )
+        );
+        assert_eq!(
+            both(&BTreeMap::from([("$", 1)])),
+            (r#"{"$":1}"#.into(), r#"["t",{"$":"o","v":{"$":1}}]"#.into())
+        );
+        assert_eq!(
+            both(&(1u64 << 53, "plain")),
+            (
+                r#"[9007199254740992n,"plain"]"#.into(),
+                r#"["t",[{"$":"b","v":"9007199254740992"},"plain"]]"#.into()
+            )
+        );
+        assert_eq!(
+            both(&(1u64, 1u32)),
+            (r#"[1n,1]"#.into(), r#"["t",[{"$":"b","v":"1"},1]]"#.into())
+        );
+        // Passed directly, an integer is a `JsNumber`.
+        let value = UseInJsCodeWriter(&1u64);
+        assert_eq!(
+            (format!("{value}"), format!("{value:#}")),
+            ("1".into(), r#"["l",1]"#.into())
+        );
+    }
+}
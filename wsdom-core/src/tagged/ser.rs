@@ -0,0 +1,445 @@
+use core::fmt::{self, Display, Write as _};
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use base64::Engine;
+use serde::{Serialize, ser};
+use serde_json::Error;
+
+/// Write `value` as JS source or, if the formatter's alternate flag is set, as an opcode operand:
+/// `["l",json]` if plain JSON carries it faithfully, `["t",json]` in the [tagged encoding][super] otherwise.
+pub(crate) fn write<T: Serialize + ?Sized>(value: &T, buf: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut writer = Writer::new(buf.alternate());
+    value.serialize(&mut writer).map_err(|_| fmt::Error)?;
+    match (buf.alternate(), writer.tagged) {
+        (false, _) => buf.write_str(&writer.out),
+        (true, false) => write!(buf, "[\"l\",{}]", writer.out),
+        (true, true) => write!(buf, "[\"t\",{}]", writer.out),
+    }
+}
+
+/// Writes a value as JS source, or as tagged JSON.
+struct Writer {
+    out: String,
+    json: bool,
+    /// Whether any tag has been written.
+    tagged: bool,
+}
+
+impl Writer {
+    fn new(json: bool) -> Self {
+        Self {
+            out: String::new(),
+            json,
+            tagged: false,
+        }
+    }
+    fn write_string(&mut self, s: &str) -> Result<(), Error> {
+        self.out.push_str(&serde_json::to_string(s)?);
+        Ok(())
+    }
+    /// Write `{"$":tag,"v":value}`, where `value` is already JSON.
+    fn write_tag(&mut self, tag: &str, value: impl Display) {
+        self.tagged = true;
+        let _ = write!(self.out, r#"{{"$":"{tag}","v":{value}}}"#);
+    }
+    /// Write an integer as a `Number`, or as a `BigInt` if `big`.
+    ///
+    /// `big` goes by the Rust type, not the value, so a field keeps its JS type whatever it holds.
+    fn write_integer(&mut self, big: bool, n: impl Display) {
+        match (big, self.json) {
+            (false, _) => {
+                let _ = write!(self.out, "{n}");
+            }
+            (true, false) => {
+                let _ = write!(self.out, "{n}n");
+            }
+            (true, true) => self.write_tag("b", format_args!("\"{n}\"")),
+        }
+    }
+    fn write_float(
+        &mut self,
+        f: f64,
+        finite: impl FnOnce() -> Result<String, Error>,
+    ) -> Result<(), Error> {
+        let special = match f {
+            f if f.is_nan() => "NaN",
+            f64::INFINITY => "Infinity",
+            f64::NEG_INFINITY => "-Infinity",
+            f if f == 0.0 && f.is_sign_negative() => "-0",
+            _ => {
+                self.out.push_str(&finite()?);
+                return Ok(());
+            }
+        };
+        match self.json {
+            false => self.out.push_str(special),
+            true => self.write_tag("n", format_args!("\"{special}\"")),
+        }
+        Ok(())
+    }
+    fn open_object(&mut self) -> usize {
+        let start = self.out.len();
+        self.out.push('{');
+        start
+    }
+    /// Close an object opened at `start`, escaping it if one of its keys is `"$"`.
+    fn close_object(&mut self, start: usize, dollar: bool) {
+        self.out.push('}');
+        if dollar && self.json {
+            self.tagged = true;
+            self.out.insert_str(start, r#"{"$":"o","v":"#);
+            self.out.push('}');
+        }
+    }
+    fn write_key(&mut self, key: &str) -> Result<bool, Error> {
+        self.write_string(key)?;
+        self.out.push(':');
+        Ok(key == "$")
+    }
+    /// Serialize `value` on its own, returning its text.
+    fn serialize_apart<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<String, Error> {
+        let mut apart = Writer::new(self.json);
+        value.serialize(&mut apart)?;
+        self.tagged |= apart.tagged;
+        Ok(apart.out)
+    }
+}
+
+macro_rules! serialize_integers {
+    ($($method:ident: $ty:ty => $big:literal,)*) => {
+        $(
+            fn $method(self, v: $ty) -> Result<(), Error> {
+                self.write_integer($big, v);
+                Ok(())
+            }
+        )*
+    };
+}
+
+impl<'a> ser::Serializer for &'a mut Writer {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Compound<'a>;
+    type SerializeTuple = Compound<'a>;
+    type SerializeTupleStruct = Compound<'a>;
+    type SerializeTupleVariant = Compound<'a>;
+    type SerializeMap = MapCompound<'a>;
+    type SerializeStruct = Compound<'a>;
+    type SerializeStructVariant = Compound<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.out.push_str(if v { "true" } else { "false" });
+        Ok(())
+    }
+    serialize_integers! {
+        serialize_i8: i8 => false,
+        serialize_i16: i16 => false,
+        serialize_i32: i32 => false,
+        serialize_i64: i64 => true,
+        serialize_i128: i128 => true,
+        serialize_u8: u8 => false,
+        serialize_u16: u16 => false,
+        serialize_u32: u32 => false,
+        serialize_u64: u64 => true,
+        serialize_u128: u128 => true,
+    }
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        self.write_float(v.into(), || serde_json::to_string(&v))
+    }
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        self.write_float(v, || serde_json::to_string(&v))
+    }
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        self.write_string(v.encode_utf8(&mut [0; 4]))
+    }
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        self.write_string(v)
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        if self.json {
+            let base64 = base64::engine::general_purpose::STANDARD.encode(v);
+            self.write_tag("y", format_args!("\"{base64}\""));
+        } else {
+            self.out.push_str("new Uint8Array([");
+            for byte in v {
+                let _ = write!(self.out, "{byte},");
+            }
+            self.out.push_str("])");
+        }
+        Ok(())
+    }
+    fn serialize_none(self) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<(), Error> {
+        self.out.push_str("null");
+        Ok(())
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.write_string(variant)
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        let start = self.open_object();
+        let dollar = self.write_key(variant)?;
+        value.serialize(&mut *self)?;
+        self.close_object(start, dollar);
+        Ok(())
+    }
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'a>, Error> {
+        self.out.push('[');
+        Ok(Compound::new(self, None))
+    }
+    fn serialize_tuple(self, len: usize) -> Result<Compound<'a>, Error> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Compound<'a>, Error> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, Error> {
+        let start = self.open_object();
+        let dollar = self.write_key(variant)?;
+        self.out.push('[');
+        Ok(Compound::new(self, Some((start, dollar))))
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapCompound<'a>, Error> {
+        Ok(MapCompound {
+            writer: self,
+            entries: Vec::new(),
+            key: None,
+        })
+    }
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Compound<'a>, Error> {
+        let start = self.open_object();
+        Ok(Compound::new(self, None).object(start))
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, Error> {
+        let outer = self.open_object();
+        let dollar = self.write_key(variant)?;
+        let start = self.open_object();
+        Ok(Compound::new(self, Some((outer, dollar))).object(start))
+    }
+    fn collect_str<T: Display + ?Sized>(self, value: &T) -> Result<(), Error> {
+        self.write_string(&value.to_string())
+    }
+}
+
+/// A sequence or struct being written, possibly inside an enum variant's `{"variant":...}`.
+struct Compound<'a> {
+    writer: &'a mut Writer,
+    first: bool,
+    /// For structs, where the object starts and whether it has a `"$"` key.
+    object: Option<(usize, bool)>,
+    /// For variants, the same about the object around it.
+    variant: Option<(usize, bool)>,
+}
+
+impl<'a> Compound<'a> {
+    fn new(writer: &'a mut Writer, variant: Option<(usize, bool)>) -> Self {
+        Self {
+            writer,
+            first: true,
+            object: None,
+            variant,
+        }
+    }
+    fn object(mut self, start: usize) -> Self {
+        self.object = Some((start, false));
+        self
+    }
+    fn separate(&mut self) {
+        if !core::mem::take(&mut self.first) {
+            self.writer.out.push(',');
+        }
+    }
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.separate();
+        value.serialize(&mut *self.writer)
+    }
+    fn field<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), Error> {
+        self.separate();
+        if self.writer.write_key(key)? {
+            if let Some((_, dollar)) = &mut self.object {
+                *dollar = true;
+            }
+        }
+        value.serialize(&mut *self.writer)
+    }
+    fn finish(self) -> Result<(), Error> {
+        match self.object {
+            Some((start, dollar)) => self.writer.close_object(start, dollar),
+            None => self.writer.out.push(']'),
+        }
+        if let Some((start, dollar)) = self.variant {
+            self.writer.close_object(start, dollar);
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(key, value)
+    }
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(key, value)
+    }
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+/// A map being written. It becomes an object if all its keys are strings, and a `Map` otherwise.
+struct MapCompound<'a> {
+    writer: &'a mut Writer,
+    entries: Vec<(String, String)>,
+    key: Option<String>,
+}
+
+impl ser::SerializeMap for MapCompound<'_> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = Some(self.writer.serialize_apart(key)?);
+        Ok(())
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let value = self.writer.serialize_apart(value)?;
+        let key = self.key.take().unwrap_or_default();
+        self.entries.push((key, value));
+        Ok(())
+    }
+    fn end(self) -> Result<(), Error> {
+        let writer = self.writer;
+        // Strings are the only keys written starting with a quote.
+        if self.entries.iter().all(|(key, _)| key.starts_with('"')) {
+            let start = writer.open_object();
+            let mut dollar = false;
+            for (i, (key, value)) in self.entries.iter().enumerate() {
+                if i != 0 {
+                    writer.out.push(',');
+                }
+                dollar |= key == "\"$\"";
+                let _ = write!(writer.out, "{key}:{value}");
+            }
+            writer.close_object(start, dollar);
+            return Ok(());
+        }
+        let mut entries = String::new();
+        for (key, value) in &self.entries {
+            let _ = write!(entries, "[{key},{value}],");
+        }
+        entries.pop();
+        match writer.json {
+            false => {
+                let _ = write!(writer.out, "new Map([{entries}])");
+            }
+            true => writer.write_tag("m", format_args!("[{entries}]")),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,25 @@
+//! Helpers shared by the unit tests.
+
+use alloc::{string::String, vec::Vec};
+use core::task::{Context, Poll};
+use futures_util::{StreamExt, task::noop_waker_ref};
+
+use crate::Browser;
+
+/// The next frame `browser` sends, or an empty string if it has nothing to send.
+pub(crate) fn sent(browser: &mut Browser) -> String {
+    match browser.poll_next_unpin(&mut Context::from_waker(noop_waker_ref())) {
+        Poll::Ready(Some(commands)) => commands,
+        _ => String::new(),
+    }
+}
+
+/// Every frame `browser` has to send, in order.
+pub(crate) fn sent_frames(browser: &mut Browser) -> Vec<String> {
+    let mut cx = Context::from_waker(noop_waker_ref());
+    let mut frames = Vec::new();
+    while let Poll::Ready(Some(frame)) = browser.poll_next_unpin(&mut cx) {
+        frames.push(frame);
+    }
+    frames
+}
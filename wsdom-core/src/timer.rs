@@ -0,0 +1,178 @@
+use core::{future::Future, pin::Pin, task::Context, time::Duration};
+
+use alloc::{boxed::Box, sync::Arc};
+
+use crate::link::{Browser, BrowserInternal};
+
+/// A future that completes once a [Timer]'s duration has passed.
+pub type Sleep = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// A source of delays, provided by the async runtime.
+///
+/// `wsdom-core` does not depend on any async runtime, so it cannot sleep by itself.
+/// Give the Browser a Timer with [Browser::with_timer],
+/// then put a deadline on a single retrieval with `RetrieveFuture::timeout`,
+/// or on every retrieval with [Browser::with_default_timeout].
+///
+/// Implemented for every `Fn(Duration) -> Sleep` closure.
+/// The timer is called while the Browser is locked, so it must not use the Browser.
+///
+/// ```rust
+/// # use core::time::Duration;
+/// # use px_wsdom_core::Browser;
+/// # fn sleep(d: Duration) -> px_wsdom_core::Sleep { unimplemented!() }
+/// // With Tokio, `sleep` would be `|d| Box::pin(tokio::time::sleep(d)) as px_wsdom_core::Sleep`.
+/// let browser = Browser::new()
+///     .with_timer(sleep)
+///     .with_default_timeout(Duration::from_secs(10));
+/// ```
+pub trait Timer: Send + Sync {
+    /// Create a future that completes after `duration`.
+    fn sleep(&self, duration: Duration) -> Sleep;
+}
+
+impl<F: Fn(Duration) -> Sleep + Send + Sync> Timer for F {
+    fn sleep(&self, duration: Duration) -> Sleep {
+        self(duration)
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct SharedTimer(Arc<dyn Timer>);
+
+impl SharedTimer {
+    pub(crate) fn sleep(&self, duration: Duration) -> Sleep {
+        self.0.sleep(duration)
+    }
+}
+
+impl core::fmt::Debug for SharedTimer {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Timer").finish_non_exhaustive()
+    }
+}
+
+impl Browser {
+    /// Let futures waiting on the JS client time out, using `timer` to sleep.
+    ///
+    /// Without a timer, deadlines never pass.
+    pub fn with_timer(self, timer: impl Timer + 'static) -> Self {
+        self.0.lock().timer = Some(SharedTimer(Arc::new(timer)));
+        self
+    }
+    /// Fail retrievals and [sync][Self::sync]s with [RetrieveError::TimedOut][crate::RetrieveError::TimedOut]
+    /// if the JS client has not replied within `timeout`, unless they set their own.
+    ///
+    /// Needs a [timer][Self::with_timer].
+    pub fn with_default_timeout(self, timeout: Duration) -> Self {
+        self.0.lock().default_timeout = Some(timeout);
+        self
+    }
+}
+
+/// A deadline that starts counting the first time it is polled.
+pub(crate) struct Deadline {
+    duration: Option<Duration>,
+    sleep: Option<Sleep>,
+}
+
+impl Deadline {
+    pub(crate) fn new(duration: Option<Duration>) -> Self {
+        Self {
+            duration,
+            sleep: None,
+        }
+    }
+    /// Whether the deadline has passed. Registers `cx` to be woken when it does.
+    pub(crate) fn poll_elapsed(&mut self, link: &BrowserInternal, cx: &mut Context<'_>) -> bool {
+        if self.sleep.is_none() {
+            let (Some(duration), Some(SharedTimer(timer))) = (self.duration, link.timer.as_ref())
+            else {
+                return false;
+            };
+            self.sleep = Some(timer.sleep(duration));
+        }
+        self.sleep
+            .as_mut()
+            .is_some_and(|sleep| sleep.as_mut().poll(cx).is_ready())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{boxed::Box, sync::Arc};
+    use core::{
+        future::IntoFuture,
+        sync::atomic::{AtomicBool, Ordering},
+        task::{Context, Poll},
+        time::Duration,
+    };
+    use futures_util::{FutureExt, StreamExt, future::poll_fn, task::noop_waker_ref};
+
+    use crate::{Browser, RetrieveError};
+
+    /// A browser whose deadlines all pass once `fired` is set.
+    fn browser_with_manual_timer() -> (Browser, Arc<AtomicBool>) {
+        let fired = Arc::new(AtomicBool::new(false));
+        let timer = {
+            let fired = fired.clone();
+            move |_| -> super::Sleep {
+                let fired = fired.clone();
+                Box::pin(poll_fn(move |_| match fired.load(Ordering::SeqCst) {
+                    true => Poll::Ready(()),
+                    false => Poll::Pending,
+                }))
+            }
+        };
+        (Browser::new().with_timer(timer), fired)
+    }
+
+    #[test]
+    fn timed_out_retrieval_discards_late_reply() {
+        let (mut browser, fired) = browser_with_manual_timer();
+        let value = browser.call_function("f", [], false);
+        let mut retrieval = value.retrieve_json().timeout(Duration::from_secs(1));
+        let mut cx = Context::from_waker(noop_waker_ref());
+        assert!(retrieval.poll_unpin(&mut cx).is_pending());
+        assert!(browser.poll_next_unpin(&mut cx).is_ready());
+
+        fired.store(true, Ordering::SeqCst);
+        assert!(matches!(
+            retrieval.poll_unpin(&mut cx),
+            Poll::Ready(Err(RetrieveError::TimedOut))
+        ));
+        assert!(browser.0.lock().retrievals.is_empty());
+
+        // The value threw after all; the slot holding the exception must be freed.
+        browser.receive_incoming_message(r#"p3:{"error":9}"#.into());
+        assert_eq!(
+            browser.poll_next_unpin(&mut cx),
+            Poll::Ready(Some("_w.dm([9]);\n".into()))
+        );
+        assert!(browser.take_error().is_none());
+    }
+
+    #[test]
+    fn timed_out_await_frees_cell() {
+        let (mut browser, fired) = browser_with_manual_timer();
+        let promise = browser.call_function("f", [], false);
+        let mut waiting = promise.into_future().timeout(Duration::from_secs(1));
+        let mut cx = Context::from_waker(noop_waker_ref());
+        assert!(waiting.poll_unpin(&mut cx).is_pending());
+        assert!(browser.poll_next_unpin(&mut cx).is_ready());
+
+        fired.store(true, Ordering::SeqCst);
+        assert!(matches!(
+            waiting.poll_unpin(&mut cx),
+            Poll::Ready(Err(RetrieveError::TimedOut))
+        ));
+        drop(waiting);
+        assert!(browser.0.lock().retrievals.is_empty());
+        assert_eq!(
+            browser.poll_next_unpin(&mut cx),
+            Poll::Ready(Some("_w.dm([4]);\n".into()))
+        );
+        browser.receive_incoming_message("p3:0".into());
+        assert!(browser.take_error().is_none());
+    }
+}
@@ -1,4 +1,4 @@
-use std::{collections::BTreeMap, fmt::Display};
+use std::{collections::BTreeMap, fmt::Display, time::Duration};
 
 use itertools::Itertools;
 use sha3::Digest;
@@ -10,7 +10,47 @@ pub enum ModuleKind {
     Injected,
     ESM,
 }
+// The client must be generated with the same encoding (and capabilities) as the Rust `Browser`.
+pub use wsdom_core::{Capabilities, Encoding, PROTOCOL_VERSION};
+// A client for a Browser with custom host names must be generated with the same names.
+use portal_jit_host_names::HostMethodNames;
+pub use portal_jit_host_names::MappedHostMethodNames;
+use wsdom_core::protocol_names::{validate_host_method_names, WsdomMethod, ALL_METHODS};
+/// Options for [gen_with_options].
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    pub encoding: Encoding,
+    /// Restrict which globals, constructors, methods, and properties the client lets commands reach.
+    ///
+    /// Enforcement needs the interpreter, so this requires [Encoding::Opcode].
+    /// Use the same value with `Browser::with_capabilities` to also check commands before they are sent.
+    pub capabilities: Option<Capabilities>,
+    /// Send every exception caught by the client back to Rust, where it is yielded by `Browser::exceptions`.
+    pub report_exceptions: bool,
+    /// Reject the promise returned by an RPC method if Rust has not replied within this duration.
+    ///
+    /// By default the client waits forever.
+    pub rpc_timeout: Option<Duration>,
+    /// Name the members of the client's `_w` object with this mapping instead of the canonical names.
+    ///
+    /// Use the same mapping with `Browser::with_host_names`.
+    pub host_names: Option<MappedHostMethodNames>,
+}
 pub fn gen<D: Display>(modules: &[Module<D>], rpcs: &BTreeMap<String, usize>) -> String {
+    gen_with_options(modules, rpcs, &Options::default())
+}
+pub fn gen_with_options<D: Display>(
+    modules: &[Module<D>],
+    rpcs: &BTreeMap<String, usize>,
+    options: &Options,
+) -> String {
+    assert!(
+        options.capabilities.is_none() || options.encoding == Encoding::Opcode,
+        "capabilities can only be enforced by an opcode-mode client"
+    );
+    if let Some(names) = &options.host_names {
+        validate_host_method_names(names).expect("host names must cover every WSDOM method");
+    }
     let modules2 = modules
         .iter()
         .map(|a| format!("{a}", a = &a.name))
@@ -51,10 +91,11 @@ pub fn gen<D: Display>(modules: &[Module<D>], rpcs: &BTreeMap<String, usize>) ->
                 .iter()
                 .map(|(a, v)| format!(
                     r#"public {a}({}): Promise<Value>{{
-                        return new Promise((then) => {{
+                        return new Promise((then, reject) => {{
                             var i = 0;
                             while(this.#callbacks.has(i))i++;
                             this.#callbacks.set(i,then);
+                            this.#expire(i,then,reject);
                             var s = `r{a}:${{i}};{};`;
                             (this.#sender)(s);
                         }});
@@ -66,6 +107,97 @@ pub fn gen<D: Display>(modules: &[Module<D>], rpcs: &BTreeMap<String, usize>) ->
                 ))
                 .join("\n")
         )
+        .replace(
+            "$$h",
+            match options.encoding {
+                Encoding::Source => {
+                    "\t\tconst fn = new this.#Function('_w', msg);\n\t\tawait fn(this.#api);"
+                }
+                Encoding::Opcode => {
+                    "\t\tfor (const line of msg.split(\"\\n\")) {\n\t\t\tif (line !== \"\") this.#step(JSON.parse(line));\n\t\t}"
+                }
+            },
+        )
+        .replace(
+            "$$m",
+            &ALL_METHODS
+                .into_iter()
+                .map(|method| {
+                    let key = match &options.host_names {
+                        Some(names) => names.property(method).unwrap().name().to_owned(),
+                        None => method.to_string(),
+                    };
+                    let value = match method {
+                        WsdomMethod::Import => format!("this.#{method}"),
+                        _ => format!("this.#{method}.bind(this)"),
+                    };
+                    format!(
+                        "        {}: {value},",
+                        serde_json::to_string(&key).unwrap()
+                    )
+                })
+                .join("\n")
+        )
+        .replace("$$t", &options.report_exceptions.to_string())
+        .replace("$$v", &PROTOCOL_VERSION.to_string())
+        .replace(
+            "$$n",
+            match options.encoding {
+                Encoding::Source => "source",
+                Encoding::Opcode => "opcode",
+            },
+        )
+        .replace(
+            "$$f",
+            &serde_json::to_string(
+                &[
+                    (options.report_exceptions, "exceptions"),
+                    (options.capabilities.is_some(), "capabilities"),
+                    (options.rpc_timeout.is_some(), "rpc-timeout"),
+                ]
+                .into_iter()
+                .filter_map(|(enabled, feature)| enabled.then_some(feature))
+                .collect_vec(),
+            )
+            .unwrap(),
+        )
+        .replace(
+            "$$i",
+            &serde_json::to_string(
+                &modules2
+                    .iter()
+                    .map(|m| hex::encode(sha3::Sha3_256::digest(m.as_bytes())))
+                    .collect_vec(),
+            )
+            .unwrap(),
+        )
+        .replace(
+            "$$o",
+            &options
+                .rpc_timeout
+                .map_or("null".to_owned(), |t| t.as_millis().to_string()),
+        )
+        .replace(
+            "$$p",
+            &match &options.capabilities {
+                None => "null".to_owned(),
+                Some(c) => {
+                    let set = |names: &mut dyn Iterator<Item = &str>| {
+                        format!(
+                            "new Set({})",
+                            serde_json::to_string(&names.collect_vec()).unwrap()
+                        )
+                    };
+                    format!(
+                        "{{globals: {}, constructors: {}, methods: {}, properties: {}}}",
+                        set(&mut c.globals()),
+                        set(&mut c.constructors()),
+                        set(&mut c.methods()),
+                        set(&mut c.properties()),
+                    )
+                }
+            },
+        )
         .replace(
             "$$a",
             &format!(
@@ -95,4 +227,104 @@ mod tests {
         assert!(!generated.contains("WSDOMConnectWebSocket"));
         assert!(!generated.contains("new WebSocket"));
     }
+
+    #[test]
+    fn opcode_client_never_constructs_function() {
+        let options = Options {
+            encoding: Encoding::Opcode,
+            capabilities: None,
+            report_exceptions: false,
+            rpc_timeout: None,
+            host_names: None,
+        };
+        let generated = gen_with_options::<&str>(&[], &BTreeMap::new(), &options);
+
+        assert!(generated.contains("this.#step(JSON.parse(line))"));
+        assert!(!generated.contains("new this.#Function"));
+        assert!(gen::<&str>(&[], &BTreeMap::new()).contains("new this.#Function('_w', msg)"));
+    }
+
+    #[test]
+    fn capabilities_are_embedded_in_client() {
+        let options = Options {
+            encoding: Encoding::Opcode,
+            capabilities: Some(
+                Capabilities::new()
+                    .allow_global("document")
+                    .allow_property("title"),
+            ),
+            report_exceptions: false,
+            rpc_timeout: None,
+            host_names: None,
+        };
+        let generated = gen_with_options::<&str>(&[], &BTreeMap::new(), &options);
+
+        assert!(generated.contains(
+            r#"{globals: new Set(["document"]), constructors: new Set([]), methods: new Set([]), properties: new Set(["title"])}"#
+        ));
+        assert!(gen::<&str>(&[], &BTreeMap::new())
+            .contains("static #capabilities: Capabilities | null = null;"));
+    }
+
+    #[test]
+    fn exception_reporting_is_opt_in() {
+        let options = Options {
+            report_exceptions: true,
+            ..Options::default()
+        };
+        let generated = gen_with_options::<&str>(&[], &BTreeMap::new(), &options);
+
+        assert!(generated.contains("static #reportExceptions: boolean = true;"));
+        assert!(gen::<&str>(&[], &BTreeMap::new())
+            .contains("static #reportExceptions: boolean = false;"));
+    }
+
+    #[test]
+    fn host_names_key_the_api_object() {
+        let options = Options {
+            host_names: Some(MappedHostMethodNames::new(
+                ALL_METHODS.map(|m| (m.to_string(), format!("not-a-name-{m}"))),
+            )),
+            ..Options::default()
+        };
+        let generated = gen_with_options::<&str>(&[], &BTreeMap::new(), &options);
+
+        assert!(generated.contains(r#""not-a-name-g": this.#g.bind(this),"#));
+        assert!(generated.contains(r#""not-a-name-x": this.#x,"#));
+        assert!(gen::<&str>(&[], &BTreeMap::new()).contains(r#""g": this.#g.bind(this),"#));
+    }
+
+    #[test]
+    fn client_says_hello() {
+        let options = Options {
+            report_exceptions: true,
+            ..Options::default()
+        };
+        let generated = gen_with_options(
+            &[Module {
+                name: "mod",
+                kind: ModuleKind::Injected,
+            }],
+            &BTreeMap::new(),
+            &options,
+        );
+
+        assert!(generated.contains(&format!(
+            r#"{{version: {PROTOCOL_VERSION}, encoding: "source", features: ["exceptions"], members: Object.keys(this.#api), opcodes: WSDOM.#opcodes, imports: ["{}"]}}"#,
+            hex::encode(sha3::Sha3_256::digest(b"mod"))
+        )));
+    }
+
+    #[test]
+    fn rpc_timeout_is_embedded_in_client() {
+        let options = Options {
+            rpc_timeout: Some(Duration::from_secs(5)),
+            ..Options::default()
+        };
+        let generated = gen_with_options::<&str>(&[], &BTreeMap::new(), &options);
+
+        assert!(generated.contains("static #rpcTimeout: number | null = 5000;"));
+        assert!(gen::<&str>(&[], &BTreeMap::new())
+            .contains("static #rpcTimeout: number | null = null;"));
+    }
 }
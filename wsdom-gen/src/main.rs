@@ -2,5 +2,15 @@ fn main() -> std::io::Result<()> {
     let mut args = std::env::args();
     args.next();
     let p = args.next().unwrap();
-    return std::fs::write(p, px_wsdom_gen::gen::<&str>(&[],&Default::default()));
+    let options = px_wsdom_gen::Options {
+        encoding: match args.next().as_deref() {
+            Some("opcode") => px_wsdom_gen::Encoding::Opcode,
+            _ => px_wsdom_gen::Encoding::Source,
+        },
+        capabilities: None,
+        report_exceptions: false,
+        rpc_timeout: None,
+        host_names: None,
+    };
+    return std::fs::write(p, px_wsdom_gen::gen_with_options::<&str>(&[],&Default::default(), &options));
 }
@@ -1,6 +1,13 @@
 type Id = number;
 type Value = unknown;
-type SendMessage = (msg: string) => void;
+// Binary frames (for `retrieve_bytes`) are sent as `Uint8Array`s.
+type SendMessage = (msg: string | Uint8Array) => void;
+type Operand = ["l", Value] | ["t", Value] | ["r", Id] | ["n", string] | ["u"] | ["a", Operand[]];
+type Opcode = [string, ...any[]];
+// A `#rb` reply: how many more chunks Rust has room for, and what to call when it makes more room.
+type ByteStream = {credit: number, resume?: () => void};
+type CallbackOptions = {preventDefault?: boolean, stopPropagation?: boolean, once?: boolean, throttle?: number, debounce?: number, keys?: string[], target?: string};
+type Capabilities = {globals: Set<string>, constructors: Set<string>, methods: Set<string>, properties: Set<string>};
 export class WSDOM{
 	#sender: SendMessage;
 	#values: Map<Id, { value: Value, error: boolean }>;
@@ -8,8 +15,7 @@ export class WSDOM{
     #next_value: Id;
 	#Function: {new(w: "_w", msg: string): (api: any) => any};
     public async handleIncomingMessage(msg: string) {
-		const fn = new this.#Function('_w', msg);
-		await fn(this.#api);
+$$h
 	}
 		#args: $$a;
 	constructor(sender: SendMessage, args: $$a, Function: { new(w: "_w", msg: string): (api: any) => any } = globalThis.Function as any) {
@@ -20,6 +26,8 @@ export class WSDOM{
 		this.#Function = Function;
 		this.#args = $$a;
         Object.freeze(this);
+		// Lets Rust check that this client speaks its protocol before relying on it.
+		sender(`h${JSON.stringify({version: $$v, encoding: "$$n", features: $$f, members: Object.keys(this.#api), opcodes: WSDOM.#opcodes, imports: $$i})}`);
 	}
     #allocate (v: Value): Id {
         var i = this.#next_value;
@@ -42,40 +50,410 @@ export class WSDOM{
 	#d (id: Id) {
 		this.#values.delete(id);
 	}
+	#dm (ids: Id[]) {
+		for (const id of ids) this.#values.delete(id);
+	}
+	#dr (ranges: [Id, Id][]) {
+		for (const [from, to] of ranges) {
+			for (let id = from; id <= to; id++) this.#values.delete(id);
+		}
+	}
 	#r (id: Id, val: Value) {
-		const valJson = JSON.stringify(val);
+		const valJson = JSON.stringify(val, WSDOM.#tag);
 		(this.#sender)(`p${id}:${valJson}`);
 	}
+	// The tagged encoding (see `wsdom_core::tagged`): what JSON would lose becomes `{"$": tag, "v": ...}`.
+	static #tags: WeakSet<object> = new WeakSet();
+	static #tagged(tag: string, v?: Value, k?: string): object {
+		const t = v === undefined ? {$: tag} : k === undefined ? {$: tag, v} : {$: tag, k, v};
+		WSDOM.#tags.add(t);
+		return t;
+	}
+	static #tag(this: any, key: string, v: Value): Value {
+		// `v` has been through `toJSON`, which turns a Date into a string.
+		const o = this[key];
+		if (o instanceof Date) return WSDOM.#tagged("d", o.getTime());
+		switch (typeof v) {
+			case "undefined": return WSDOM.#tagged("u");
+			case "bigint": return WSDOM.#tagged("b", v.toString());
+			case "number": return Number.isFinite(v) && !Object.is(v, -0) ? v : WSDOM.#tagged("n", Object.is(v, -0) ? "-0" : String(v));
+			case "object": break;
+			default: return v;
+		}
+		if (v === null || WSDOM.#tags.has(v as object)) return v;
+		if (v instanceof Map) return WSDOM.#tagged("m", [...v]);
+		if (v instanceof Set) return WSDOM.#tagged("s", [...v]);
+		if (v instanceof ArrayBuffer) return WSDOM.#tagged("y", WSDOM.#base64(new Uint8Array(v)));
+		if (ArrayBuffer.isView(v)) {
+			if (v instanceof Uint8Array || v instanceof Uint8ClampedArray || v instanceof DataView) {
+				return WSDOM.#tagged("y", WSDOM.#base64(new Uint8Array(v.buffer, v.byteOffset, v.byteLength)));
+			}
+			return WSDOM.#tagged("t", Array.from(v as unknown as ArrayLike<Value>), v.constructor.name);
+		}
+		if (!Array.isArray(v) && Object.prototype.hasOwnProperty.call(v, "$")) {
+			const copy = {...v};
+			WSDOM.#tags.add(copy);
+			return WSDOM.#tagged("o", copy);
+		}
+		return v;
+	}
+	static #untag(v: any): Value {
+		if (Array.isArray(v)) return v.map(WSDOM.#untag);
+		if (v === null || typeof v !== "object") return v;
+		const object = (o: object) => Object.fromEntries(Object.entries(o).map(([k, x]) => [k, WSDOM.#untag(x)]));
+		if (!Object.prototype.hasOwnProperty.call(v, "$")) return object(v);
+		switch (v.$) {
+			case "u": return undefined;
+			case "n": return Number(v.v);
+			case "b": return BigInt(v.v);
+			case "d": return new Date(WSDOM.#untag(v.v));
+			case "m": return new Map(v.v.map(([k, x]: [any, any]) => [WSDOM.#untag(k), WSDOM.#untag(x)]));
+			case "s": return new Set(v.v.map(WSDOM.#untag));
+			case "y": return WSDOM.#unbase64(v.v);
+			case "o": return object(v.v);
+			default: throw new TypeError(`Unknown WSDOM tag ${v.$}`);
+		}
+	}
+	static #base64(bytes: Uint8Array): string {
+		let binary = "";
+		for (let i = 0; i < bytes.length; i += 0x8000) {
+			binary += String.fromCharCode(...bytes.subarray(i, i + 0x8000));
+		}
+		return btoa(binary);
+	}
+	static #unbase64(data: string): Uint8Array {
+		const fromBase64 = (Uint8Array as any).fromBase64;
+		return fromBase64 ? fromBase64(data) : Uint8Array.from(atob(data), (c) => c.charCodeAt(0));
+	}
+	// The bytes of `value`, for `JsValue::retrieve_bytes`: `c<id>:`-prefixed binary chunks, then a reply.
+	#rb (id: Id, value: Id, credit: number) {
+		const stream: ByteStream = {credit};
+		this.#byteStreams.set(id, stream);
+		const live = () => this.#byteStreams.get(id) === stream;
+		// Wait until Rust has room for another chunk; false once it has stopped listening.
+		const ready = async (): Promise<boolean> => {
+			while (live() && stream.credit <= 0) await new Promise((resume) => stream.resume = () => resume(undefined));
+			return live();
+		};
+		const send = async (bytes: Uint8Array): Promise<boolean> => {
+			const header = new TextEncoder().encode(`c${id}:`);
+			for (let i = 0; i < bytes.length; i += WSDOM.#chunkSize) {
+				if (!await ready()) return false;
+				stream.credit--;
+				const chunk = bytes.subarray(i, i + WSDOM.#chunkSize);
+				const frame = new Uint8Array(header.length + chunk.length);
+				frame.set(header);
+				frame.set(chunk, header.length);
+				(this.#sender)(frame);
+			}
+			return true;
+		};
+		const end = (error?: unknown) => {
+			if (!live()) return;
+			this.#byteStreams.delete(id);
+			(this.#sender)(`p${id}:${JSON.stringify(error === undefined ? {value: null} : {error: this.#allocate(error)})}`);
+		};
+		const pump = async (): Promise<void> => {
+			const v = this.#g(value);
+			if (v instanceof Blob) {
+				const reader = v.stream().getReader();
+				for (;;) {
+					if (!await ready()) return reader.cancel();
+					const {done, value} = await reader.read();
+					if (done) return end();
+					if (!await send(value)) return reader.cancel();
+				}
+			}
+			// Copied before the first `await`, so commands that come after cannot change what is sent.
+			let bytes: Uint8Array;
+			if (v instanceof ArrayBuffer) bytes = new Uint8Array(v.slice(0));
+			else if (ArrayBuffer.isView(v)) bytes = new Uint8Array(v.buffer.slice(v.byteOffset, v.byteOffset + v.byteLength));
+			else if (typeof ImageData !== "undefined" && v instanceof ImageData) bytes = v.data.slice();
+			else throw new TypeError("WSDOM can only retrieve the bytes of an ArrayBuffer, typed array, DataView, Blob, or ImageData");
+			if (await send(bytes)) end();
+		};
+		pump().catch(end);
+	}
+	// Room for `credit` more chunks of a `#rb` reply, or null once Rust has stopped listening.
+	#rc (id: Id, credit: number | null) {
+		const stream = this.#byteStreams.get(id);
+		if (stream === undefined) return;
+		if (credit === null) this.#byteStreams.delete(id);
+		else stream.credit += credit;
+		stream.resume?.();
+	}
+	#byteStreams: Map<Id, ByteStream> = new Map();
+	static #chunkSize = 1 << 20;
+	// `CallbackOptions`: wraps the callback function in slot `func`, applying them synchronously as events arrive.
+	#cw (func: Id, options: CallbackOptions) {
+		const f = this.#g(func) as (e: any) => Value;
+		let done = false;
+		let last = -Infinity;
+		let timer: ReturnType<typeof setTimeout> | undefined;
+		this.#s(func, (e: any) => {
+			if (done) return;
+			if (options.keys && !options.keys.includes(e?.key)) return;
+			if (options.target !== undefined && !(typeof e?.target?.closest === "function" && e.target.closest(options.target) !== null)) return;
+			if (options.preventDefault) e?.preventDefault?.();
+			if (options.stopPropagation) e?.stopPropagation?.();
+			if (options.once) done = true;
+			if (options.throttle !== undefined) {
+				const now = Date.now();
+				if (now - last < options.throttle) return;
+				last = now;
+			}
+			if (options.debounce !== undefined) {
+				clearTimeout(timer);
+				timer = setTimeout(() => f(e), options.debounce);
+				return;
+			}
+			return f(e);
+		});
+	}
+	// Bytes uploaded by `Browser::new_uint8array` and friends.
+	#b (id: Id, data: string, kind: "u8" | "buffer" | "blob", type: string | null) {
+		this.#try(id, () => {
+			const bytes = WSDOM.#unbase64(data);
+			switch (kind) {
+				case "u8": return bytes;
+				case "buffer": return bytes.buffer;
+				case "blob": return new Blob([bytes], type === null ? {} : {type});
+			}
+		});
+	}
     #rp (id: Id, val: Value) {
         var cb = this.#callbacks.get(id);
         if(cb !== undefined){
+            this.#callbacks.delete(id);
             cb(val)
         }
 	}
-	#c (id: Id): {value: Value} | {slot: Id} | undefined  {
+	// Set by `wsdom-gen`; milliseconds to wait for Rust to reply to an RPC call, or null to wait forever.
+	static #rpcTimeout: number | null = $$o;
+	#expire (id: Id, then: (value: Value) => void, reject: (reason: Error) => void) {
+		if (WSDOM.#rpcTimeout === null) return;
+		setTimeout(() => {
+			// The id may have been reused by a later call; only expire our own.
+			if (this.#callbacks.get(id) !== then) return;
+			// A late reply finds no callback and is ignored by `#rp`.
+			this.#callbacks.delete(id);
+			reject(new Error("WSDOM RPC call timed out"));
+		}, WSDOM.#rpcTimeout);
+	}
+	#c (id: Id): {value: Value} | {error: Id} | undefined  {
 		var w = this.#values.get(id);
 		if(w?.error){
-			return {slot: this.#allocate(w.value)};
+			return {error: this.#allocate(w.value)};
 		}else{
 			return {value: w?.value}
 		}
 	}
 	#e (id: Id, value: Value) {
 		this.#values.set(id, { value, error: true })
+		this.#failures.set(id, value);
+		// Nothing drains the map if `Browser::sync` is never called; keep only the newest.
+		if (this.#failures.size > WSDOM.#maxFailures) this.#failures.delete(this.#failures.keys().next().value as Id);
+		if (WSDOM.#reportExceptions) (this.#sender)(`e${id}:${JSON.stringify(WSDOM.#describe(value))}`);
+	}
+	// Exceptions stored since the last checkpoint, drained by `#k`.
+	#failures: Map<Id, Value> = new Map();
+	static #maxFailures = 1024;
+	#k () {
+		const failures = [...this.#failures].map(([id, error]) => ({id, ...WSDOM.#describe(error)}));
+		this.#failures.clear();
+		return failures;
+	}
+	#z () {
+		return this.#values.size;
+	}
+	static #describe (error: any) {
+		// Reading properties of an arbitrary thrown value may itself throw.
+		const field = (f: () => unknown) => {
+			try {
+				const v = f();
+				return v == null ? null : String(v);
+			} catch {
+				return null;
+			}
+		};
+		return {
+			name: field(() => error?.name) ?? typeof error,
+			message: field(() => error?.message ?? error) ?? "",
+			stack: field(() => error?.stack),
+		};
 	}
     #x: {[key: string]: Value} = (self => Object.freeze({__proto__: null, $$x}))(this);
 
+	// Opcode interpreter, used instead of `Function` by clients generated in opcode mode.
+	// The opcodes it knows are listed in the hello.
+	static #opcodes = ["f", "n", "m", "c", "g", "p", "s", "o", "d", "dm", "dr", "r", "k", "z", "x", "rp", "aw", "at", "cb", "cs", "acb", "acr", "acs", "b", "rb", "rc", "cw"];
+	#step(op: Opcode) {
+		switch (op[0]) {
+			case "f": return this.#try(op[1], () => {
+				const [base, fn] = this.#lookup(op[2], "globals");
+				return Reflect.apply(fn, base, this.#spread(op[3], op[4]));
+			});
+			case "n": return this.#try(op[1], () => Reflect.construct(this.#lookup(op[2], "constructors")[1], this.#spread(op[3], op[4])));
+			case "m": return this.#try(op[1], () => {
+				WSDOM.#allow("methods", op[3]);
+				const base: any = this.#g(op[2]);
+				return Reflect.apply(base[op[3]], base, this.#spread(op[4], op[5]));
+			});
+			case "c": return this.#try(op[1], () => Reflect.apply(this.#g(op[2]) as Function, undefined, this.#spread(op[3], op[4])));
+			case "g": return this.#try(op[1], () => (this.#operand(op[2]) as any)[this.#property(op[3])]);
+			case "p": {
+				(this.#operand(op[1]) as any)[this.#property(op[2])] = this.#operand(op[3]);
+				return;
+			}
+			case "s": return this.#try(op[1], () => this.#operand(op[2]));
+			case "o": return this.#try(op[1], () => op.length === 4
+				? WSDOM.#unary[op[2]](this.#operand(op[3]))
+				: WSDOM.#binary[op[2]](this.#operand(op[3]), this.#operand(op[4])));
+			case "d": return this.#d(op[1]);
+			case "dm": return this.#dm(op[1]);
+			case "dr": return this.#dr(op[1]);
+			case "r": return this.#r(op[1], this.#c(op[2]));
+			case "k": return this.#r(op[1], this.#k());
+			case "z": return this.#r(op[1], this.#z());
+			case "x": return this.#try(op[1], () => this.#x[`_${op[2]}`]);
+			case "rp": return this.#rp(op[1], this.#operand(op[2]));
+			case "aw": {
+				const [, cell, ret, id] = op;
+				const slot: {$?: {e: Value, r: number}} = {};
+				this.#s(cell, slot);
+				const settle = (r: number) => (e: Value) => {
+					slot.$ = {e, r};
+					this.#r(ret, 0);
+				};
+				try {
+					Promise.prototype.then.call(this.#g(id) as Promise<Value>, settle(0), settle(1));
+				} catch ($) {
+					settle(1)($);
+				}
+				return;
+			}
+			case "at": {
+				const v = (this.#g(op[2]) as {$: {e: Value, r: number}}).$;
+				if (v.r) this.#e(op[1], v.e);
+				else this.#s(op[1], v.e);
+				return this.#d(op[2]);
+			}
+			case "cb": {
+				const [, arr, func, ret, paths, events = true] = op;
+				// Each path's keys, read off the argument and sent with the call.
+				const keys: PropertyKey[][] | undefined = (paths as string[] | undefined)?.map((path) => path.split(".").map((k) => /^\d+$/.test(k) ? Number(k) : k));
+				keys?.forEach((path) => path.forEach((k) => WSDOM.#allow("properties", k)));
+				this.#s(arr, []);
+				this.#s(func, (e: Value) => {
+					if (events) (this.#g(arr) as Value[]).push(e);
+					this.#r(ret, keys ? keys.map((path) => path.reduce((v: any, k) => v?.[k], e)) : 0);
+				});
+				return;
+			}
+			case "cs": return this.#s(op[1], (this.#g(op[2]) as Value[]).shift());
+			case "acb": {
+				const [, arr, func, ret] = op;
+				this.#s(arr, []);
+				this.#s(func, (e: Value) => new Promise((resolve, reject) => {
+					(this.#g(arr) as Value[]).push([e, (rejected: boolean, v: Value) => (rejected ? reject : resolve)(v)]);
+					this.#r(ret, 0);
+				}));
+				return;
+			}
+			case "acr": return (this.#g(op[1]) as [Value, (rejected: boolean, v: Value) => void][]).forEach(([, settle]) => settle(true, op[2]));
+			case "acs": {
+				const [e, settle] = (this.#g(op[3]) as [Value, Value][]).shift() as [Value, Value];
+				this.#s(op[1], e);
+				return this.#s(op[2], settle);
+			}
+			case "b": return this.#b(op[1], op[2], op[3], op[4]);
+			case "rb": return this.#rb(op[1], op[2], op[3]);
+			case "rc": return this.#rc(op[1], op[2]);
+			case "cw": return this.#cw(op[1], op[2]);
+			default: throw new TypeError(`Unknown WSDOM opcode ${op[0]}`);
+		}
+	}
+	#try(id: Id, f: () => Value) {
+		try {
+			this.#s(id, f());
+		} catch ($) {
+			this.#e(id, $);
+		}
+	}
+	#operand(o: Operand): Value {
+		switch (o[0]) {
+			case "l": return o[1];
+			case "t": return WSDOM.#untag(o[1]);
+			case "r": return this.#g(o[1]);
+			case "n": return this.#lookup(o[1], "globals")[1];
+			case "u": return undefined;
+			case "a": return o[1].map((o) => this.#operand(o));
+		}
+	}
+	#spread(args: Operand[], variadic: boolean): Value[] {
+		const values = args.map((o) => this.#operand(o));
+		if (variadic && values.length > 0) values.push(...(values.pop() as Iterable<Value>));
+		return values;
+	}
+	#property(o: Operand): PropertyKey {
+		const key = this.#operand(o) as PropertyKey;
+		WSDOM.#allow("properties", key);
+		return key;
+	}
+	/** Resolves a dotted global name, returning the object it was read from along with the value. */
+	#lookup(path: string, kind: "globals" | "constructors"): [any, any] {
+		WSDOM.#allow(kind, path);
+		let base: any = undefined;
+		let value: any = globalThis;
+		for (const key of path.split(".")) {
+			base = value;
+			value = base[key];
+		}
+		return [base, value];
+	}
+	// Set by `wsdom-gen`.
+	static #reportExceptions: boolean = $$t;
+	// Set by `wsdom-gen`; null allows everything.
+	static #capabilities: Capabilities | null = $$p;
+	static #allow(kind: keyof Capabilities, name: PropertyKey) {
+		const capabilities = WSDOM.#capabilities;
+		if (capabilities === null || typeof name === "number" || (typeof name === "string" && capabilities[kind].has(name))) return;
+		throw new TypeError(`WSDOM capability denied: ${kind} ${String(name)}`);
+	}
+	static #unary: Readonly<Record<string, (a: any) => Value>> = Object.freeze(Object.assign(Object.create(null), {
+		"-": (a: any) => -a,
+		"~": (a: any) => ~a,
+		"!": (a: any) => !a,
+	}));
+	static #binary: Readonly<Record<string, (a: any, b: any) => Value>> = Object.freeze(Object.assign(Object.create(null), {
+		"+": (a: any, b: any) => a + b,
+		"-": (a: any, b: any) => a - b,
+		"*": (a: any, b: any) => a * b,
+		"/": (a: any, b: any) => a / b,
+		"%": (a: any, b: any) => a % b,
+		"**": (a: any, b: any) => a ** b,
+		"&": (a: any, b: any) => a & b,
+		"|": (a: any, b: any) => a | b,
+		"^": (a: any, b: any) => a ^ b,
+		"<<": (a: any, b: any) => a << b,
+		">>": (a: any, b: any) => a >> b,
+		">>>": (a: any, b: any) => a >>> b,
+		"&&": (a: any, b: any) => a && b,
+		"||": (a: any, b: any) => a || b,
+		"==": (a: any, b: any) => a == b,
+		"!=": (a: any, b: any) => a != b,
+		"===": (a: any, b: any) => a === b,
+		"!==": (a: any, b: any) => a !== b,
+		">": (a: any, b: any) => a > b,
+		">=": (a: any, b: any) => a >= b,
+		"<": (a: any, b: any) => a < b,
+		"<=": (a: any, b: any) => a <= b,
+	}));
+
+    // Keys set by `wsdom-gen`, from its host names.
     #api = Object.freeze({
         __proto__: null,
-        a: this.#a.bind(this),
-        g: this.#g.bind(this),
-        s: this.#s.bind(this),
-        d: this.#d.bind(this),
-        r: this.#r.bind(this),
-        rp: this.#rp.bind(this),
-        c: this.#c.bind(this),
-        e: this.#e.bind(this),
-        x: this.#x,
+$$m
     });
 
     static{
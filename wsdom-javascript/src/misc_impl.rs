@@ -3,7 +3,8 @@ use core::future::IntoFuture;
 use crate::{Promise, PromiseLike};
 
 use super::Array;
-use wsdom_core::{r#await::Await, Cast, JsCast, ToJs};
+use alloc::vec::Vec;
+use wsdom_core::{r#await::Await, Cast, JsCast, Retrieve, RetrieveError, ToJs};
 
 impl<'a, T, U, const N: usize> ToJs<Array<T>> for [&'a U; N]
 where
@@ -11,10 +12,13 @@ where
     U: ToJs<T>,
 {
 }
+impl<T: Retrieve> Retrieve for Array<T> {
+    type Output = Vec<T::Output>;
+}
 macro_rules! promise_like {
     ($pr:ident) => {
         impl<T: JsCast> IntoFuture for $pr<T>{
-            type Output = T;
+            type Output = Result<T, RetrieveError>;
         
             type IntoFuture = Cast<Await,T>;
         
@@ -430,6 +430,7 @@ impl<'a> Context<'a> {
         let getter_name_ident = new_ident_safe(&format!("get_{}", to_snake_case(field_name_str)));
         let ret = self.convert_type(self.simplify_type(getter.ret.to_owned()));
         Some(quote! {
+            #[track_caller]
             pub fn #getter_name_ident (&self) -> #ret {
                 __wsdom_load_ts_macro::JsCast::unchecked_from_js(
                     __wsdom_load_ts_macro::JsObject::js_get_field(self.as_ref(), &#field_name_str)
@@ -458,6 +459,7 @@ impl<'a> Context<'a> {
         };
 
         Some(quote! {
+            #[track_caller]
             pub fn #setter_name_ident (&self, value: #ty_tokens) {
                 __wsdom_load_ts_macro::JsObject::js_set_field(self.as_ref(), &#field_name_str, __wsdom_load_ts_macro::UpcastWorkaround::new( value ).cast() )
             }
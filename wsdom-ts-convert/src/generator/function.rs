@@ -16,6 +16,7 @@ impl<'a> Context<'a> {
         let function_generics = self.make_sig_generics(&df.generics.args);
         let function = df.name;
         quote! {
+            #[track_caller]
             pub fn #function_name_ident #function_generics (browser: &__wsdom_load_ts_macro::Browser, #(#arg_names_sig: #arg_types,)*) -> #ret {
                 __wsdom_load_ts_macro::JsCast::unchecked_from_js(
                     browser.call_function(#function, [
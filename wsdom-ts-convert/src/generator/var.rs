@@ -13,6 +13,7 @@ impl<'a> Context<'a> {
         let function_name = new_ident_safe(name);
         let ty = self.convert_type(self.simplify_type(ty.to_owned()));
         quote! {
+            #[track_caller]
             pub fn #function_name (browser: &__wsdom_load_ts_macro::Browser) -> #ty {
                 __wsdom_load_ts_macro::JsCast::unchecked_from_js(
                     browser.value_from_raw_code(core::format_args!(#name))
@@ -31,6 +32,7 @@ impl<'a> Context<'a> {
         let ret_ty = self.convert_type(self.simplify_type(ret_ty.to_owned()));
         let name = new_ident_safe(class_name);
         quote! {
+            #[track_caller]
             pub fn #name (browser: &__wsdom_load_ts_macro::Browser, #(#arg_names_sig : #arg_types,)*) -> #ret_ty {
                 __wsdom_load_ts_macro::JsCast::unchecked_from_js(
                     browser.call_constructor(